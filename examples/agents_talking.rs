@@ -1,10 +1,27 @@
 //! Three agents: Asker asks questions, Responder answers, Observer watches.
+//!
+//! Who talks to whom, and what they say, comes from `data/agents_talking.toml`.
 use agentropic_core::{Agent, AgentContext, AgentId, AgentResult};
+use agentropic_examples::{
+    AgentRegistry, ContextExt, FromManifest, Performative, RuntimeBuilder, RuntimeExt, Switchboard, Tracing,
+};
 use agentropic_runtime::prelude::*;
 use async_trait::async_trait;
+use serde::Deserialize;
+use std::time::Duration;
 
-struct AskerAgent { id: AgentId, asked: bool }
-impl AskerAgent { fn new() -> Self { Self { id: AgentId::new(), asked: false } } }
+const MANIFEST: &str = "data/agents_talking.toml";
+
+#[derive(Deserialize)]
+struct AskerParams { to: String, question: String }
+
+struct AskerAgent { id: AgentId, params: AskerParams, asked: bool }
+
+impl FromManifest for AskerAgent {
+    const KIND: &'static str = "asker";
+    type Params = AskerParams;
+    fn from_params(params: AskerParams) -> Self { Self { id: AgentId::new(), params, asked: false } }
+}
 
 #[async_trait]
 impl Agent for AskerAgent {
@@ -15,8 +32,15 @@ impl Agent for AskerAgent {
     }
     async fn execute(&mut self, ctx: &AgentContext) -> AgentResult<()> {
         if !self.asked {
-            println!("  [Asker] → \"What patterns does Agentropic support?\"");
-            ctx.send_message("responder", "query", "What patterns does Agentropic support?");
+            println!("  [Asker] → \"{}\"", self.params.question);
+            let (to, question) = (&self.params.to, &self.params.question);
+            let answer = ctx.request(to, Performative::Query, question, Duration::from_secs(2));
+            tokio::spawn(async move {
+                match answer.await {
+                    Ok(reply) => println!("  [Asker] ← Answer: \"{}\"", reply.content),
+                    Err(err) => println!("  [Asker] ✗ {}", err),
+                }
+            });
             self.asked = true;
         }
         tokio::time::sleep(std::time::Duration::from_millis(200)).await;
@@ -26,14 +50,18 @@ impl Agent for AskerAgent {
         println!("  [Asker] Goodbye!");
         Ok(())
     }
-    async fn handle_message(&mut self, _ctx: &AgentContext, _s: &str, _p: &str, content: &str) -> AgentResult<()> {
-        println!("  [Asker] ← Answer: \"{}\"", content);
-        Ok(())
-    }
 }
 
-struct ResponderAgent { id: AgentId }
-impl ResponderAgent { fn new() -> Self { Self { id: AgentId::new() } } }
+#[derive(Deserialize)]
+struct ResponderParams { answer: String }
+
+struct ResponderAgent { id: AgentId, answer: String }
+
+impl FromManifest for ResponderAgent {
+    const KIND: &'static str = "responder";
+    type Params = ResponderParams;
+    fn from_params(params: ResponderParams) -> Self { Self { id: AgentId::new(), answer: params.answer } }
+}
 
 #[async_trait]
 impl Agent for ResponderAgent {
@@ -42,25 +70,35 @@ impl Agent for ResponderAgent {
         println!("  [Responder] Waiting for questions...");
         Ok(())
     }
-    async fn execute(&mut self, _ctx: &AgentContext) -> AgentResult<()> {
-        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
-        Ok(())
-    }
+    async fn execute(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
     async fn shutdown(&mut self, _ctx: &AgentContext) -> AgentResult<()> {
         println!("  [Responder] Goodbye!");
         Ok(())
     }
-    async fn handle_message(&mut self, ctx: &AgentContext, sender: &str, _p: &str, content: &str) -> AgentResult<()> {
+    async fn handle_message(&mut self, ctx: &AgentContext, _s: &str, perf: &str, content: &str) -> AgentResult<()> {
+        if perf.parse() != Ok(Performative::Query) {
+            ctx.reply(Performative::NotUnderstood, content)?;
+            return Ok(());
+        }
         println!("  [Responder] ← \"{}\"", content);
-        let answer = "Hierarchy, Swarm, Coalition, Market, Federation, Team, Holarchy, and Blackboard.";
-        println!("  [Responder] → \"{}\"", answer);
-        ctx.send_message(sender, "inform", answer);
+        println!("  [Responder] → \"{}\"", self.answer);
+        ctx.reply(Performative::Inform, &self.answer)?;
         Ok(())
     }
 }
 
-struct ObserverAgent { id: AgentId, ticks: u32 }
-impl ObserverAgent { fn new() -> Self { Self { id: AgentId::new(), ticks: 0 } } }
+#[derive(Deserialize)]
+struct ObserverParams { remark_after: u32 }
+
+struct ObserverAgent { id: AgentId, remark_after: u32, ticks: u32 }
+
+impl FromManifest for ObserverAgent {
+    const KIND: &'static str = "observer";
+    type Params = ObserverParams;
+    fn from_params(params: ObserverParams) -> Self {
+        Self { id: AgentId::new(), remark_after: params.remark_after, ticks: 0 }
+    }
+}
 
 #[async_trait]
 impl Agent for ObserverAgent {
@@ -71,7 +109,7 @@ impl Agent for ObserverAgent {
     }
     async fn execute(&mut self, _ctx: &AgentContext) -> AgentResult<()> {
         self.ticks += 1;
-        if self.ticks == 5 { println!("  [Observer] The agents are communicating!"); }
+        if self.ticks == self.remark_after { println!("  [Observer] The agents are communicating!"); }
         tokio::time::sleep(std::time::Duration::from_millis(200)).await;
         Ok(())
     }
@@ -83,14 +121,31 @@ impl Agent for ObserverAgent {
 
 #[tokio::main]
 async fn main() -> Result<(), RuntimeError> {
+    run().await
+}
+
+pub async fn run() -> Result<(), RuntimeError> {
     println!("=== Agents Talking ===\n");
 
-    let runtime = Runtime::new();
-    runtime.spawn(Box::new(ResponderAgent::new()), "responder").await?;
-    runtime.spawn(Box::new(AskerAgent::new()), "asker").await?;
-    runtime.spawn(Box::new(ObserverAgent::new()), "observer").await?;
+    let board = Switchboard::new();
+    // Print every message between managed agents as it is sent
+    board.add_middleware(Tracing::new());
+    let registry = AgentRegistry::new()
+        .register::<AskerAgent>()
+        .register::<ResponderAgent>()
+        .register::<ObserverAgent>();
+    let builder = match RuntimeBuilder::new(registry).with_switchboard(board).load(MANIFEST) {
+        Ok(builder) => builder,
+        Err(err) => {
+            println!("  {}", err);
+            return Ok(());
+        }
+    };
+    let (runtime, board) = builder.build().await?;
 
-    tokio::time::sleep(std::time::Duration::from_secs(3)).await;
+    if let Err(err) = runtime.run_until_idle(&board, Duration::from_secs(5)).await {
+        println!("  {}", err);
+    }
 
     runtime.shutdown().await?;
     println!("\n=== Done ===");
@@ -1,9 +1,22 @@
 //! Blackboard: Agents read and write to shared knowledge space to solve a problem.
 use agentropic_core::{Agent, AgentContext, AgentId, AgentResult};
+use agentropic_examples::{ContextExt, Performative, RuntimeExt, Switchboard, TypedHandler, TIMER};
 use agentropic_patterns::blackboard::Blackboard;
 use agentropic_runtime::prelude::*;
 use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Tells other agents which blackboard entry changed.
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "notice", rename_all = "snake_case")]
+enum Notice {
+    /// A sensor wrote a reading under `key`.
+    Updated { key: String },
+    /// The analyzer wrote its analysis of the reading under `key`.
+    Analyzed { key: String },
+}
 
 struct SensorAgent { id: AgentId, name: String, data: Vec<(&'static str, &'static str)>, index: usize, board: Arc<Mutex<Blackboard>> }
 impl SensorAgent {
@@ -15,23 +28,24 @@ impl SensorAgent {
 #[async_trait]
 impl Agent for SensorAgent {
     fn id(&self) -> &AgentId { &self.id }
-    async fn initialize(&mut self, _ctx: &AgentContext) -> AgentResult<()> {
+    async fn initialize(&mut self, ctx: &AgentContext) -> AgentResult<()> {
         println!("  [{}] Sensor online. {} readings to report.", self.name, self.data.len());
+        ctx.schedule_every(Duration::from_millis(600), "read")?;
         Ok(())
     }
-    async fn execute(&mut self, ctx: &AgentContext) -> AgentResult<()> {
-        if self.index < self.data.len() {
+    async fn execute(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
+    async fn shutdown(&mut self, _ctx: &AgentContext) -> AgentResult<()> {
+        println!("  [{}] Sensor offline.", self.name);
+        Ok(())
+    }
+    async fn handle_message(&mut self, ctx: &AgentContext, sender: &str, _p: &str, _content: &str) -> AgentResult<()> {
+        if sender == TIMER && self.index < self.data.len() {
             let (key, value) = self.data[self.index];
             self.board.lock().unwrap().write(key, value);
             println!("  [{}]  Wrote: {} = {}", self.name, key, value);
-            ctx.send_message("analyzer", "inform", &format!("updated:{}", key));
+            ctx.send_payload("analyzer", Performative::Inform, &Notice::Updated { key: key.to_string() })?;
             self.index += 1;
         }
-        tokio::time::sleep(std::time::Duration::from_millis(600)).await;
-        Ok(())
-    }
-    async fn shutdown(&mut self, _ctx: &AgentContext) -> AgentResult<()> {
-        println!("  [{}] Sensor offline.", self.name);
         Ok(())
     }
 }
@@ -50,37 +64,45 @@ impl Agent for AnalyzerAgent {
         println!("  [Analyzer] Watching blackboard for data.");
         Ok(())
     }
-    async fn execute(&mut self, _ctx: &AgentContext) -> AgentResult<()> {
-        tokio::time::sleep(std::time::Duration::from_millis(300)).await;
-        Ok(())
-    }
+    async fn execute(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
     async fn shutdown(&mut self, _ctx: &AgentContext) -> AgentResult<()> {
         println!("  [Analyzer] Performed {} analyses.", self.analyses);
         let board = self.board.lock().unwrap();
         println!("\n  Final Blackboard State ({} entries):", board.size());
         let mut entries: Vec<_> = board.knowledge().iter().collect();
-        entries.sort_by_key(|(k, _)| k.clone());
+        entries.sort_by_key(|(k, _)| *k);
         for (key, value) in entries {
             println!("    {} = {}", key, value);
         }
         Ok(())
     }
-    async fn handle_message(&mut self, ctx: &AgentContext, _s: &str, _p: &str, content: &str) -> AgentResult<()> {
-        if content.starts_with("updated:") {
-            let key = &content[8..];
-            let board = self.board.lock().unwrap();
-            if let Some(value) = board.read(key) {
-                println!("  [Analyzer] Read: {} = {}", key, value);
-                self.analyses += 1;
+    async fn handle_message(&mut self, ctx: &AgentContext, sender: &str, perf: &str, content: &str) -> AgentResult<()> {
+        self.handle_payload(ctx, sender, perf, content).await
+    }
+}
 
-                // Write analysis result back to blackboard
-                drop(board);
-                let analysis_key = format!("analysis:{}", key);
-                let analysis = format!("Processed {}", key);
-                self.board.lock().unwrap().write(&analysis_key, &analysis);
-                println!("  [Analyzer]  Wrote: {} = {}", analysis_key, analysis);
-                ctx.send_message("decision_maker", "inform", &format!("analyzed:{}", key));
-            }
+#[async_trait]
+impl TypedHandler for AnalyzerAgent {
+    type Payload = Notice;
+
+    async fn handle_typed_message(
+        &mut self, ctx: &AgentContext, _sender: &str, _perf: Performative, notice: Notice,
+    ) -> AgentResult<()> {
+        let Notice::Updated { key } = notice else {
+            return Ok(());
+        };
+        let board = self.board.lock().unwrap();
+        if let Some(value) = board.read(&key) {
+            println!("  [Analyzer] Read: {} = {}", key, value);
+            self.analyses += 1;
+
+            // Write analysis result back to blackboard
+            drop(board);
+            let analysis_key = format!("analysis:{}", key);
+            let analysis = format!("Processed {}", key);
+            self.board.lock().unwrap().write(&analysis_key, &analysis);
+            println!("  [Analyzer]  Wrote: {} = {}", analysis_key, analysis);
+            ctx.send_payload("decision_maker", Performative::Inform, &Notice::Analyzed { key })?;
         }
         Ok(())
     }
@@ -100,7 +122,7 @@ impl Agent for DecisionAgent {
         println!("  [DecisionMaker] Waiting for enough data.");
         Ok(())
     }
-    async fn execute(&mut self, _ctx: &AgentContext) -> AgentResult<()> {
+    async fn execute(&mut self, ctx: &AgentContext) -> AgentResult<()> {
         if !self.decided {
             let board = self.board.lock().unwrap();
             let analysis_count = board.knowledge().keys().filter(|k| k.starts_with("analysis:")).count();
@@ -110,6 +132,7 @@ impl Agent for DecisionAgent {
                 drop(board);
                 self.board.lock().unwrap().write("decision", "Proceed with launch");
                 self.decided = true;
+                ctx.stop_self()?;
             }
         }
         tokio::time::sleep(std::time::Duration::from_millis(300)).await;
@@ -126,28 +149,42 @@ impl Agent for DecisionAgent {
 
 #[tokio::main]
 async fn main() -> Result<(), RuntimeError> {
+    run().await
+}
+
+pub async fn run() -> Result<(), RuntimeError> {
     println!("╔═══════════════════════════════════════════════╗");
     println!("║   Agentropic — Blackboard Pattern             ║");
     println!("║   Shared knowledge for collaborative solving  ║");
     println!("╚═══════════════════════════════════════════════╝\n");
 
-    let board = Arc::new(Mutex::new(Blackboard::new("Mission Control")));
-    println!("  Blackboard: \"{}\"\n", board.lock().unwrap().name());
+    let blackboard = Arc::new(Mutex::new(Blackboard::new("Mission Control")));
+    println!("  Blackboard: \"{}\"\n", blackboard.lock().unwrap().name());
 
     let runtime = Runtime::new();
+    let board = Switchboard::new();
 
-    runtime.spawn(Box::new(DecisionAgent::new(board.clone())), "decision_maker").await?;
-    runtime.spawn(Box::new(AnalyzerAgent::new(board.clone())), "analyzer").await?;
-    runtime.spawn(Box::new(SensorAgent::new("TempSensor", vec![
+    board.spawn(&runtime, Box::new(DecisionAgent::new(blackboard.clone())), "decision_maker").await?;
+    board.spawn_reactive(&runtime, Box::new(AnalyzerAgent::new(blackboard.clone())), "analyzer").await?;
+    board.spawn_reactive(&runtime, Box::new(SensorAgent::new("TempSensor", vec![
         ("temperature", "72°F"),
         ("humidity", "45%"),
-    ], board.clone())), "temp_sensor").await?;
-    runtime.spawn(Box::new(SensorAgent::new("PressureSensor", vec![
+    ], blackboard.clone())), "temp_sensor").await?;
+    board.spawn_reactive(&runtime, Box::new(SensorAgent::new("PressureSensor", vec![
         ("pressure", "1013 hPa"),
         ("wind_speed", "12 mph"),
-    ], board.clone())), "pressure_sensor").await?;
+    ], blackboard.clone())), "pressure_sensor").await?;
 
-    tokio::time::sleep(std::time::Duration::from_secs(6)).await;
+    // Run until a decision is made and every reading has been analyzed
+    let readings = 4;
+    let finished = || {
+        let blackboard = blackboard.lock().unwrap();
+        let analyses = blackboard.knowledge().keys().filter(|k| k.starts_with("analysis:")).count();
+        blackboard.read("decision").is_some() && analyses >= readings
+    };
+    if let Err(err) = runtime.run_until(finished, Duration::from_secs(20)).await {
+        println!("\n  {}", err);
+    }
     runtime.shutdown().await?;
     println!("\n✓ Blackboard demo complete.");
     Ok(())
@@ -1,8 +1,22 @@
 //! Coalition: Agents form a temporary alliance to tackle a big task, then disband.
 use agentropic_core::{Agent, AgentContext, AgentId, AgentResult};
+use agentropic_examples::protocol::{ContractNet, ContractNetParticipant};
+use agentropic_examples::{ContextExt, RuntimeExt, Switchboard};
 use agentropic_patterns::coalition::{Coalition, Strategy, StrategyType};
 use agentropic_runtime::prelude::*;
 use async_trait::async_trait;
+use std::time::Duration;
+
+/// How long specialists have to answer the call, and then to finish their part.
+const MISSION_WINDOW: Duration = Duration::from_secs(2);
+
+/// The skills the mission needs, with the name and title of a specialist
+/// to recruit for each one nobody offers yet.
+const NEEDED: [(&str, &str, &str); 3] = [
+    ("infiltration", "hacker", "Hacker"),
+    ("analysis", "analyst", "Analyst"),
+    ("extraction", "extractor", "Extractor"),
+];
 
 struct RecruiterAgent { id: AgentId, recruited: bool }
 impl RecruiterAgent { fn new() -> Self { Self { id: AgentId::new(), recruited: false } } }
@@ -17,9 +31,36 @@ impl Agent for RecruiterAgent {
     async fn execute(&mut self, ctx: &AgentContext) -> AgentResult<()> {
         if !self.recruited {
             println!("  [Recruiter] → Recruiting specialists...");
-            ctx.send_message("hacker", "propose", "Join coalition: infiltrate target system");
-            ctx.send_message("analyst", "propose", "Join coalition: analyze defenses");
-            ctx.send_message("extractor", "propose", "Join coalition: extract the data");
+            let mut members = Vec::new();
+            for (skill, name, title) in NEEDED {
+                if let Some(member) = ctx.find_agents(skill)?.into_iter().next() {
+                    members.push(member);
+                    continue;
+                }
+                // Nobody offers it yet, so the coalition takes on a new member; it leaves with the recruiter
+                println!("  [Recruiter] + No one offers {}; recruiting {} as \"{}\".", skill, title, name);
+                let policy = RestartPolicy::new(RestartStrategy::Never);
+                match ctx.spawn_child(Box::new(SpecialistAgent::new(title, skill)), name, policy).await {
+                    Ok(_) => members.push(name.to_string()),
+                    Err(err) => println!("  [Recruiter] ✗ Could not recruit {}: {}", name, err),
+                }
+            }
+            // Everyone who signs up is taken on
+            let mission = ContractNet::new("Operation Nightfall")
+                .with_participants(&members)
+                .with_deadline(MISSION_WINDOW)
+                .start(|proposals| proposals.iter().map(|proposal| proposal.sender.clone()).collect());
+            tokio::spawn(async move {
+                match mission.await {
+                    Ok(outcome) => {
+                        for result in &outcome.results {
+                            println!("  [Recruiter] ← \"{}\"", result.content);
+                        }
+                        println!("  [Recruiter] {} of {} specialists delivered.", outcome.results.len(), outcome.accepted.len());
+                    }
+                    Err(err) => println!("  [Recruiter] ✗ {}", err),
+                }
+            });
             self.recruited = true;
         }
         tokio::time::sleep(std::time::Duration::from_millis(300)).await;
@@ -29,10 +70,6 @@ impl Agent for RecruiterAgent {
         println!("  [Recruiter] Coalition disbanded.");
         Ok(())
     }
-    async fn handle_message(&mut self, _ctx: &AgentContext, _s: &str, _p: &str, content: &str) -> AgentResult<()> {
-        println!("  [Recruiter] ← \"{}\"", content);
-        Ok(())
-    }
 }
 
 struct SpecialistAgent { id: AgentId, name: String, skill: String }
@@ -45,8 +82,9 @@ impl SpecialistAgent {
 #[async_trait]
 impl Agent for SpecialistAgent {
     fn id(&self) -> &AgentId { &self.id }
-    async fn initialize(&mut self, _ctx: &AgentContext) -> AgentResult<()> {
+    async fn initialize(&mut self, ctx: &AgentContext) -> AgentResult<()> {
         println!("  [{}] Specialist ({}) standing by.", self.name, self.skill);
+        ctx.register_capability(&self.skill)?;
         Ok(())
     }
     async fn execute(&mut self, _ctx: &AgentContext) -> AgentResult<()> {
@@ -57,21 +95,33 @@ impl Agent for SpecialistAgent {
         println!("  [{}] Leaving coalition.", self.name);
         Ok(())
     }
-    async fn handle_message(&mut self, ctx: &AgentContext, _s: &str, perf: &str, content: &str) -> AgentResult<()> {
-        if perf == "Propose" || perf == "propose" {
-            println!("  [{}] ← Proposal: \"{}\"", self.name, content);
-            println!("  [{}] ✓ Accepted! Applying {} skills...", self.name, self.skill);
-            tokio::time::sleep(std::time::Duration::from_millis(600)).await;
-            let result = format!("{} complete: {} phase done", self.name, self.skill);
-            println!("  [{}] → \"{}\"", self.name, result);
-            ctx.send_message("recruiter", "inform", &result);
-        }
-        Ok(())
+    async fn handle_message(&mut self, ctx: &AgentContext, s: &str, p: &str, content: &str) -> AgentResult<()> {
+        self.handle_contract_net(ctx, s, p, content).await
+    }
+}
+
+#[async_trait]
+impl ContractNetParticipant for SpecialistAgent {
+    async fn propose(&mut self, _ctx: &AgentContext, _initiator: &str, mission: &str) -> Option<String> {
+        println!("  [{}] ← Call for \"{}\"", self.name, mission);
+        Some(format!("{} for {}", self.skill, mission))
+    }
+
+    async fn perform(&mut self, _ctx: &AgentContext, _initiator: &str, _proposal: &str) -> Result<String, String> {
+        println!("  [{}] ✓ Accepted! Applying {} skills...", self.name, self.skill);
+        tokio::time::sleep(std::time::Duration::from_millis(600)).await;
+        let result = format!("{} complete: {} phase done", self.name, self.skill);
+        println!("  [{}] → \"{}\"", self.name, result);
+        Ok(result)
     }
 }
 
 #[tokio::main]
 async fn main() -> Result<(), RuntimeError> {
+    run().await
+}
+
+pub async fn run() -> Result<(), RuntimeError> {
     println!("╔═══════════════════════════════════════════════╗");
     println!("║   Agentropic — Coalition Pattern              ║");
     println!("║   Temporary alliance for a mission            ║");
@@ -86,12 +136,14 @@ async fn main() -> Result<(), RuntimeError> {
     println!("  Strategy: {:?}\n", coalition.strategy().unwrap().strategy_type());
 
     let runtime = Runtime::new();
-    runtime.spawn(Box::new(SpecialistAgent::new("Hacker", "infiltration")), "hacker").await?;
-    runtime.spawn(Box::new(SpecialistAgent::new("Analyst", "analysis")), "analyst").await?;
-    runtime.spawn(Box::new(SpecialistAgent::new("Extractor", "extraction")), "extractor").await?;
-    runtime.spawn(Box::new(RecruiterAgent::new()), "recruiter").await?;
+    let board = Switchboard::new();
+    board.spawn(&runtime, Box::new(SpecialistAgent::new("Hacker", "infiltration")), "hacker").await?;
+    board.spawn(&runtime, Box::new(SpecialistAgent::new("Analyst", "analysis")), "analyst").await?;
+    board.spawn(&runtime, Box::new(RecruiterAgent::new()), "recruiter").await?;
 
-    tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+    if let Err(err) = runtime.run_until_idle(&board, Duration::from_secs(10)).await {
+        println!("\n  {}", err);
+    }
     runtime.shutdown().await?;
     println!("\n✓ Coalition demo complete.");
     Ok(())
@@ -1,15 +1,24 @@
 //! CognitiveAgent reasons from BeliefBase, falls back to LLM for unknown questions.
 use agentropic_core::{Agent, AgentContext, AgentId, AgentResult};
+use agentropic_examples::{ContextExt, Performative, RuntimeExt, SpawnOptions, Switchboard, Timeouts};
 use agentropic_cognition::Rule;
 use agentropic_runtime::prelude::*;
 use agentropic_runtime::CognitiveAgent;
 use async_trait::async_trait;
+use std::time::Duration;
+
+/// How long to wait for the thinker; LLM fallbacks can be slow.
+const ANSWER_TIMEOUT: Duration = Duration::from_secs(100);
+
+/// How long the thinker may spend on one question. A hung LLM call is
+/// cancelled after this and the asker told it failed, so later questions
+/// still get answered.
+const THINK_TIMEOUT: Duration = Duration::from_secs(30);
 
 struct CuriousAgent {
     id: AgentId,
     questions: Vec<&'static str>,
-    index: usize,
-    waiting: bool,
+    asked: bool,
 }
 
 impl CuriousAgent {
@@ -21,8 +30,7 @@ impl CuriousAgent {
                 "What patterns does it support?",
                 "Can Agentropic agents collaborate with external APIs?",
             ],
-            index: 0,
-            waiting: false,
+            asked: false,
         }
     }
 }
@@ -35,31 +43,38 @@ impl Agent for CuriousAgent {
         Ok(())
     }
     async fn execute(&mut self, ctx: &AgentContext) -> AgentResult<()> {
-        if !self.waiting && self.index < self.questions.len() {
-            println!("\n  [Curious] → \"{}\"", self.questions[self.index]);
-            ctx.send_message("thinker", "query", self.questions[self.index]);
-            self.waiting = true;
+        if !self.asked {
+            // Send every question up front; answers are matched by conversation.
+            let pending: Vec<_> = self.questions.iter().map(|question| {
+                println!("  [Curious] → \"{}\"", question);
+                (*question, ctx.request("thinker", Performative::Query, question, ANSWER_TIMEOUT))
+            }).collect();
+            tokio::spawn(async move {
+                for (question, answer) in pending {
+                    match answer.await {
+                        Ok(reply) => println!("\n  [Curious] \"{}\"\n  [Curious] ← \"{}\"", question, reply.content),
+                        Err(err) => println!("\n  [Curious] \"{}\"\n  [Curious] ✗ {}", question, err),
+                    }
+                }
+                println!("\n  [Curious] All questions answered! ✓");
+            });
+            self.asked = true;
         }
-        tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+        tokio::time::sleep(Duration::from_millis(500)).await;
         Ok(())
     }
     async fn shutdown(&mut self, _ctx: &AgentContext) -> AgentResult<()> {
         println!("  [Curious] Done!");
         Ok(())
     }
-    async fn handle_message(&mut self, _ctx: &AgentContext, _s: &str, _p: &str, content: &str) -> AgentResult<()> {
-        println!("  [Curious] ← \"{}\"", content);
-        self.index += 1;
-        self.waiting = false;
-        if self.index >= self.questions.len() {
-            println!("\n  [Curious] All questions answered! ✓");
-        }
-        Ok(())
-    }
 }
 
 #[tokio::main]
 async fn main() -> Result<(), RuntimeError> {
+    run().await
+}
+
+pub async fn run() -> Result<(), RuntimeError> {
     println!("=== Cognitive Agent ===\n");
 
     let mut thinker = CognitiveAgent::from_config("data/beliefs.json", "data/config.json");
@@ -74,10 +89,17 @@ async fn main() -> Result<(), RuntimeError> {
     println!("  CognitiveAgent: {} beliefs, {} rules\n", thinker.belief_count(), thinker.rule_count());
 
     let runtime = Runtime::new();
-    runtime.spawn(Box::new(thinker), "thinker").await?;
-    runtime.spawn(Box::new(CuriousAgent::new()), "curious").await?;
+    let board = Switchboard::new();
+    // CognitiveAgent answers with a plain send_message, not a reply
+    let timeouts = Timeouts::new().with_handle_message(THINK_TIMEOUT);
+    let options = SpawnOptions::new().with_timeouts(timeouts).replies_without_envelope();
+    board.spawn_with(&runtime, Box::new(thinker), "thinker", options).await?;
+    board.spawn(&runtime, Box::new(CuriousAgent::new()), "curious").await?;
 
-    tokio::time::sleep(std::time::Duration::from_secs(120)).await;
+    // Done once every question has been answered (or timed out)
+    if let Err(err) = runtime.run_until_idle(&board, Duration::from_secs(120)).await {
+        println!("  {}", err);
+    }
 
     runtime.shutdown().await?;
     println!("\n=== Done ===");
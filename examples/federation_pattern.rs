@@ -1,9 +1,15 @@
 //! Federation: Agents vote on proposals with weighted voting.
 use agentropic_core::{Agent, AgentContext, AgentId, AgentResult};
+use agentropic_examples::payload::Vote;
+use agentropic_examples::{ContextExt, MessageOptions, Performative, RuntimeExt, Switchboard, TypedHandler};
 use agentropic_patterns::federation::{Federation, Policy, PolicyType};
 use agentropic_runtime::prelude::*;
 use async_trait::async_trait;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// How long delegates have to vote on a proposal.
+const VOTING_WINDOW: Duration = Duration::from_secs(2);
 
 struct VoteResult { yes: f64, no: f64, total_weight: f64 }
 
@@ -32,9 +38,8 @@ impl Agent for ChairAgent {
         if !self.proposed {
             let proposal = "Allocate $50k to AI research fund";
             println!("  [Chair] Proposal: \"{}\"", proposal);
-            ctx.send_message("delegate_1", "cfp", proposal);
-            ctx.send_message("delegate_2", "cfp", proposal);
-            ctx.send_message("delegate_3", "cfp", proposal);
+            let call = MessageOptions::new().with_ttl(VOTING_WINDOW);
+            ctx.publish_with("council", Performative::Cfp, proposal, call)?;
             self.proposed = true;
         }
 
@@ -54,6 +59,8 @@ impl Agent for ChairAgent {
                 self.resolved = true;
             }
         }
+        // The session stays open until every vote is counted
+        ctx.set_busy(!self.resolved)?;
 
         tokio::time::sleep(std::time::Duration::from_millis(300)).await;
         Ok(())
@@ -62,15 +69,22 @@ impl Agent for ChairAgent {
         println!("  [Chair] Session closed.");
         Ok(())
     }
-    async fn handle_message(&mut self, _ctx: &AgentContext, _s: &str, _p: &str, content: &str) -> AgentResult<()> {
-        if content.starts_with("yes:") {
-            let weight: f64 = content[4..].parse().unwrap_or(1.0);
-            self.votes.lock().unwrap().yes += weight;
-            println!("  [Chair] ← Vote YES (weight {:.1})", weight);
-        } else if content.starts_with("no:") {
-            let weight: f64 = content[3..].parse().unwrap_or(1.0);
-            self.votes.lock().unwrap().no += weight;
-            println!("  [Chair] ← Vote NO (weight {:.1})", weight);
+    async fn handle_message(&mut self, ctx: &AgentContext, s: &str, p: &str, content: &str) -> AgentResult<()> {
+        self.handle_payload(ctx, s, p, content).await
+    }
+}
+
+#[async_trait]
+impl TypedHandler for ChairAgent {
+    type Payload = Vote;
+
+    async fn handle_typed_message(&mut self, _ctx: &AgentContext, _s: &str, _p: Performative, vote: Vote) -> AgentResult<()> {
+        if vote.approve {
+            self.votes.lock().unwrap().yes += vote.weight;
+            println!("  [Chair] ← Vote YES (weight {:.1})", vote.weight);
+        } else {
+            self.votes.lock().unwrap().no += vote.weight;
+            println!("  [Chair] ← Vote NO (weight {:.1})", vote.weight);
         }
         Ok(())
     }
@@ -95,55 +109,74 @@ impl Agent for DelegateAgent {
         Ok(())
     }
     async fn shutdown(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
-    async fn handle_message(&mut self, ctx: &AgentContext, _s: &str, _p: &str, content: &str) -> AgentResult<()> {
+    async fn handle_message(&mut self, ctx: &AgentContext, _s: &str, perf: &str, content: &str) -> AgentResult<()> {
+        match perf.parse() {
+            Ok(Performative::Cfp) => {}
+            Ok(_) => return Ok(()),
+            Err(_) => return Ok(ctx.reply(Performative::NotUnderstood, content)?),
+        }
         println!("  [{}] Considering: \"{}\"", self.name, content);
         tokio::time::sleep(std::time::Duration::from_millis(300)).await;
+        // A vote cast after the session's deadline would not be counted
+        if ctx.time_left().is_some_and(|left| left.is_zero()) {
+            println!("  [{}]  Too late to vote", self.name);
+            return Ok(());
+        }
         if self.votes_yes {
             println!("  [{}]  Voting YES", self.name);
-            ctx.send_message("chair", "inform", &format!("yes:{}", self.weight));
         } else {
             println!("  [{}]  Voting NO", self.name);
-            ctx.send_message("chair", "inform", &format!("no:{}", self.weight));
         }
+        let vote = Vote { approve: self.votes_yes, weight: self.weight };
+        ctx.send_payload("chair", Performative::Inform, &vote)?;
         Ok(())
     }
 }
 
 #[tokio::main]
 async fn main() -> Result<(), RuntimeError> {
+    run().await
+}
+
+pub async fn run() -> Result<(), RuntimeError> {
     println!("╔═══════════════════════════════════════════════╗");
     println!("║   Agentropic — Federation Pattern             ║");
     println!("║   Weighted voting on proposals                ║");
     println!("╚═══════════════════════════════════════════════╝\n");
 
+    let delegates = vec![
+        ("delegate_1", DelegateAgent::new("Delegate-1", 2.0, true)),
+        ("delegate_2", DelegateAgent::new("Delegate-2", 1.5, true)),
+        ("delegate_3", DelegateAgent::new("Delegate-3", 1.0, false)),
+    ];
+
     let mut federation = Federation::new("Research Council");
-    let d1 = AgentId::new();
-    let d2 = AgentId::new();
-    let d3 = AgentId::new();
-    federation.add_member(d1);
-    federation.add_member(d2);
-    federation.add_member(d3);
-    federation.set_weight(d1, 2.0);
-    federation.set_weight(d2, 1.5);
-    federation.set_weight(d3, 1.0);
+    for (_, delegate) in &delegates {
+        federation.add_member(*delegate.id());
+        federation.set_weight(*delegate.id(), delegate.weight);
+    }
 
     let policy = Policy::new("budget_approval", PolicyType::WeightedVote)
         .with_threshold(0.6)
         .with_rule("Requires 60% weighted approval");
     federation.add_policy(policy);
 
-    let total_weight = 2.0 + 1.5 + 1.0;
+    let total_weight: f64 = delegates.iter().map(|(_, d)| d.weight).sum();
     let votes = Arc::new(Mutex::new(VoteResult { yes: 0.0, no: 0.0, total_weight }));
 
     println!("  Federation: \"{}\" ({} members, policy: WeightedVote)\n", federation.name(), federation.size());
 
     let runtime = Runtime::new();
-    runtime.spawn(Box::new(DelegateAgent::new("Delegate-1", 2.0, true)), "delegate_1").await?;
-    runtime.spawn(Box::new(DelegateAgent::new("Delegate-2", 1.5, true)), "delegate_2").await?;
-    runtime.spawn(Box::new(DelegateAgent::new("Delegate-3", 1.0, false)), "delegate_3").await?;
-    runtime.spawn(Box::new(ChairAgent::new(votes, 0.6)), "chair").await?;
+    let board = Switchboard::new();
+    board.group("council", &federation);
+    for (name, delegate) in delegates {
+        board.spawn(&runtime, Box::new(delegate), name).await?;
+    }
+    board.spawn(&runtime, Box::new(ChairAgent::new(votes, 0.6)), "chair").await?;
 
-    tokio::time::sleep(std::time::Duration::from_secs(4)).await;
+    if let Err(err) = runtime.run_until_idle(&board, Duration::from_secs(10)).await {
+        println!("\n  {}", err);
+    }
     runtime.shutdown().await?;
     println!("\n✓ Federation demo complete.");
     Ok(())
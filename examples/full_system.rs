@@ -1,22 +1,34 @@
 //! Full system demo: agents run, talk, reason, and self-heal.
 use agentropic_core::{Agent, AgentContext, AgentId, AgentError, AgentResult};
+use agentropic_examples::payload::{self, TaskAssignment, TaskCompletion};
+use agentropic_examples::{ContextExt, Lifecycle, LifecycleEvent, Performative, RuntimeExt, Switchboard, LIFECYCLE_TOPIC};
 use agentropic_cognition::Rule;
 use agentropic_runtime::prelude::*;
 use agentropic_runtime::CognitiveAgent;
 use async_trait::async_trait;
+use std::collections::{BTreeMap, VecDeque};
 use std::sync::atomic::{AtomicU32, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
 
-/// Worker that does tasks and reports to manager
+/// Worker that does the tasks it is given and reports them to the manager
 struct WorkerAgent {
     id: AgentId,
     name: String,
+    queue: VecDeque<TaskAssignment>,
     tasks_done: u32,
+    /// Crash instead of doing the next task once this many are done.
+    crash_after: Option<u32>,
 }
 
 impl WorkerAgent {
     fn new(name: &str) -> Self {
-        Self { id: AgentId::new(), name: name.to_string(), tasks_done: 0 }
+        Self { id: AgentId::new(), name: name.to_string(), queue: VecDeque::new(), tasks_done: 0, crash_after: None }
+    }
+
+    fn crashing_after(mut self, tasks: u32) -> Self {
+        self.crash_after = Some(tasks);
+        self
     }
 }
 
@@ -30,11 +42,18 @@ impl Agent for WorkerAgent {
     }
 
     async fn execute(&mut self, ctx: &AgentContext) -> AgentResult<()> {
-        self.tasks_done += 1;
-        if self.tasks_done <= 3 {
-            let report = format!("{} completed task #{}", self.name, self.tasks_done);
-            ctx.send_message("manager", "inform", &report);
-            println!("  [{}] {}", self.name, report);
+        if let Some(assignment) = self.queue.pop_front() {
+            if self.crash_after == Some(self.tasks_done) {
+                println!("  [{}] Crashed while working on {}!", self.name, assignment.task);
+                return Err(AgentError::ExecutionFailed("worker crashed".into()));
+            }
+            self.tasks_done += 1;
+            println!("  [{}] Completed {}", self.name, assignment.task);
+            let done = TaskCompletion { task: assignment.task, executor: assignment.executor, duration_ms: 500 };
+            ctx.try_send("manager", Performative::Inform, &payload::encode(&done)?)?;
+            if self.queue.is_empty() {
+                ctx.set_busy(false)?;
+            }
         }
         tokio::time::sleep(std::time::Duration::from_millis(500)).await;
         Ok(())
@@ -44,17 +63,41 @@ impl Agent for WorkerAgent {
         println!("  [{}] Finished {} tasks.", self.name, self.tasks_done);
         Ok(())
     }
+
+    async fn handle_message(&mut self, ctx: &AgentContext, _s: &str, _p: &str, content: &str) -> AgentResult<()> {
+        self.queue.push_back(payload::decode(content)?);
+        ctx.set_busy(true)?;
+        Ok(())
+    }
 }
 
-/// Manager that receives reports from workers
+/// Manager that hands out tasks, and hands them out again when a worker dies
 struct ManagerAgent {
     id: AgentId,
+    workers: Vec<String>,
+    /// Tasks not yet reported done, and who is doing them.
+    outstanding: BTreeMap<String, String>,
+    next: usize,
     reports: Vec<String>,
 }
 
 impl ManagerAgent {
-    fn new() -> Self {
-        Self { id: AgentId::new(), reports: Vec::new() }
+    fn new(workers: &[&str]) -> Self {
+        let workers = workers.iter().map(|worker| worker.to_string()).collect();
+        Self { id: AgentId::new(), workers, outstanding: BTreeMap::new(), next: 0, reports: Vec::new() }
+    }
+
+    fn assign(&mut self, ctx: &AgentContext, task: String) -> AgentResult<()> {
+        if self.workers.is_empty() {
+            println!("  [Manager] No workers left for {}.", task);
+            return Ok(());
+        }
+        let worker = self.workers[self.next % self.workers.len()].clone();
+        self.next += 1;
+        let assignment = TaskAssignment { task: task.clone(), executor: worker.clone() };
+        ctx.try_send(&worker, Performative::Request, &payload::encode(&assignment)?)?;
+        self.outstanding.insert(task, worker);
+        Ok(())
     }
 }
 
@@ -62,8 +105,12 @@ impl ManagerAgent {
 impl Agent for ManagerAgent {
     fn id(&self) -> &AgentId { &self.id }
 
-    async fn initialize(&mut self, _ctx: &AgentContext) -> AgentResult<()> {
+    async fn initialize(&mut self, ctx: &AgentContext) -> AgentResult<()> {
         println!("  [Manager] Overseeing workers.");
+        ctx.subscribe(LIFECYCLE_TOPIC)?;
+        for task in 1..=6 {
+            self.assign(ctx, format!("task #{}", task))?;
+        }
         Ok(())
     }
 
@@ -77,9 +124,26 @@ impl Agent for ManagerAgent {
         Ok(())
     }
 
-    async fn handle_message(&mut self, _ctx: &AgentContext, _s: &str, _p: &str, content: &str) -> AgentResult<()> {
-        println!("  [Manager] ← Report: \"{}\"", content);
-        self.reports.push(content.to_string());
+    async fn handle_message(&mut self, ctx: &AgentContext, sender: &str, _p: &str, content: &str) -> AgentResult<()> {
+        if sender == LIFECYCLE_TOPIC {
+            let LifecycleEvent { agent, event } = payload::decode(content)?;
+            if let (Lifecycle::GaveUp(reason), true) = (event, self.workers.contains(&agent)) {
+                println!("  [Manager] {} is gone ({}); reassigning its work.", agent, reason);
+                self.workers.retain(|worker| *worker != agent);
+                let orphaned: Vec<String> = self.outstanding.iter()
+                    .filter(|(_, worker)| **worker == agent)
+                    .map(|(task, _)| task.clone())
+                    .collect();
+                for task in orphaned {
+                    self.assign(ctx, task)?;
+                }
+            }
+            return Ok(());
+        }
+        let done: TaskCompletion = payload::decode(content)?;
+        println!("  [Manager] ← {} done by {}", done.task, done.executor);
+        self.outstanding.remove(&done.task);
+        self.reports.push(done.task);
         Ok(())
     }
 }
@@ -121,21 +185,29 @@ impl Agent for UnreliableAgent {
 
 #[tokio::main]
 async fn main() -> Result<(), RuntimeError> {
+    run().await
+}
+
+pub async fn run() -> Result<(), RuntimeError> {
     println!("╔═══════════════════════════════════════════════╗");
     println!("║   Agentropic — Full System Demo               ║");
     println!("║   Running + Messaging + Reasoning + Recovery   ║");
     println!("╚═══════════════════════════════════════════════╝\n");
 
     let runtime = Runtime::new();
+    let board = Switchboard::new();
 
     // --- 1. Workers report to Manager ---
     println!("--- Stage 1: Workers & Manager ---\n");
 
-    runtime.spawn(Box::new(ManagerAgent::new()), "manager").await?;
-    runtime.spawn(Box::new(WorkerAgent::new("Alpha")), "alpha").await?;
-    runtime.spawn(Box::new(WorkerAgent::new("Beta")), "beta").await?;
+    board.spawn(&runtime, Box::new(WorkerAgent::new("Alpha")), "alpha").await?;
+    board.spawn(&runtime, Box::new(WorkerAgent::new("Beta").crashing_after(1)), "beta").await?;
+    board.spawn(&runtime, Box::new(ManagerAgent::new(&["alpha", "beta"])), "manager").await?;
 
-    tokio::time::sleep(std::time::Duration::from_secs(3)).await;
+    // Beta dies with work queued; the manager hears it from the lifecycle topic
+    if let Err(err) = runtime.run_until_idle(&board, Duration::from_secs(10)).await {
+        println!("  {}", err);
+    }
 
     // --- 2. Cognitive Agent answers questions ---
     println!("\n--- Stage 2: Cognitive Reasoning ---\n");
@@ -148,7 +220,7 @@ async fn main() -> Result<(), RuntimeError> {
         .with_condition("pattern").with_condition("support")
         .with_conclusion("patterns"));
 
-    runtime.spawn(Box::new(thinker), "thinker").await?;
+    board.spawn_external(&runtime, Box::new(thinker), "thinker").await?;
 
     // Alpha asks Thinker a question
     // (We simulate by spawning a quick asker)
@@ -162,21 +234,27 @@ async fn main() -> Result<(), RuntimeError> {
         async fn execute(&mut self, ctx: &AgentContext) -> AgentResult<()> {
             if !self.asked {
                 println!("  [QuickAsker] → \"What is Agentropic?\"");
-                ctx.send_message("thinker", "query", "What is Agentropic?");
+                let answer = ctx.request(
+                    "thinker", Performative::Query, "What is Agentropic?", std::time::Duration::from_secs(3),
+                );
+                tokio::spawn(async move {
+                    match answer.await {
+                        Ok(reply) => println!("  [QuickAsker] ← \"{}\"", reply.content),
+                        Err(err) => println!("  [QuickAsker] ✗ {}", err),
+                    }
+                });
                 self.asked = true;
             }
             tokio::time::sleep(std::time::Duration::from_millis(200)).await;
             Ok(())
         }
         async fn shutdown(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
-        async fn handle_message(&mut self, _ctx: &AgentContext, _s: &str, _p: &str, content: &str) -> AgentResult<()> {
-            println!("  [QuickAsker] ← \"{}\"", content);
-            Ok(())
-        }
     }
 
-    runtime.spawn(Box::new(QuickAsker::new()), "quick_asker").await?;
-    tokio::time::sleep(std::time::Duration::from_secs(3)).await;
+    board.spawn(&runtime, Box::new(QuickAsker::new()), "quick_asker").await?;
+    if let Err(err) = runtime.run_until_idle(&board, Duration::from_secs(5)).await {
+        println!("  {}", err);
+    }
 
     // --- 3. Self-healing agent ---
     println!("\n--- Stage 3: Self-Healing ---\n");
@@ -186,13 +264,27 @@ async fn main() -> Result<(), RuntimeError> {
         .with_max_retries(5)
         .with_backoff_seconds(1);
 
-    runtime.spawn_with_policy(
+    // Follow the unreliable agent's crashes and restarts from here
+    let mut events = runtime.events(&board);
+    tokio::spawn(async move {
+        while let Some(LifecycleEvent { agent, event }) = events.next().await {
+            if agent == "unreliable" {
+                println!("  [events] unreliable {}", event);
+            }
+        }
+    });
+
+    board.spawn_with_policy(
+        &runtime,
         Box::new(UnreliableAgent::new(crash_counter.clone())),
         "unreliable",
         policy,
     ).await?;
 
-    tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+    let recovered = || crash_counter.load(Ordering::SeqCst) >= 3;
+    if let Err(err) = runtime.run_until(recovered, Duration::from_secs(10)).await {
+        println!("  {}", err);
+    }
 
     // --- Summary ---
     println!("\n--- Summary ---");
@@ -1,7 +1,9 @@
-//! Simplest possible agent — spawns with Runtime, ticks, shuts down.
+//! Simplest possible agent — spawns with Runtime, ticks on a timer, shuts down.
 use agentropic_core::{Agent, AgentContext, AgentId, AgentResult};
+use agentropic_examples::{ContextExt, Switchboard, TIMER};
 use agentropic_runtime::prelude::*;
 use async_trait::async_trait;
+use std::time::Duration;
 
 struct GreeterAgent {
     id: AgentId,
@@ -18,33 +20,44 @@ impl GreeterAgent {
 impl Agent for GreeterAgent {
     fn id(&self) -> &AgentId { &self.id }
 
-    async fn initialize(&mut self, _ctx: &AgentContext) -> AgentResult<()> {
+    async fn initialize(&mut self, ctx: &AgentContext) -> AgentResult<()> {
         println!("  [Greeter] Hello! I'm alive.");
+        ctx.schedule_every(Duration::from_millis(500), "tick")?;
         Ok(())
     }
 
-    async fn execute(&mut self, _ctx: &AgentContext) -> AgentResult<()> {
-        self.count += 1;
-        println!("  [Greeter] Tick #{}", self.count);
-        tokio::time::sleep(std::time::Duration::from_millis(500)).await;
-        Ok(())
-    }
+    async fn execute(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
 
     async fn shutdown(&mut self, _ctx: &AgentContext) -> AgentResult<()> {
         println!("  [Greeter] Goodbye after {} ticks!", self.count);
         Ok(())
     }
+
+    async fn handle_message(
+        &mut self, _ctx: &AgentContext, sender: &str, _perf: &str, _content: &str,
+    ) -> AgentResult<()> {
+        if sender == TIMER {
+            self.count += 1;
+            println!("  [Greeter] Tick #{}", self.count);
+        }
+        Ok(())
+    }
 }
 
 #[tokio::main]
 async fn main() -> Result<(), RuntimeError> {
+    run().await
+}
+
+pub async fn run() -> Result<(), RuntimeError> {
     println!("=== Hello Agent ===\n");
 
     let runtime = Runtime::new();
-    runtime.spawn(Box::new(GreeterAgent::new()), "greeter").await?;
+    let board = Switchboard::new();
+    board.spawn_reactive(&runtime, Box::new(GreeterAgent::new()), "greeter").await?;
 
     println!("  Agent running for 3 seconds...\n");
-    tokio::time::sleep(std::time::Duration::from_secs(3)).await;
+    tokio::time::sleep(Duration::from_secs(3)).await;
 
     runtime.shutdown().await?;
     println!("\n=== Done ===");
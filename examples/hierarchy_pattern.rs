@@ -1,8 +1,10 @@
 //! Hierarchy: Commander → Captain → Soldiers. Orders flow down, reports flow up.
-use agentropic_core::{Agent, AgentContext, AgentId, AgentResult};
+use agentropic_core::{Agent, AgentContext, AgentError, AgentId, AgentResult};
+use agentropic_examples::{ContextExt, Performative, RuntimeExt, SpawnOptions, Strategy, Supervisor, Switchboard};
 use agentropic_patterns::hierarchy::{Hierarchy, Level, LevelType};
 use agentropic_runtime::prelude::*;
 use async_trait::async_trait;
+use std::time::Duration;
 
 struct CommanderAgent { id: AgentId, issued: bool, reports: u32 }
 impl CommanderAgent { fn new() -> Self { Self { id: AgentId::new(), issued: false, reports: 0 } } }
@@ -17,7 +19,7 @@ impl Agent for CommanderAgent {
     async fn execute(&mut self, ctx: &AgentContext) -> AgentResult<()> {
         if !self.issued {
             println!("  [Commander] ↓ Order: \"Secure sector 7\"");
-            ctx.send_message("captain", "request", "Secure sector 7");
+            ctx.try_send("captain", Performative::Request, "Secure sector 7")?;
             self.issued = true;
         }
         tokio::time::sleep(std::time::Duration::from_millis(300)).await;
@@ -34,8 +36,8 @@ impl Agent for CommanderAgent {
     }
 }
 
-struct CaptainAgent { id: AgentId, delegated: bool }
-impl CaptainAgent { fn new() -> Self { Self { id: AgentId::new(), delegated: false } } }
+struct CaptainAgent { id: AgentId, delegated: bool, hit: bool }
+impl CaptainAgent { fn new() -> Self { Self { id: AgentId::new(), delegated: false, hit: false } } }
 
 #[async_trait]
 impl Agent for CaptainAgent {
@@ -45,6 +47,12 @@ impl Agent for CaptainAgent {
         Ok(())
     }
     async fn execute(&mut self, _ctx: &AgentContext) -> AgentResult<()> {
+        if self.delegated && !self.hit {
+            // The captain goes down once; the unit's supervisor brings back the whole unit
+            self.hit = true;
+            println!("  [Captain] ✗ Hit!");
+            return Err(AgentError::ExecutionFailed("captain was hit".to_string()));
+        }
         tokio::time::sleep(std::time::Duration::from_millis(300)).await;
         Ok(())
     }
@@ -52,20 +60,23 @@ impl Agent for CaptainAgent {
         println!("  [Captain] Standing down.");
         Ok(())
     }
-    async fn handle_message(&mut self, ctx: &AgentContext, _s: &str, _p: &str, content: &str) -> AgentResult<()> {
-        if !self.delegated {
-            // First message = order from Commander. Delegate to soldiers.
+    async fn handle_message(&mut self, ctx: &AgentContext, _s: &str, perf: &str, content: &str) -> AgentResult<()> {
+        let Ok(perf) = perf.parse::<Performative>() else {
+            return Ok(ctx.reply(Performative::NotUnderstood, content)?);
+        };
+        if perf == Performative::Request && !self.delegated {
+            // Request = order from Commander. Delegate to soldiers.
             println!("  [Captain] ← Order: \"{}\"", content);
             let zone = content.replace("Secure ", "");
             println!("  [Captain] ↓ soldier_1: \"Scout {}\"", zone);
-            ctx.send_message("soldier_1", "request", &format!("Scout {}", zone));
+            ctx.try_send("soldier_1", Performative::Request, &format!("Scout {}", zone))?;
             println!("  [Captain] ↓ soldier_2: \"Hold perimeter of {}\"", zone);
-            ctx.send_message("soldier_2", "request", &format!("Hold perimeter of {}", zone));
+            ctx.try_send("soldier_2", Performative::Request, &format!("Hold perimeter of {}", zone))?;
             self.delegated = true;
-        } else {
-            // Subsequent messages = reports from soldiers. Forward up.
+        } else if perf == Performative::Inform {
+            // Inform = report from a soldier. Forward up.
             println!("  [Captain] ← Report: \"{}\"", content);
-            ctx.send_message("commander", "inform", content);
+            ctx.try_send("commander", Performative::Inform, content)?;
         }
         Ok(())
     }
@@ -81,35 +92,36 @@ impl Agent for SoldierAgent {
         println!("  [{}] Operational level online.", self.name);
         Ok(())
     }
-    async fn execute(&mut self, _ctx: &AgentContext) -> AgentResult<()> {
-        tokio::time::sleep(std::time::Duration::from_millis(300)).await;
-        Ok(())
-    }
+    async fn execute(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
     async fn shutdown(&mut self, _ctx: &AgentContext) -> AgentResult<()> {
         println!("  [{}] Standing down.", self.name);
         Ok(())
     }
-    async fn handle_message(&mut self, ctx: &AgentContext, _s: &str, _p: &str, content: &str) -> AgentResult<()> {
+    async fn handle_message(&mut self, ctx: &AgentContext, _s: &str, perf: &str, content: &str) -> AgentResult<()> {
+        match perf.parse() {
+            Ok(Performative::Request) => {}
+            Ok(_) => return Ok(()),
+            Err(_) => return Ok(ctx.reply(Performative::NotUnderstood, content)?),
+        }
         println!("  [{}] ← Task: \"{}\"", self.name, content);
         tokio::time::sleep(std::time::Duration::from_millis(500)).await;
         println!("  [{}] ✓ Completed: \"{}\"", self.name, content);
-        ctx.send_message("captain", "inform", &format!("{}: {} done", self.name, content));
+        ctx.try_send("captain", Performative::Inform, &format!("{}: {} done", self.name, content))?;
         Ok(())
     }
 }
 
 #[tokio::main]
 async fn main() -> Result<(), RuntimeError> {
+    run().await
+}
+
+pub async fn run() -> Result<(), RuntimeError> {
     println!("╔═══════════════════════════════════════════════╗");
     println!("║   Agentropic — Hierarchy Pattern              ║");
     println!("║   Commander → Captain → Soldiers              ║");
     println!("╚═══════════════════════════════════════════════╝\n");
 
-    let cmd_id = AgentId::new();
-    let cap_id = AgentId::new();
-    let s1_id = AgentId::new();
-    let s2_id = AgentId::new();
-
     let mut hierarchy = Hierarchy::new("Military Operations");
     let strategic = Level::new("Command", LevelType::Strategic, 3);
     let tactical = Level::new("Tactical", LevelType::Tactical, 2);
@@ -117,20 +129,40 @@ async fn main() -> Result<(), RuntimeError> {
     hierarchy.add_level(strategic.clone());
     hierarchy.add_level(tactical.clone());
     hierarchy.add_level(operational.clone());
-    hierarchy.assign_agent(cmd_id, strategic);
-    hierarchy.assign_agent(cap_id, tactical);
-    hierarchy.assign_agent(s1_id, operational.clone());
-    hierarchy.assign_agent(s2_id, operational);
+
+    // Orders from a higher level are delivered ahead of routine reports
+    let board = Switchboard::new();
+    board.set_level("commander", &strategic);
+    board.set_level("captain", &tactical);
+    board.set_level("soldier_1", &operational);
+    board.set_level("soldier_2", &operational);
 
     println!("  Hierarchy: \"{}\" ({} levels)\n", hierarchy.name(), hierarchy.levels().len());
 
+    // If the captain falls, its soldiers are restarted with it
+    let unit = Supervisor::new("unit", Strategy::OneForAll)
+        .child("captain", Box::new(CaptainAgent::new()))
+        .child_with("soldier_1", Box::new(SoldierAgent::new("Soldier-1")), SpawnOptions::new().reactive())
+        .child_with("soldier_2", Box::new(SoldierAgent::new("Soldier-2")), SpawnOptions::new().reactive());
+    let command = Supervisor::new("command", Strategy::OneForOne)
+        .with_intensity(3, Duration::from_secs(10))
+        .supervisor(unit)
+        .child("commander", Box::new(CommanderAgent::new()));
+
     let runtime = Runtime::new();
-    runtime.spawn(Box::new(SoldierAgent::new("Soldier-1")), "soldier_1").await?;
-    runtime.spawn(Box::new(SoldierAgent::new("Soldier-2")), "soldier_2").await?;
-    runtime.spawn(Box::new(CaptainAgent::new()), "captain").await?;
-    runtime.spawn(Box::new(CommanderAgent::new()), "commander").await?;
+    board.spawn_supervisor(&runtime, command).await?;
 
-    tokio::time::sleep(std::time::Duration::from_secs(4)).await;
+    for (name, level) in [
+        ("commander", strategic), ("captain", tactical), ("soldier_1", operational.clone()), ("soldier_2", operational),
+    ] {
+        if let Some(id) = board.id_of(name) {
+            hierarchy.assign_agent(id, level);
+        }
+    }
+
+    if let Err(err) = runtime.run_until_idle(&board, Duration::from_secs(10)).await {
+        println!("\n  {}", err);
+    }
     runtime.shutdown().await?;
     println!("\n✓ Hierarchy demo complete.");
     Ok(())
@@ -1,22 +1,51 @@
 //! Holarchy: Nested autonomous units. Parent delegates, children act independently.
+//!
+//! Only the company is spawned up front; each composite holon spawns its own
+//! sub-holons, and they are stopped along with it.
 use agentropic_core::{Agent, AgentContext, AgentId, AgentResult};
+use agentropic_examples::{ContextExt, Performative, Switchboard};
 use agentropic_patterns::holarchy::{Holarchy, Holon};
 use agentropic_runtime::prelude::*;
 use async_trait::async_trait;
+use std::time::Duration;
+
+/// Reports the company waits for before it dissolves: one per atomic holon.
+const REPORTS: u32 = 3;
+
+struct HolonAgent {
+    id: AgentId,
+    name: String,
+    is_composite: bool,
+    /// Sub-holons not spawned yet, by runtime name.
+    parts: Vec<(String, HolonAgent)>,
+    delegated: bool,
+    reports: u32,
+}
 
-struct HolonAgent { id: AgentId, name: String, is_composite: bool, delegated: bool }
 impl HolonAgent {
     fn new(name: &str, is_composite: bool) -> Self {
-        Self { id: AgentId::new(), name: name.to_string(), is_composite, delegated: false }
+        let name = name.to_string();
+        Self { id: AgentId::new(), name, is_composite, parts: Vec::new(), delegated: false, reports: 0 }
+    }
+
+    fn with_part(mut self, name: &str, part: HolonAgent) -> Self {
+        self.parts.push((name.to_string(), part));
+        self
     }
 }
 
 #[async_trait]
 impl Agent for HolonAgent {
     fn id(&self) -> &AgentId { &self.id }
-    async fn initialize(&mut self, _ctx: &AgentContext) -> AgentResult<()> {
+    async fn initialize(&mut self, ctx: &AgentContext) -> AgentResult<()> {
         let kind = if self.is_composite { "composite" } else { "atomic" };
         println!("  [{}] Online ({})", self.name, kind);
+        for (name, part) in self.parts.drain(..) {
+            let policy = RestartPolicy::new(RestartStrategy::Never);
+            if let Err(err) = ctx.spawn_child(Box::new(part), &name, policy).await {
+                println!("  [{}] ✗ Could not spawn {}: {}", self.name, name, err);
+            }
+        }
         Ok(())
     }
     async fn execute(&mut self, ctx: &AgentContext) -> AgentResult<()> {
@@ -24,14 +53,14 @@ impl Agent for HolonAgent {
             match self.name.as_str() {
                 "Company" => {
                     println!("  [Company] ↓ Delegating to departments...");
-                    ctx.send_message("engineering", "request", "Build the product");
-                    ctx.send_message("marketing", "request", "Launch the campaign");
+                    ctx.try_send("engineering", Performative::Request, "Build the product")?;
+                    ctx.try_send("marketing", Performative::Request, "Launch the campaign")?;
                     self.delegated = true;
                 }
                 "Engineering" => {
                     println!("  [Engineering] ↓ Delegating to teams...");
-                    ctx.send_message("frontend", "request", "Build the UI");
-                    ctx.send_message("backend", "request", "Build the API");
+                    ctx.try_send("frontend", Performative::Request, "Build the UI")?;
+                    ctx.try_send("backend", Performative::Request, "Build the API")?;
                     self.delegated = true;
                 }
                 _ => {}
@@ -44,18 +73,27 @@ impl Agent for HolonAgent {
         println!("  [{}] Shutdown.", self.name);
         Ok(())
     }
-    async fn handle_message(&mut self, ctx: &AgentContext, _s: &str, _p: &str, content: &str) -> AgentResult<()> {
+    async fn handle_message(&mut self, ctx: &AgentContext, _s: &str, perf: &str, content: &str) -> AgentResult<()> {
         println!("  [{}] ← \"{}\"", self.name, content);
+        let Ok(perf) = perf.parse::<Performative>() else {
+            return Ok(ctx.reply(Performative::NotUnderstood, content)?);
+        };
 
-        if self.is_composite {
+        if self.name == "Company" && perf == Performative::Inform {
+            self.reports += 1;
+            if self.reports == REPORTS {
+                println!("  [Company] All work reported; dissolving.");
+                ctx.stop_self()?;
+            }
+        } else if self.is_composite {
             // Composite holons delegate further (handled in execute)
-        } else {
+        } else if perf == Performative::Request {
             // Atomic holons do the work
             tokio::time::sleep(std::time::Duration::from_millis(400)).await;
             let result = format!("{} done: {}", self.name, content);
             println!("  [{}] ✓ \"{}\"", self.name, result);
             // Report up
-            ctx.send_message("company", "inform", &result);
+            ctx.try_send("company", Performative::Inform, &result)?;
         }
         Ok(())
     }
@@ -63,16 +101,26 @@ impl Agent for HolonAgent {
 
 #[tokio::main]
 async fn main() -> Result<(), RuntimeError> {
+    run().await
+}
+
+pub async fn run() -> Result<(), RuntimeError> {
     println!("╔═══════════════════════════════════════════════╗");
     println!("║   Agentropic — Holarchy Pattern               ║");
     println!("║   Nested autonomous units                     ║");
     println!("╚═══════════════════════════════════════════════╝\n");
 
-    let company_id = AgentId::new();
-    let eng_id = AgentId::new();
-    let mkt_id = AgentId::new();
-    let fe_id = AgentId::new();
-    let be_id = AgentId::new();
+    let frontend = HolonAgent::new("Frontend", false);
+    let backend = HolonAgent::new("Backend", false);
+    let marketing = HolonAgent::new("Marketing", false);
+    let (fe_id, be_id, mkt_id) = (*frontend.id(), *backend.id(), *marketing.id());
+    let engineering = HolonAgent::new("Engineering", true)
+        .with_part("frontend", frontend)
+        .with_part("backend", backend);
+    let eng_id = *engineering.id();
+    let company_agent =
+        HolonAgent::new("Company", true).with_part("engineering", engineering).with_part("marketing", marketing);
+    let company_id = *company_agent.id();
 
     let mut holarchy = Holarchy::new("TechCorp");
     let mut company = Holon::composite(company_id);
@@ -103,13 +151,21 @@ async fn main() -> Result<(), RuntimeError> {
     println!("  Company → [Engineering → [Frontend, Backend], Marketing]\n");
 
     let runtime = Runtime::new();
-    runtime.spawn(Box::new(HolonAgent::new("Frontend", false)), "frontend").await?;
-    runtime.spawn(Box::new(HolonAgent::new("Backend", false)), "backend").await?;
-    runtime.spawn(Box::new(HolonAgent::new("Marketing", false)), "marketing").await?;
-    runtime.spawn(Box::new(HolonAgent::new("Engineering", true)), "engineering").await?;
-    runtime.spawn(Box::new(HolonAgent::new("Company", true)), "company").await?;
+    let board = Switchboard::new();
+    board.spawn(&runtime, Box::new(company_agent), "company").await?;
 
-    tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+    // Stopping the company stops its departments, and Engineering its teams
+    let dissolved = async {
+        board.join("company").await;
+        for holon in ["engineering", "marketing", "frontend", "backend"] {
+            if let Some(status) = board.join(holon).await {
+                println!("  {} ({}): {}", holon, board.parent(holon).unwrap_or_default(), status);
+            }
+        }
+    };
+    if tokio::time::timeout(Duration::from_secs(10), dissolved).await.is_err() {
+        println!("\n  The holarchy was still running after 10s.");
+    }
     runtime.shutdown().await?;
     println!("\n✓ Holarchy demo complete.");
     Ok(())
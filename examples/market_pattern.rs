@@ -1,21 +1,27 @@
 //! Market: Agents bid in an auction. Highest bidder wins the resource.
 use agentropic_core::{Agent, AgentContext, AgentId, AgentResult};
+use agentropic_examples::payload;
+use agentropic_examples::protocol::{ContractNet, ContractNetOutcome, ContractNetParticipant};
+use agentropic_examples::{ContextExt, RequestError, RuntimeExt, Switchboard};
 use agentropic_patterns::market::{Auction, AuctionType, Bid};
 use agentropic_runtime::prelude::*;
 use async_trait::async_trait;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// How long a call for proposals stays open.
+const BIDDING_WINDOW: Duration = Duration::from_secs(2);
 
 struct AuctioneerAgent {
     id: AgentId,
     auction: Arc<Mutex<Auction>>,
+    outcome: Arc<Mutex<Option<Result<ContractNetOutcome, RequestError>>>>,
     announced: bool,
-    resolved: bool,
-    expected_bids: usize,
 }
 
 impl AuctioneerAgent {
-    fn new(auction: Arc<Mutex<Auction>>, expected: usize) -> Self {
-        Self { id: AgentId::new(), auction, announced: false, resolved: false, expected_bids: expected }
+    fn new(auction: Arc<Mutex<Auction>>) -> Self {
+        Self { id: AgentId::new(), auction, outcome: Arc::new(Mutex::new(None)), announced: false }
     }
 }
 
@@ -30,27 +36,56 @@ impl Agent for AuctioneerAgent {
         if !self.announced {
             let resource = self.auction.lock().unwrap().resource().to_string();
             println!("  [Auctioneer] Now auctioning: \"{}\"", resource);
-            ctx.send_message("trader_1", "cfp", &resource);
-            ctx.send_message("trader_2", "cfp", &resource);
-            ctx.send_message("trader_3", "cfp", &resource);
+            let auction = self.auction.clone();
+            // Traders that only get to the call after bidding closes never see it
+            let round = ContractNet::new(&resource)
+                .with_topic("auction")
+                .with_deadline(BIDDING_WINDOW)
+                .start(move |proposals| {
+                    let mut auction = auction.lock().unwrap();
+                    for proposal in proposals {
+                        if let (Ok(bid), Some(bidder)) = (proposal.decode::<payload::Bid>(), proposal.sender_id) {
+                            println!("  [Auctioneer] <- Bid received from {}: ${:.0}", proposal.sender, bid.amount);
+                            auction.add_bid(Bid::new(bidder, bid.amount, &bid.item));
+                        }
+                    }
+                    let Some(winner) = auction.winner() else { return Vec::new() };
+                    let winning = proposals.iter().find(|proposal| proposal.sender_id.as_ref() == Some(winner.bidder()));
+                    winning.map(|proposal| proposal.sender.clone()).into_iter().collect()
+                });
+            let outcome = self.outcome.clone();
+            tokio::spawn(async move {
+                let result = round.await;
+                *outcome.lock().unwrap() = Some(result);
+            });
             self.announced = true;
         }
 
-        if !self.resolved {
-            let auction = self.auction.lock().unwrap();
-            let bid_count = auction.bids().len();
-            if bid_count >= self.expected_bids {
-                println!("\n  [Auctioneer] Bidding closed! {} bids received.", bid_count);
-                for bid in auction.bids() {
-                    println!("    ${:.0}", bid.amount());
-                }
-                if let Some(winner) = auction.winner() {
-                    println!("  [Auctioneer] WINNER: ${:.0}!", winner.amount());
-                } else {
-                    println!("  [Auctioneer] No bids met the reserve price.");
+        let finished = self.outcome.lock().unwrap().take();
+        if let Some(result) = finished {
+            match result {
+                Ok(outcome) => {
+                    let auction = self.auction.lock().unwrap();
+                    let bidder = |bid: &Bid| {
+                        let mut proposals = outcome.proposals.iter();
+                        let proposal = proposals.find(|proposal| proposal.sender_id.as_ref() == Some(bid.bidder()));
+                        proposal.map_or("unknown", |proposal| proposal.sender.as_str())
+                    };
+                    println!("\n  [Auctioneer] Bidding closed! {} bids received.", auction.bids().len());
+                    for bid in auction.bids() {
+                        println!("    {:<10} ${:.0}", bidder(bid), bid.amount());
+                    }
+                    match (auction.winner(), outcome.results.first()) {
+                        (Some(winner), Some(paid)) => {
+                            println!("  [Auctioneer] WINNER: {} at ${:.0}! {}", bidder(winner), winner.amount(), paid.content)
+                        }
+                        (Some(winner), None) => println!("  [Auctioneer] ${:.0} won but never paid.", winner.amount()),
+                        (None, _) => println!("  [Auctioneer] No bids met the reserve price."),
+                    }
                 }
-                self.resolved = true;
+                Err(err) => println!("  [Auctioneer] ✗ {}", err),
             }
+            ctx.stop_self()?;
         }
 
         tokio::time::sleep(std::time::Duration::from_millis(300)).await;
@@ -60,22 +95,12 @@ impl Agent for AuctioneerAgent {
         println!("  [Auctioneer] Auction closed.");
         Ok(())
     }
-    async fn handle_message(&mut self, _ctx: &AgentContext, _s: &str, _p: &str, content: &str) -> AgentResult<()> {
-        if content.starts_with("bid:") {
-            if let Ok(amount) = content[4..].parse::<f64>() {
-                let bid = Bid::new(AgentId::new(), amount, "GPU Cluster");
-                println!("  [Auctioneer] <- Bid received: ${:.0}", amount);
-                self.auction.lock().unwrap().add_bid(bid);
-            }
-        }
-        Ok(())
-    }
 }
 
-struct TraderAgent { id: AgentId, name: String, budget: f64, bid_placed: bool }
+struct TraderAgent { id: AgentId, name: String, budget: f64 }
 impl TraderAgent {
     fn new(name: &str, budget: f64) -> Self {
-        Self { id: AgentId::new(), name: name.to_string(), budget, bid_placed: false }
+        Self { id: AgentId::new(), name: name.to_string(), budget }
     }
 }
 
@@ -91,20 +116,41 @@ impl Agent for TraderAgent {
         Ok(())
     }
     async fn shutdown(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
-    async fn handle_message(&mut self, ctx: &AgentContext, _s: &str, _p: &str, content: &str) -> AgentResult<()> {
-        if !self.bid_placed {
-            let bid_pct = 0.7 + (self.budget % 100.0) / 333.0;
-            let bid_amount = (self.budget * bid_pct).round();
-            println!("  [{}] Bidding ${:.0} for \"{}\"", self.name, bid_amount, content);
-            ctx.send_message("auctioneer", "propose", &format!("bid:{}", bid_amount));
-            self.bid_placed = true;
+    async fn handle_message(&mut self, ctx: &AgentContext, s: &str, p: &str, content: &str) -> AgentResult<()> {
+        self.handle_contract_net(ctx, s, p, content).await
+    }
+}
+
+#[async_trait]
+impl ContractNetParticipant for TraderAgent {
+    async fn propose(&mut self, ctx: &AgentContext, _initiator: &str, item: &str) -> Option<String> {
+        if ctx.time_left().is_some_and(|left| left.is_zero()) {
+            println!("  [{}] Bidding on \"{}\" has closed", self.name, item);
+            return None;
         }
-        Ok(())
+        let bid_pct = 0.7 + (self.budget % 100.0) / 333.0;
+        let bid_amount = (self.budget * bid_pct).round();
+        println!("  [{}] Bidding ${:.0} for \"{}\"", self.name, bid_amount, item);
+        payload::encode(&payload::Bid { item: item.to_string(), amount: bid_amount }).ok()
+    }
+
+    async fn perform(&mut self, _ctx: &AgentContext, _initiator: &str, bid: &str) -> Result<String, String> {
+        let bid: payload::Bid = payload::decode(bid).map_err(|err| err.to_string())?;
+        println!("  [{}] Won \"{}\"!", self.name, bid.item);
+        Ok(format!("{} paid ${:.0}.", self.name, bid.amount))
+    }
+
+    async fn rejected(&mut self, _ctx: &AgentContext, _initiator: &str, _bid: &str) {
+        println!("  [{}] Outbid.", self.name);
     }
 }
 
 #[tokio::main]
 async fn main() -> Result<(), RuntimeError> {
+    run().await
+}
+
+pub async fn run() -> Result<(), RuntimeError> {
     println!("=== Agentropic — Market Pattern ===");
     println!("    Sealed-bid auction for GPU cluster\n");
 
@@ -120,12 +166,23 @@ async fn main() -> Result<(), RuntimeError> {
     println!("  Reserve price: $5,000\n");
 
     let runtime = Runtime::new();
-    runtime.spawn(Box::new(TraderAgent::new("Trader-1", 8000.0)), "trader_1").await?;
-    runtime.spawn(Box::new(TraderAgent::new("Trader-2", 12000.0)), "trader_2").await?;
-    runtime.spawn(Box::new(TraderAgent::new("Trader-3", 6500.0)), "trader_3").await?;
-    runtime.spawn(Box::new(AuctioneerAgent::new(auction.clone(), 3)), "auctioneer").await?;
+    let board = Switchboard::new();
+    for (name, trader) in [
+        ("trader_1", TraderAgent::new("Trader-1", 8000.0)),
+        ("trader_2", TraderAgent::new("Trader-2", 12000.0)),
+        ("trader_3", TraderAgent::new("Trader-3", 6500.0)),
+    ] {
+        board.subscribe("auction", name);
+        board.spawn(&runtime, Box::new(trader), name).await?;
+    }
+    board.spawn(&runtime, Box::new(AuctioneerAgent::new(auction.clone())), "auctioneer").await?;
 
-    tokio::time::sleep(std::time::Duration::from_secs(4)).await;
+    // The auctioneer stops itself once bidding has closed
+    match tokio::time::timeout(Duration::from_secs(10), runtime.join(&board, "auctioneer")).await {
+        Ok(Some(status)) => println!("\n  Auctioneer {}.", status),
+        Ok(None) => println!("\n  Auctioneer was never spawned."),
+        Err(_) => println!("\n  Auction still open after 10s."),
+    }
     runtime.shutdown().await?;
     println!("\n=== Market demo complete ===");
     Ok(())
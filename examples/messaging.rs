@@ -1,7 +1,9 @@
 //! Two agents exchanging messages through the Runtime's Router.
 use agentropic_core::{Agent, AgentContext, AgentId, AgentResult};
+use agentropic_examples::{ContextExt, Performative, RuntimeExt, Switchboard};
 use agentropic_runtime::prelude::*;
 use async_trait::async_trait;
+use std::time::Duration;
 
 struct PingAgent {
     id: AgentId,
@@ -24,7 +26,11 @@ impl Agent for PingAgent {
     async fn execute(&mut self, ctx: &AgentContext) -> AgentResult<()> {
         if !self.pinged {
             println!("  [Ping] → Sending 'ping!' to Pong");
-            ctx.send_message("pong", "request", "ping!");
+            ctx.try_send("pong", Performative::Request, "ping!")?;
+            // A misspelled name is reported instead of vanishing
+            if let Err(err) = ctx.try_send("pnog", Performative::Request, "ping?") {
+                println!("  [Ping] ✗ Not sent: {}", err);
+            }
             self.pinged = true;
         }
         tokio::time::sleep(std::time::Duration::from_millis(200)).await;
@@ -61,10 +67,7 @@ impl Agent for PongAgent {
         Ok(())
     }
 
-    async fn execute(&mut self, _ctx: &AgentContext) -> AgentResult<()> {
-        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
-        Ok(())
-    }
+    async fn execute(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
 
     async fn shutdown(&mut self, _ctx: &AgentContext) -> AgentResult<()> {
         println!("  [Pong] Done.");
@@ -72,24 +75,39 @@ impl Agent for PongAgent {
     }
 
     async fn handle_message(
-        &mut self, ctx: &AgentContext, sender: &str, _perf: &str, content: &str,
+        &mut self, ctx: &AgentContext, sender: &str, perf: &str, content: &str,
     ) -> AgentResult<()> {
         println!("  [Pong] ← Received: \"{}\"", content);
-        println!("  [Pong] → Replying: \"pong!\"");
-        ctx.send_message(sender, "inform", "pong!");
+        match perf.parse() {
+            Ok(Performative::Request) => {
+                println!("  [Pong] → Replying: \"pong!\"");
+                ctx.try_send(sender, Performative::Inform, "pong!")?;
+            }
+            _ => ctx.try_send(sender, Performative::NotUnderstood, content)?,
+        }
         Ok(())
     }
 }
 
 #[tokio::main]
 async fn main() -> Result<(), RuntimeError> {
+    run().await
+}
+
+pub async fn run() -> Result<(), RuntimeError> {
     println!("=== Messaging Example ===\n");
 
     let runtime = Runtime::new();
-    runtime.spawn(Box::new(PongAgent::new()), "pong").await?;
-    runtime.spawn(Box::new(PingAgent::new()), "ping").await?;
+    let board = Switchboard::new();
+    board.spawn_reactive(&runtime, Box::new(PongAgent::new()), "pong").await?;
+    board.spawn(&runtime, Box::new(PingAgent::new()), "ping").await?;
 
-    tokio::time::sleep(std::time::Duration::from_secs(2)).await;
+    if let Err(err) = runtime.run_until_idle(&board, Duration::from_secs(5)).await {
+        println!("  {}", err);
+    }
+    for dead in board.dead_letters() {
+        println!("  Undelivered from {} to {}: \"{}\" ({})", dead.sender, dead.receiver, dead.content, dead.reason);
+    }
 
     runtime.shutdown().await?;
     println!("\n=== Done ===");
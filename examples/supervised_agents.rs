@@ -1,19 +1,25 @@
 //! Agent crashes on purpose, Supervisor restarts it automatically.
 use agentropic_core::{Agent, AgentContext, AgentId, AgentError, AgentResult};
+use agentropic_examples::{
+    Checkpointed, ContextExt, Performative, Recovery, RestartState, SpawnOptions, Stateful, Switchboard, CIRCUIT_TOPIC,
+};
 use agentropic_runtime::prelude::*;
 use async_trait::async_trait;
-use std::sync::atomic::{AtomicU32, Ordering};
-use std::sync::Arc;
+use std::time::Duration;
+
+/// Steps the agent completes before it stops itself.
+const STEPS: u32 = 5;
 
 struct FlakyAgent {
     id: AgentId,
-    counter: Arc<AtomicU32>,
-    fail_until: u32,
+    completed: u32,
+    /// How many more times it crashes; this is the fault, not the agent's state.
+    crashes_left: u32,
 }
 
 impl FlakyAgent {
-    fn new(counter: Arc<AtomicU32>, fail_until: u32) -> Self {
-        Self { id: AgentId::new(), counter, fail_until }
+    fn new(crashes: u32) -> Self {
+        Self { id: AgentId::new(), completed: 0, crashes_left: crashes }
     }
 }
 
@@ -22,19 +28,23 @@ impl Agent for FlakyAgent {
     fn id(&self) -> &AgentId { &self.id }
 
     async fn initialize(&mut self, _ctx: &AgentContext) -> AgentResult<()> {
-        println!("  [Flaky] Initialized (attempt {})", self.counter.load(Ordering::SeqCst) + 1);
+        println!("  [Flaky] Initialized with {} steps completed", self.completed);
         Ok(())
     }
 
-    async fn execute(&mut self, _ctx: &AgentContext) -> AgentResult<()> {
-        let count = self.counter.fetch_add(1, Ordering::SeqCst);
+    async fn execute(&mut self, ctx: &AgentContext) -> AgentResult<()> {
+        self.completed += 1;
 
-        if count < self.fail_until {
-            println!("  [Flaky] Crashing! (execution #{})", count + 1);
-            return Err(AgentError::ExecutionFailed(format!("Intentional crash #{}", count + 1)));
+        if self.completed.is_multiple_of(2) && self.crashes_left > 0 {
+            self.crashes_left -= 1;
+            println!("  [Flaky] Crashing halfway through step {}!", self.completed);
+            return Err(AgentError::ExecutionFailed(format!("Intentional crash in step {}", self.completed)));
         }
 
-        println!("  [Flaky] ✓ Running normally (execution #{})", count + 1);
+        println!("  [Flaky] ✓ Completed step {}", self.completed);
+        if self.completed == STEPS {
+            ctx.stop_self()?;
+        }
         tokio::time::sleep(std::time::Duration::from_millis(300)).await;
         Ok(())
     }
@@ -45,26 +55,83 @@ impl Agent for FlakyAgent {
     }
 }
 
+impl Stateful for FlakyAgent {
+    type State = u32;
+    fn snapshot(&self) -> u32 { self.completed }
+    fn restore(&mut self, completed: u32) { self.completed = completed; }
+}
+
+/// Fails every time it runs.
+struct DoomedAgent { id: AgentId }
+
+#[async_trait]
+impl Agent for DoomedAgent {
+    fn id(&self) -> &AgentId { &self.id }
+    async fn initialize(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
+    async fn execute(&mut self, _ctx: &AgentContext) -> AgentResult<()> {
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        Err(AgentError::ExecutionFailed("disk unreachable".into()))
+    }
+    async fn shutdown(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
+}
+
+/// Reports agents whose circuit breaker opens or closes.
+struct WatchdogAgent { id: AgentId }
+
+#[async_trait]
+impl Agent for WatchdogAgent {
+    fn id(&self) -> &AgentId { &self.id }
+    async fn initialize(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
+    async fn execute(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
+    async fn shutdown(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
+    async fn handle_message(&mut self, _ctx: &AgentContext, sender: &str, perf: &str, content: &str) -> AgentResult<()> {
+        match perf.parse() {
+            Ok(Performative::Failure) => println!("  [Watchdog] {} parked: {}", sender, content),
+            _ => println!("  [Watchdog] {} {}", sender, content),
+        }
+        Ok(())
+    }
+}
+
 #[tokio::main]
 async fn main() -> Result<(), RuntimeError> {
+    run().await
+}
+
+pub async fn run() -> Result<(), RuntimeError> {
     println!("=== Supervised Agent ===\n");
-    println!("  Agent will crash 3 times, then run normally.\n");
+    println!("  Flaky will crash 3 times, resuming from its last completed step each time.");
+    println!("  Doomed always crashes, until its circuit breaker parks it.\n");
 
     let runtime = Runtime::new();
-    let counter = Arc::new(AtomicU32::new(0));
+    let board = Switchboard::new();
 
-    let agent = FlakyAgent::new(counter.clone(), 3);
-    let policy = RestartPolicy::new(RestartStrategy::OnFailure)
-        .with_max_retries(5)
-        .with_backoff_seconds(1);
+    board.subscribe(CIRCUIT_TOPIC, "watchdog");
+    board.spawn_reactive(&runtime, Box::new(WatchdogAgent { id: AgentId::new() }), "watchdog").await?;
 
-    runtime.spawn_with_policy(Box::new(agent), "flaky", policy).await?;
+    let agent = Checkpointed::new(FlakyAgent::new(3));
+    let recovery = Recovery::new()
+        .with_backoff(Duration::from_millis(200), Duration::from_secs(2))
+        .with_jitter(0.2);
+    let options = SpawnOptions::new().with_recovery(recovery).with_restart_state(RestartState::LastCheckpoint);
+    board.spawn_with(&runtime, Box::new(agent), "flaky", options).await?;
 
-    // Wait for crashes + restarts + normal execution
-    tokio::time::sleep(std::time::Duration::from_secs(8)).await;
+    let recovery = Recovery::new()
+        .with_backoff(Duration::from_millis(100), Duration::from_secs(1))
+        .with_failure_window(3, Duration::from_secs(10))
+        .with_cooldown(Duration::from_secs(2));
+    let options = SpawnOptions::new().with_recovery(recovery);
+    board.spawn_with(&runtime, Box::new(DoomedAgent { id: AgentId::new() }), "doomed", options).await?;
 
-    let total = counter.load(Ordering::SeqCst);
-    println!("\n  Total executions: {} (3 crashes + {} successful)", total, total - 3);
+    // The agent stops itself once every step is done
+    match tokio::time::timeout(Duration::from_secs(15), board.join("flaky")).await {
+        Ok(Some(status)) => println!("\n  Flaky {} after {} steps.", status, STEPS),
+        Ok(None) => println!("\n  Flaky was never spawned."),
+        Err(_) => println!("\n  Flaky still running after 15s."),
+    }
+    if let Some(circuit) = board.circuit("doomed") {
+        println!("  Doomed's circuit is {}.", circuit);
+    }
 
     runtime.shutdown().await?;
     println!("\n=== Done ===");
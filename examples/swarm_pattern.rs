@@ -1,9 +1,11 @@
 //! Swarm: Decentralized agents vote to reach consensus without a leader.
 use agentropic_core::{Agent, AgentContext, AgentId, AgentResult};
+use agentropic_examples::{ContextExt, Performative, RuntimeExt, Switchboard};
 use agentropic_patterns::swarm::Swarm;
 use agentropic_runtime::prelude::*;
 use async_trait::async_trait;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 #[derive(Clone)]
 struct VoteBoard {
@@ -22,7 +24,7 @@ impl VoteBoard {
             *counts.entry(choice.clone()).or_insert(0) += 1;
         }
         let mut result: Vec<_> = counts.into_iter().collect();
-        result.sort_by(|a, b| b.1.cmp(&a.1));
+        result.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
         result
     }
     fn count(&self) -> usize { self.votes.lock().unwrap().len() }
@@ -51,13 +53,9 @@ impl Agent for SwarmAgent {
     }
     async fn execute(&mut self, ctx: &AgentContext) -> AgentResult<()> {
         if !self.voted {
-            // Broadcast vote to all peers
+            // Publish vote to the rest of the swarm
             let msg = format!("vote:{}", self.preference);
-            for peer in &["scout_1", "scout_2", "scout_3", "scout_4", "scout_5"] {
-                if *peer != self.name.to_lowercase().replace("-", "_").replace(" ", "_") {
-                    ctx.send_message(peer, "inform", &msg);
-                }
-            }
+            ctx.publish("scouts", Performative::Inform, &msg)?;
             self.board.cast(&self.name, &self.preference);
             println!("  [{}]  Voted: {}", self.name, self.preference);
             self.voted = true;
@@ -88,7 +86,7 @@ impl TallyAgent {
 impl Agent for TallyAgent {
     fn id(&self) -> &AgentId { &self.id }
     async fn initialize(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
-    async fn execute(&mut self, _ctx: &AgentContext) -> AgentResult<()> {
+    async fn execute(&mut self, ctx: &AgentContext) -> AgentResult<()> {
         if !self.reported && self.board.count() >= self.expected {
             println!("\n  [Tally] Consensus Results:");
             for (choice, count) in self.board.tally() {
@@ -99,6 +97,7 @@ impl Agent for TallyAgent {
             println!("    → Swarm decision: {}", winner);
             self.reported = true;
         }
+        ctx.set_busy(!self.reported)?;
         tokio::time::sleep(std::time::Duration::from_millis(200)).await;
         Ok(())
     }
@@ -107,15 +106,19 @@ impl Agent for TallyAgent {
 
 #[tokio::main]
 async fn main() -> Result<(), RuntimeError> {
+    run().await
+}
+
+pub async fn run() -> Result<(), RuntimeError> {
     println!("╔═══════════════════════════════════════════════╗");
     println!("║   Agentropic — Swarm Pattern                  ║");
     println!("║   Decentralized consensus voting              ║");
     println!("╚═══════════════════════════════════════════════╝\n");
 
     let mut swarm = Swarm::new("Scout Swarm");
-    let board = VoteBoard::new();
+    let votes = VoteBoard::new();
 
-    let agents_data = vec![
+    let agents_data = [
         ("Scout-1", "Route A"),
         ("Scout-2", "Route B"),
         ("Scout-3", "Route A"),
@@ -123,20 +126,28 @@ async fn main() -> Result<(), RuntimeError> {
         ("Scout-5", "Route B"),
     ];
 
+    let agents: Vec<_> = agents_data.iter()
+        .map(|(name, pref)| (name.to_lowercase().replace("-", "_"), SwarmAgent::new(name, pref, votes.clone())))
+        .collect();
+    for (_, agent) in &agents {
+        swarm.add_member(*agent.id());
+    }
+
     let runtime = Runtime::new();
+    let board = Switchboard::new();
+    board.group("scouts", &swarm);
 
-    for (name, pref) in &agents_data {
-        let agent = SwarmAgent::new(name, pref, board.clone());
-        swarm.add_member(*agent.id());
-        let runtime_name = name.to_lowercase().replace("-", "_");
-        runtime.spawn(Box::new(agent), &runtime_name).await?;
+    for (runtime_name, agent) in agents {
+        board.spawn(&runtime, Box::new(agent), &runtime_name).await?;
     }
 
-    runtime.spawn(Box::new(TallyAgent::new(board.clone(), 5)), "tally").await?;
+    board.spawn(&runtime, Box::new(TallyAgent::new(votes.clone(), 5)), "tally").await?;
 
     println!("  Swarm: \"{}\" ({} members)\n", swarm.name(), swarm.size());
 
-    tokio::time::sleep(std::time::Duration::from_secs(3)).await;
+    if let Err(err) = runtime.run_until_idle(&board, Duration::from_secs(10)).await {
+        println!("\n  {}", err);
+    }
     runtime.shutdown().await?;
     println!("\n✓ Swarm demo complete.");
     Ok(())
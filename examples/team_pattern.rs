@@ -1,9 +1,21 @@
 //! Team Pattern: Leader assigns tasks, Executors work, Coordinator tracks progress.
 //! Uses Team, Role, and RoleType from agentropic-patterns with real running agents.
 use agentropic_core::{Agent, AgentContext, AgentId, AgentResult};
+use agentropic_examples::payload::{self, TaskAssignment, TaskCompletion};
+use agentropic_examples::{
+    Budget, ContextExt, MailboxConfig, Overflow, Performative, RuntimeExt, SendError, Shutdown, SpawnOptions,
+    Switchboard, TypedHandler,
+};
 use agentropic_patterns::team::{Team, Role, RoleType};
 use agentropic_runtime::prelude::*;
 use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// What executors register in the switchboard's directory.
+const EXECUTES_TASKS: &str = "task-execution";
 
 // ── Leader Agent ────────────────────────────────────────────────────
 
@@ -44,20 +56,26 @@ impl Agent for LeaderAgent {
     }
 
     async fn execute(&mut self, ctx: &AgentContext) -> AgentResult<()> {
-        if self.task_index < self.tasks.len() {
+        let executors = ctx.find_agents(EXECUTES_TASKS)?;
+        if self.task_index < self.tasks.len() && executors.is_empty() {
+            println!("  [Leader] No executors available yet");
+        } else if self.task_index < self.tasks.len() {
             let task = self.tasks[self.task_index];
-            // Round-robin between executors
-            let executor = match self.task_index % 3 {
-                0 => "executor_1",
-                1 => "executor_2",
-                _ => "executor_3",
-            };
-
-            println!("  [Leader] → Assigning to {}: \"{}\"", executor, task);
-            ctx.send_message(executor, "request", task);
-            ctx.send_message("coordinator", "inform", &format!("assigned:{}", task));
-            self.task_index += 1;
-            self.assigned += 1;
+            // Round-robin between whichever executors are registered
+            let executor = executors[self.task_index % executors.len()].as_str();
+
+            let assignment = TaskAssignment { task: task.to_string(), executor: executor.to_string() };
+            // Executors have small mailboxes; a full one means try again next tick
+            match ctx.try_send(executor, Performative::Request, &payload::encode(&assignment)?) {
+                Ok(()) => {
+                    println!("  [Leader] → Assigned to {}: \"{}\"", executor, task);
+                    ctx.send_payload("coordinator", Performative::Inform, &Progress::Assigned(assignment))?;
+                    self.task_index += 1;
+                    self.assigned += 1;
+                }
+                Err(SendError::MailboxFull { .. }) => println!("  [Leader] {} is backed up, retrying", executor),
+                Err(err) => return Err(err.into()),
+            }
         }
 
         tokio::time::sleep(std::time::Duration::from_millis(800)).await;
@@ -70,14 +88,22 @@ impl Agent for LeaderAgent {
     }
 
     async fn handle_message(
-        &mut self, _ctx: &AgentContext, sender: &str, _perf: &str, content: &str,
+        &mut self, ctx: &AgentContext, sender: &str, perf: &str, content: &str,
     ) -> AgentResult<()> {
-        if content.starts_with("done:") {
-            self.completed += 1;
-            let task = &content[5..];
-            println!("  [Leader] ← {} finished: \"{}\" ({}/{})",
-                sender, task, self.completed, self.assigned);
-        }
+        self.handle_payload(ctx, sender, perf, content).await
+    }
+}
+
+#[async_trait]
+impl TypedHandler for LeaderAgent {
+    type Payload = TaskCompletion;
+
+    async fn handle_typed_message(
+        &mut self, _ctx: &AgentContext, sender: &str, _perf: Performative, done: TaskCompletion,
+    ) -> AgentResult<()> {
+        self.completed += 1;
+        println!("  [Leader] ← {} finished: \"{}\" ({}/{})",
+            sender, done.task, self.completed, self.assigned);
         Ok(())
     }
 }
@@ -100,15 +126,13 @@ impl ExecutorAgent {
 impl Agent for ExecutorAgent {
     fn id(&self) -> &AgentId { &self.id }
 
-    async fn initialize(&mut self, _ctx: &AgentContext) -> AgentResult<()> {
+    async fn initialize(&mut self, ctx: &AgentContext) -> AgentResult<()> {
+        ctx.register_capability(EXECUTES_TASKS)?;
         println!("  [{}] Ready for tasks.", self.name);
         Ok(())
     }
 
-    async fn execute(&mut self, _ctx: &AgentContext) -> AgentResult<()> {
-        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
-        Ok(())
-    }
+    async fn execute(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
 
     async fn shutdown(&mut self, _ctx: &AgentContext) -> AgentResult<()> {
         println!("  [{}] Completed {} tasks.", self.name, self.tasks_done);
@@ -116,21 +140,38 @@ impl Agent for ExecutorAgent {
     }
 
     async fn handle_message(
-        &mut self, ctx: &AgentContext, _sender: &str, _perf: &str, content: &str,
+        &mut self, ctx: &AgentContext, sender: &str, perf: &str, content: &str,
     ) -> AgentResult<()> {
-        println!("  [{}] ← Received task: \"{}\"", self.name, content);
+        self.handle_payload(ctx, sender, perf, content).await
+    }
+}
+
+#[async_trait]
+impl TypedHandler for ExecutorAgent {
+    type Payload = TaskAssignment;
+
+    async fn handle_typed_message(
+        &mut self, ctx: &AgentContext, sender: &str, perf: Performative, assignment: TaskAssignment,
+    ) -> AgentResult<()> {
+        if perf != Performative::Request {
+            ctx.try_send(sender, Performative::NotUnderstood, &assignment.task)?;
+            return Ok(());
+        }
+        let task = assignment.task;
+        println!("  [{}] ← Received task: \"{}\"", self.name, task);
 
         // Simulate work
-        let work_ms = 300 + (content.len() as u64 * 20);
+        let work_ms = 300 + (task.len() as u64 * 20);
         println!("  [{}]  Working... ({}ms)", self.name, work_ms);
         tokio::time::sleep(std::time::Duration::from_millis(work_ms)).await;
 
         self.tasks_done += 1;
-        println!("  [{}] ✓ Done: \"{}\"", self.name, content);
+        println!("  [{}] ✓ Done: \"{}\"", self.name, task);
 
         // Report back to leader and coordinator
-        ctx.send_message("leader", "inform", &format!("done:{}", content));
-        ctx.send_message("coordinator", "inform", &format!("completed:{}", content));
+        let done = TaskCompletion { task, executor: assignment.executor, duration_ms: work_ms };
+        ctx.send_payload("leader", Performative::Inform, &done)?;
+        ctx.send_payload("coordinator", Performative::Inform, &Progress::Completed(done))?;
 
         Ok(())
     }
@@ -138,15 +179,24 @@ impl Agent for ExecutorAgent {
 
 // ── Coordinator Agent ───────────────────────────────────────────────
 
+/// Everything the coordinator hears about: new assignments and completions.
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "progress", rename_all = "snake_case")]
+enum Progress {
+    Completed(TaskCompletion),
+    Assigned(TaskAssignment),
+}
+
 struct CoordinatorAgent {
     id: AgentId,
     assigned: Vec<String>,
     completed: Vec<String>,
+    done: Arc<AtomicUsize>,
 }
 
 impl CoordinatorAgent {
-    fn new() -> Self {
-        Self { id: AgentId::new(), assigned: Vec::new(), completed: Vec::new() }
+    fn new(done: Arc<AtomicUsize>) -> Self {
+        Self { id: AgentId::new(), assigned: Vec::new(), completed: Vec::new(), done }
     }
 }
 
@@ -159,16 +209,14 @@ impl Agent for CoordinatorAgent {
         Ok(())
     }
 
-    async fn execute(&mut self, _ctx: &AgentContext) -> AgentResult<()> {
-        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
-        Ok(())
-    }
+    async fn execute(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
 
     async fn shutdown(&mut self, _ctx: &AgentContext) -> AgentResult<()> {
         println!("\n  [Coordinator] === Team Report ===");
         println!("    Tasks assigned:  {}", self.assigned.len());
         println!("    Tasks completed: {}", self.completed.len());
-        let pending = self.assigned.len() - self.completed.len();
+        // A completion can be reported before its assignment arrives
+        let pending = self.assigned.iter().filter(|task| !self.completed.contains(task)).count();
         if pending > 0 {
             println!("    Tasks pending:   {}", pending);
         } else {
@@ -178,15 +226,26 @@ impl Agent for CoordinatorAgent {
     }
 
     async fn handle_message(
-        &mut self, _ctx: &AgentContext, _sender: &str, _perf: &str, content: &str,
+        &mut self, ctx: &AgentContext, sender: &str, perf: &str, content: &str,
+    ) -> AgentResult<()> {
+        self.handle_payload(ctx, sender, perf, content).await
+    }
+}
+
+#[async_trait]
+impl TypedHandler for CoordinatorAgent {
+    type Payload = Progress;
+
+    async fn handle_typed_message(
+        &mut self, _ctx: &AgentContext, _sender: &str, _perf: Performative, progress: Progress,
     ) -> AgentResult<()> {
-        if content.starts_with("assigned:") {
-            let task = content[9..].to_string();
-            self.assigned.push(task);
-        } else if content.starts_with("completed:") {
-            let task = content[10..].to_string();
-            self.completed.push(task);
-            println!("  [Coordinator] Progress: {}/{}", self.completed.len(), self.assigned.len());
+        match progress {
+            Progress::Assigned(assignment) => self.assigned.push(assignment.task),
+            Progress::Completed(done) => {
+                self.completed.push(done.task);
+                self.done.store(self.completed.len(), Ordering::SeqCst);
+                println!("  [Coordinator] Progress: {}/{}", self.completed.len(), self.assigned.len());
+            }
         }
         Ok(())
     }
@@ -194,17 +253,24 @@ impl Agent for CoordinatorAgent {
 
 #[tokio::main]
 async fn main() -> Result<(), RuntimeError> {
+    run().await
+}
+
+pub async fn run() -> Result<(), RuntimeError> {
     println!("╔═══════════════════════════════════════════════╗");
     println!("║   Agentropic — Team Pattern Demo              ║");
     println!("║   Leader → Executors → Coordinator            ║");
     println!("╚═══════════════════════════════════════════════╝\n");
 
     // --- Define the team structure using patterns crate ---
-    let executor_1_id = AgentId::new();
-    let executor_2_id = AgentId::new();
-    let executor_3_id = AgentId::new();
-    let leader_id = AgentId::new();
-    let coord_id = AgentId::new();
+    let done = Arc::new(AtomicUsize::new(0));
+    let executor_1 = ExecutorAgent::new("Executor-1");
+    let executor_2 = ExecutorAgent::new("Executor-2");
+    let executor_3 = ExecutorAgent::new("Executor-3");
+    let leader = LeaderAgent::new();
+    let coordinator = CoordinatorAgent::new(done.clone());
+    let (executor_1_id, executor_2_id, executor_3_id) = (*executor_1.id(), *executor_2.id(), *executor_3.id());
+    let (leader_id, coord_id) = (*leader.id(), *coordinator.id());
 
     let mut team = Team::new("Trading Operations");
 
@@ -237,18 +303,48 @@ async fn main() -> Result<(), RuntimeError> {
 
     // --- Spawn real agents with the Runtime ---
     let runtime = Runtime::new();
+    let board = Switchboard::new();
+
+    // On shutdown the leader stops handing out work first, then the
+    // executors finish theirs, and the coordinator hears every report
+    board.spawn_reactive(&runtime, Box::new(coordinator), "coordinator").await?;
+    // A task still running after a second is cancelled rather than holding the executor up
+    let executor_options = || {
+        SpawnOptions::new()
+            .reactive()
+            .with_mailbox(MailboxConfig::bounded(2, Overflow::Reject))
+            .with_budget(Budget::new().with_max_handler(Duration::from_secs(1)).with_max_rate(5))
+            .depends_on("coordinator")
+    };
+    board.spawn_with(&runtime, Box::new(executor_1), "executor_1", executor_options()).await?;
+    board.spawn_with(&runtime, Box::new(executor_2), "executor_2", executor_options()).await?;
+    board.spawn_with(&runtime, Box::new(executor_3), "executor_3", executor_options()).await?;
+    let leader_options = ["executor_1", "executor_2", "executor_3", "coordinator"]
+        .iter()
+        .fold(SpawnOptions::new(), |options, name| options.depends_on(name));
+    board.spawn_with(&runtime, Box::new(leader), "leader", leader_options).await?;
+
+    // Wait for all tasks to be completed; the shutdown waits for the last reports
+    let tasks = 6;
+    if let Err(err) = runtime.run_until(|| done.load(Ordering::SeqCst) >= tasks, Duration::from_secs(30)).await {
+        println!("\n  {}", err);
+    }
 
-    runtime.spawn(Box::new(CoordinatorAgent::new()), "coordinator").await?;
-    runtime.spawn(Box::new(ExecutorAgent::new("Executor-1")), "executor_1").await?;
-    runtime.spawn(Box::new(ExecutorAgent::new("Executor-2")), "executor_2").await?;
-    runtime.spawn(Box::new(ExecutorAgent::new("Executor-3")), "executor_3").await?;
-    runtime.spawn(Box::new(LeaderAgent::new()), "leader").await?;
-
-    // Wait for all tasks to be assigned and completed
-    tokio::time::sleep(std::time::Duration::from_secs(10)).await;
+    for name in board.names() {
+        if let (Some(stats), Some(usage)) = (board.mailbox(&name), board.usage(&name)) {
+            println!("  Mailbox {:<12} depth {} dropped {}, handled {} in {:?} of running time",
+                name, stats.depth, stats.dropped, usage.messages, usage.poll_time);
+        }
+    }
 
     println!("\n--- Shutting down ---\n");
-    runtime.shutdown().await?;
+    let report = board.shutdown(&runtime, Shutdown::new().with_drain_deadline(Duration::from_secs(5))).await?;
+    println!("\n  Stopped in order: {}", report.stopped.join(", "));
+    if !report.is_clean() {
+        println!("  Not drained in time: {}", !report.drained);
+        println!("  Shutdown timed out: {:?}", report.timed_out);
+        println!("  Force-killed: {:?}", report.force_killed);
+    }
 
     println!("\n✓ Team pattern demo complete.");
     Ok(())
@@ -0,0 +1,180 @@
+//! Limits on how much of the runtime one managed agent may take, and
+//! accounting of what it used.
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// The window [`Budget::with_max_rate`] counts messages in.
+const RATE_WINDOW: Duration = Duration::from_secs(1);
+
+/// Limits enforced on a managed agent; set them with
+/// [`SpawnOptions::with_budget`](crate::SpawnOptions::with_budget).
+///
+/// Whenever the agent goes over one it is reported as a
+/// [`Lifecycle::OverBudget`](crate::Lifecycle::OverBudget) event and counted
+/// in its [`Usage`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Budget {
+    max_handler: Option<Duration>,
+    max_rate: Option<u32>,
+}
+
+impl Budget {
+    /// No limits.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancel a `handle_message` still running after `limit`, as
+    /// [`Timeouts::with_handle_message`](crate::Timeouts::with_handle_message)
+    /// would, and report it as a [`Violation::SlowHandler`].
+    pub fn with_max_handler(mut self, limit: Duration) -> Self {
+        self.max_handler = Some(limit);
+        self
+    }
+
+    /// Handle at most `messages` messages and timer events a second; the
+    /// rest wait in the mailbox for the next second.
+    pub fn with_max_rate(mut self, messages: u32) -> Self {
+        self.max_rate = Some(messages.max(1));
+        self
+    }
+
+    pub fn max_handler(&self) -> Option<Duration> {
+        self.max_handler
+    }
+
+    pub fn max_rate(&self) -> Option<u32> {
+        self.max_rate
+    }
+}
+
+/// How a managed agent went over its [`Budget`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Violation {
+    /// Handling a message from `sender` took longer than `limit` and was cancelled.
+    SlowHandler { sender: String, limit: Duration },
+    /// More than `limit` messages arrived in a second; the rest were held back.
+    Throttled { limit: u32 },
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Violation::SlowHandler { sender, limit } => {
+                write!(f, "handling a message from {} took over {:?}", sender, limit)
+            }
+            Violation::Throttled { limit } => write!(f, "more than {} messages a second", limit),
+        }
+    }
+}
+
+/// What a managed agent has used so far, from
+/// [`Switchboard::usage`](crate::Switchboard::usage).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Usage {
+    /// Time spent running the agent's `initialize`, `execute` and
+    /// `handle_message`, not counting time they spent waiting.
+    pub poll_time: Duration,
+    /// Messages and timer events handed to the agent.
+    pub messages: u64,
+    /// Bytes of message content queued for the agent and not handled yet.
+    ///
+    /// This is what stands in for the agent's allocations in flight: agents
+    /// share one allocator, so memory their own code allocates cannot be
+    /// told apart and is not counted.
+    pub queued_bytes: usize,
+    /// Times it went over its [`Budget`].
+    pub violations: u64,
+}
+
+/// Running totals of one agent's [`Usage`], shared with its switchboard.
+#[derive(Default)]
+pub(crate) struct Meter {
+    poll_nanos: AtomicU64,
+    messages: AtomicU64,
+    violations: AtomicU64,
+}
+
+impl Meter {
+    pub fn handled(&self) {
+        self.messages.fetch_add(1, Ordering::SeqCst);
+    }
+
+    pub fn violated(&self) {
+        self.violations.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// Time `future` spends being polled.
+    pub fn time<F: Future + Unpin>(&self, future: F) -> Timed<'_, F> {
+        Timed { future, meter: self }
+    }
+
+    /// The totals, with `queued_bytes` from the agent's mailbox.
+    pub fn usage(&self, queued_bytes: usize) -> Usage {
+        Usage {
+            poll_time: Duration::from_nanos(self.poll_nanos.load(Ordering::SeqCst)),
+            messages: self.messages.load(Ordering::SeqCst),
+            queued_bytes,
+            violations: self.violations.load(Ordering::SeqCst),
+        }
+    }
+}
+
+/// A future whose polls are added to a [`Meter`].
+pub(crate) struct Timed<'a, F> {
+    future: F,
+    meter: &'a Meter,
+}
+
+impl<F: Future + Unpin> Future for Timed<'_, F> {
+    type Output = F::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // Wall-clock time, so it is measured even on a simulated clock
+        let started = std::time::Instant::now();
+        let poll = Pin::new(&mut self.future).poll(cx);
+        let nanos = u64::try_from(started.elapsed().as_nanos()).unwrap_or(u64::MAX);
+        self.meter.poll_nanos.fetch_add(nanos, Ordering::SeqCst);
+        poll
+    }
+}
+
+/// Counts messages against [`Budget::with_max_rate`].
+pub(crate) struct Throttle {
+    limit: u32,
+    window_start: Instant,
+    handled: u32,
+}
+
+impl Throttle {
+    pub fn new(limit: u32) -> Self {
+        Self { limit, window_start: Instant::now(), handled: 0 }
+    }
+
+    pub fn limit(&self) -> u32 {
+        self.limit
+    }
+
+    /// Count one more message. Returns when it may be handled if this
+    /// second's allowance is used up.
+    pub fn admit(&mut self) -> Option<Instant> {
+        let now = Instant::now();
+        if now >= self.window_start + RATE_WINDOW {
+            self.window_start = now;
+            self.handled = 0;
+        }
+        if self.handled < self.limit {
+            self.handled += 1;
+            return None;
+        }
+        self.window_start += RATE_WINDOW;
+        self.handled = 1;
+        Some(self.window_start)
+    }
+}
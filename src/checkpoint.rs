@@ -0,0 +1,101 @@
+//! Agent state that survives restarts.
+use crate::managed;
+use crate::payload;
+use agentropic_core::{Agent, AgentContext, AgentId, AgentResult};
+use async_trait::async_trait;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// An agent whose state can be saved and put back; wrap it in
+/// [`Checkpointed`] to keep that state across restarts.
+pub trait Stateful: Agent {
+    /// Everything the agent needs to pick up where it left off.
+    type State: Serialize + DeserializeOwned;
+
+    fn snapshot(&self) -> Self::State;
+
+    /// Replace the agent's state, before `initialize` runs again.
+    fn restore(&mut self, state: Self::State);
+}
+
+/// Which state a [`Checkpointed`] agent is restarted with; set it with
+/// [`SpawnOptions::with_restart_state`](crate::SpawnOptions::with_restart_state).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RestartState {
+    /// The state it was spawned with.
+    Fresh,
+    /// The state it had after the last `execute` or message it handled
+    /// without an error.
+    #[default]
+    LastCheckpoint,
+}
+
+/// Runs a [`Stateful`] agent, checkpointing its state as it goes and
+/// restoring it whenever the agent is initialized again, whether by a
+/// restart policy or a [`Supervisor`](crate::Supervisor).
+///
+/// A checkpoint is taken before the first `initialize` and after every
+/// `execute` and handled message that succeeds, so a restarted agent never
+/// sees state left behind by the failure. Checkpoints are kept as JSON.
+/// Outside a switchboard the agent restarts from its last checkpoint.
+pub struct Checkpointed<A> {
+    inner: A,
+    fresh: Option<String>,
+    last: Option<String>,
+}
+
+impl<A: Stateful> Checkpointed<A> {
+    pub fn new(inner: A) -> Self {
+        Self { inner, fresh: None, last: None }
+    }
+
+    /// The state the agent would be restarted with, if it has been checkpointed.
+    pub fn checkpoint(&self) -> Option<A::State> {
+        self.last.as_deref().and_then(|state| payload::decode(state).ok())
+    }
+
+    fn save(&mut self, result: AgentResult<()>) -> AgentResult<()> {
+        result?;
+        self.last = Some(payload::encode(&self.inner.snapshot())?);
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl<A: Stateful> Agent for Checkpointed<A> {
+    fn id(&self) -> &AgentId { self.inner.id() }
+
+    async fn initialize(&mut self, ctx: &AgentContext) -> AgentResult<()> {
+        match &self.fresh {
+            None => {
+                let state = payload::encode(&self.inner.snapshot())?;
+                self.fresh = Some(state.clone());
+                self.last = Some(state);
+            }
+            Some(fresh) => {
+                let restart_state = managed::current().map(|scope| scope.restart_state).unwrap_or_default();
+                let state = match restart_state {
+                    RestartState::Fresh => fresh.clone(),
+                    RestartState::LastCheckpoint => self.last.clone().unwrap_or_else(|| fresh.clone()),
+                };
+                self.last = Some(state.clone());
+                self.inner.restore(payload::decode(&state)?);
+            }
+        }
+        self.inner.initialize(ctx).await
+    }
+
+    async fn execute(&mut self, ctx: &AgentContext) -> AgentResult<()> {
+        let result = self.inner.execute(ctx).await;
+        self.save(result)
+    }
+
+    async fn shutdown(&mut self, ctx: &AgentContext) -> AgentResult<()> {
+        self.inner.shutdown(ctx).await
+    }
+
+    async fn handle_message(&mut self, ctx: &AgentContext, sender: &str, perf: &str, content: &str) -> AgentResult<()> {
+        let result = self.inner.handle_message(ctx, sender, perf, content).await;
+        self.save(result)
+    }
+}
@@ -0,0 +1,382 @@
+//! Typed extensions to [`AgentContext`].
+use crate::conversation::{Outstanding, Reply, RequestError};
+use crate::mailbox::{DeadLetter, MessageOptions, Priority, SendError, TimerId};
+use crate::managed;
+use crate::payload::{self, PayloadError};
+use crate::switchboard::SpawnError;
+use crate::timeout::Cancellation;
+use crate::{Performative, SpawnOptions};
+use agentropic_core::{Agent, AgentContext, AgentId};
+use agentropic_runtime::prelude::RestartPolicy;
+use serde::Serialize;
+use std::future::Future;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+/// Sends messages with a typed [`Performative`] instead of a string.
+pub trait ContextExt {
+    /// Send `content` to the agent spawned as `receiver`.
+    ///
+    /// From a managed agent, messages to other managed agents are counted
+    /// as in flight until handled; see [`Switchboard::is_idle`](crate::Switchboard::is_idle).
+    /// Messages that cannot be delivered end up in the switchboard's
+    /// [`dead_letters`](crate::Switchboard::dead_letters); use
+    /// [`try_send`](ContextExt::try_send) to find out at once.
+    fn send(&self, receiver: &str, performative: Performative, content: &str);
+
+    /// Like [`send`](ContextExt::send), queued in the receiver's mailbox
+    /// ahead of messages of lower [`Priority`]. Agents outside the
+    /// switchboard receive it in the usual order.
+    fn send_with_priority(&self, receiver: &str, performative: Performative, content: &str, priority: Priority);
+
+    /// Like [`send`](ContextExt::send), with a priority and time to live.
+    /// Both only apply to managed receivers.
+    fn send_with(&self, receiver: &str, performative: Performative, content: &str, options: MessageOptions);
+
+    /// Like [`send`](ContextExt::send), but report why the message could
+    /// not be delivered, such as a full mailbox, instead of keeping it as a
+    /// dead letter.
+    fn try_send(&self, receiver: &str, performative: Performative, content: &str) -> Result<(), SendError>;
+
+    /// Like [`try_send`](ContextExt::try_send), but waits for space when the
+    /// receiver's mailbox uses [`Overflow::Block`](crate::Overflow::Block).
+    fn send_wait(
+        &self,
+        receiver: &str,
+        performative: Performative,
+        content: &str,
+    ) -> impl Future<Output = Result<(), SendError>> + Send + 'static;
+
+    /// Serialize `payload` as JSON and send it to `receiver`.
+    fn send_payload<T: Serialize>(
+        &self,
+        receiver: &str,
+        performative: Performative,
+        payload: &T,
+    ) -> Result<(), PayloadError>;
+
+    /// Send `content` to `receiver` and wait for the reply to this message.
+    ///
+    /// The message is sent immediately; the returned future only waits for
+    /// the reply, so several requests can be in flight at once. Replies are
+    /// delivered through `handle_message`, so spawn the future rather than
+    /// awaiting it inside one of the same agent's own handlers.
+    fn request(
+        &self,
+        receiver: &str,
+        performative: Performative,
+        content: &str,
+        timeout: Duration,
+    ) -> impl Future<Output = Result<Reply, RequestError>> + Send + 'static;
+
+    /// Answer the message currently being handled.
+    fn reply(&self, performative: Performative, content: &str) -> Result<(), RequestError>;
+
+    /// Send to every running managed agent except this one. Returns how many were sent.
+    fn broadcast(&self, performative: Performative, content: &str) -> Result<usize, RequestError>;
+
+    /// Receive everything published to `topic`.
+    fn subscribe(&self, topic: &str) -> Result<(), RequestError>;
+
+    fn unsubscribe(&self, topic: &str) -> Result<(), RequestError>;
+
+    /// List this agent in the switchboard's directory as offering
+    /// `capability`. Call it from `initialize`: the entry is removed when
+    /// the agent exits or fails, and again before a restart.
+    fn register_capability(&self, capability: &str) -> Result<(), RequestError>;
+
+    fn withdraw_capability(&self, capability: &str) -> Result<(), RequestError>;
+
+    /// Names of the running agents that registered `capability`, sorted.
+    fn find_agents(&self, capability: &str) -> Result<Vec<String>, RequestError>;
+
+    /// Send to every subscriber of `topic` except this agent. Returns how many were sent.
+    fn publish(&self, topic: &str, performative: Performative, content: &str) -> Result<usize, RequestError>;
+
+    /// Like [`publish`](ContextExt::publish), with a priority and time to live.
+    fn publish_with(
+        &self,
+        topic: &str,
+        performative: Performative,
+        content: &str,
+        options: MessageOptions,
+    ) -> Result<usize, RequestError>;
+
+    /// How long the message being handled has left before it expires, if it
+    /// was sent with [`MessageOptions::with_ttl`]. Agents can use this to
+    /// refuse work they cannot finish in time.
+    fn time_left(&self) -> Option<Duration>;
+
+    /// The [`AgentId`] of whoever sent the message being handled, if the
+    /// switchboard knows it. Timer events and unregistered agents have none.
+    fn sender_id(&self) -> Option<AgentId>;
+
+    /// Cancelled once the call this agent is in has run past its
+    /// [`Timeouts`](crate::Timeouts), or the agent is asked to stop. Outside
+    /// a managed agent it is never cancelled.
+    fn cancellation(&self) -> Cancellation;
+
+    /// Mark this agent as having work outstanding, which keeps the
+    /// switchboard from reporting idle.
+    fn set_busy(&self, busy: bool) -> Result<(), RequestError>;
+
+    /// Finish this agent once the current call returns. It is shut down
+    /// and reports [`ExitStatus::Completed`](crate::ExitStatus::Completed),
+    /// which a restart policy does not treat as a failure.
+    fn stop_self(&self) -> Result<(), RequestError>;
+
+    /// Spawn `agent` as `name`, managed by this agent's switchboard under
+    /// `policy`, as a child of this agent: it is stopped when this agent
+    /// exits. See [`Switchboard::spawn_child`](crate::Switchboard::spawn_child).
+    fn spawn_child(
+        &self,
+        agent: Box<dyn Agent>,
+        name: &str,
+        policy: RestartPolicy,
+    ) -> impl Future<Output = Result<AgentId, SpawnError>> + Send + 'static;
+
+    /// Shut down the managed agent `name`, and its children, at its next
+    /// `execute`. It exits with [`ExitStatus::Stopped`](crate::ExitStatus::Stopped).
+    fn stop(&self, name: &str) -> Result<(), SendError>;
+
+    /// Hand the running agent `name`, with its mailbox, over to `agent`;
+    /// see [`Switchboard::replace`](crate::Switchboard::replace).
+    fn replace(&self, name: &str, agent: Box<dyn Agent>) -> Result<(), SendError>;
+
+    /// Deliver `event` to this agent once, after `delay`. It arrives in
+    /// `handle_message` from [`TIMER`](crate::TIMER).
+    fn schedule_after(&self, delay: Duration, event: &str) -> Result<TimerId, RequestError>;
+
+    /// Deliver `event` to this agent every `period`, starting one period from now.
+    /// Periods under a millisecond are taken as one millisecond. Ticks missed
+    /// while the agent was busy are skipped: it hears one, not one per period.
+    fn schedule_every(&self, period: Duration, event: &str) -> Result<TimerId, RequestError>;
+
+    /// Stop a timer. Returns false if it already fired or was cancelled.
+    fn cancel_timer(&self, timer: TimerId) -> Result<bool, RequestError>;
+}
+
+impl ContextExt for AgentContext {
+    fn send(&self, receiver: &str, performative: Performative, content: &str) {
+        self.send_with(receiver, performative, content, MessageOptions::new());
+    }
+
+    fn send_with_priority(&self, receiver: &str, performative: Performative, content: &str, priority: Priority) {
+        self.send_with(receiver, performative, content, MessageOptions::new().with_priority(priority));
+    }
+
+    fn send_with(&self, receiver: &str, performative: Performative, content: &str, options: MessageOptions) {
+        match managed::current() {
+            Some(scope) => {
+                let letter = || scope.letter(performative, content, None, None).with_options(options);
+                if let Err(reason) = scope.send(self, receiver, letter()) {
+                    scope.board.dead_letter(DeadLetter::new(receiver, letter(), reason));
+                }
+            }
+            None => self.send_message(receiver, performative.as_str(), content),
+        }
+    }
+
+    fn try_send(&self, receiver: &str, performative: Performative, content: &str) -> Result<(), SendError> {
+        let scope = managed::current().ok_or(SendError::Unmanaged)?;
+        scope.send(self, receiver, scope.letter(performative, content, None, None))
+    }
+
+    fn send_wait(
+        &self,
+        receiver: &str,
+        performative: Performative,
+        content: &str,
+    ) -> impl Future<Output = Result<(), SendError>> + Send + 'static {
+        let screened = managed::current().ok_or(SendError::Unmanaged).and_then(|scope| {
+            let letter = scope.letter(performative, content, None, None);
+            let screened = scope.board.screen_send(receiver, letter)?;
+            Ok((scope.board.clone(), screened))
+        });
+        async move {
+            let (board, (receiver, letter, delay)) = screened?;
+            tokio::time::sleep(delay).await;
+            board.post_wait(&receiver, letter).await
+        }
+    }
+
+    fn send_payload<T: Serialize>(
+        &self,
+        receiver: &str,
+        performative: Performative,
+        payload: &T,
+    ) -> Result<(), PayloadError> {
+        let content = payload::encode(payload)?;
+        self.send(receiver, performative, &content);
+        Ok(())
+    }
+
+    fn request(
+        &self,
+        receiver: &str,
+        performative: Performative,
+        content: &str,
+        timeout: Duration,
+    ) -> impl Future<Output = Result<Reply, RequestError>> + Send + 'static {
+        let opened = managed::current().map(|scope| {
+            let conversations = scope.board.conversations();
+            let legacy = scope.board.replies_by_sender(receiver);
+            let (id, rx) = conversations.open(&scope.name, receiver, legacy);
+            // Closes the conversation however the request ends, even if it is dropped
+            let outstanding = Outstanding::new(conversations, &id);
+            scope.send(self, receiver, scope.letter(performative, content, Some(id), None))?;
+            Ok::<_, SendError>((outstanding, rx))
+        });
+        let receiver = receiver.to_string();
+
+        async move {
+            let (_outstanding, rx) = opened.ok_or(RequestError::Unmanaged)??;
+            match tokio::time::timeout(timeout, rx).await {
+                Ok(Ok(reply)) => Ok(reply),
+                Ok(Err(_)) => Err(RequestError::Closed),
+                Err(_) => Err(RequestError::Timeout { receiver, after: timeout }),
+            }
+        }
+    }
+
+    fn reply(&self, performative: Performative, content: &str) -> Result<(), RequestError> {
+        let scope = managed::current().ok_or(RequestError::Unmanaged)?;
+        scope.reply(self, performative, content)
+    }
+
+    fn broadcast(&self, performative: Performative, content: &str) -> Result<usize, RequestError> {
+        let scope = managed::current().ok_or(RequestError::Unmanaged)?;
+        let peers = scope.board.running();
+        Ok(send_all(self, &peers, &scope.name, performative, content, MessageOptions::new()))
+    }
+
+    fn subscribe(&self, topic: &str) -> Result<(), RequestError> {
+        let scope = managed::current().ok_or(RequestError::Unmanaged)?;
+        scope.board.subscribe(topic, &scope.name);
+        Ok(())
+    }
+
+    fn unsubscribe(&self, topic: &str) -> Result<(), RequestError> {
+        let scope = managed::current().ok_or(RequestError::Unmanaged)?;
+        scope.board.unsubscribe(topic, &scope.name);
+        Ok(())
+    }
+
+    fn register_capability(&self, capability: &str) -> Result<(), RequestError> {
+        let scope = managed::current().ok_or(RequestError::Unmanaged)?;
+        scope.board.register(&scope.name, capability);
+        Ok(())
+    }
+
+    fn withdraw_capability(&self, capability: &str) -> Result<(), RequestError> {
+        let scope = managed::current().ok_or(RequestError::Unmanaged)?;
+        scope.board.withdraw(&scope.name, capability);
+        Ok(())
+    }
+
+    fn find_agents(&self, capability: &str) -> Result<Vec<String>, RequestError> {
+        let scope = managed::current().ok_or(RequestError::Unmanaged)?;
+        Ok(scope.board.find_agents(capability))
+    }
+
+    fn publish(&self, topic: &str, performative: Performative, content: &str) -> Result<usize, RequestError> {
+        self.publish_with(topic, performative, content, MessageOptions::new())
+    }
+
+    fn publish_with(
+        &self,
+        topic: &str,
+        performative: Performative,
+        content: &str,
+        options: MessageOptions,
+    ) -> Result<usize, RequestError> {
+        let scope = managed::current().ok_or(RequestError::Unmanaged)?;
+        let subscribers = scope.board.subscribers(topic);
+        Ok(send_all(self, &subscribers, &scope.name, performative, content, options))
+    }
+
+    fn time_left(&self) -> Option<Duration> {
+        let scope = managed::current()?;
+        let deadline = scope.incoming.lock().unwrap().as_ref()?.deadline?;
+        Some(deadline.saturating_duration_since(tokio::time::Instant::now()))
+    }
+
+    fn sender_id(&self) -> Option<AgentId> {
+        let scope = managed::current()?;
+        let incoming = scope.incoming.lock().unwrap();
+        incoming.as_ref()?.sender_id
+    }
+
+    fn cancellation(&self) -> Cancellation {
+        let scope = managed::current();
+        scope.and_then(|scope| scope.board.cancellation(&scope.name)).unwrap_or_default()
+    }
+
+    fn set_busy(&self, busy: bool) -> Result<(), RequestError> {
+        let scope = managed::current().ok_or(RequestError::Unmanaged)?;
+        scope.board.set_busy(&scope.name, busy);
+        Ok(())
+    }
+
+    fn stop_self(&self) -> Result<(), RequestError> {
+        let scope = managed::current().ok_or(RequestError::Unmanaged)?;
+        scope.stopping.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+
+    fn spawn_child(
+        &self,
+        agent: Box<dyn Agent>,
+        name: &str,
+        policy: RestartPolicy,
+    ) -> impl Future<Output = Result<AgentId, SpawnError>> + Send + 'static {
+        let parent = managed::current().map(|scope| (scope.board.clone(), scope.name.clone()));
+        let name = name.to_string();
+        async move {
+            let (board, parent) = parent.ok_or(SpawnError::Unmanaged)?;
+            board.spawn_child(&parent, agent, &name, SpawnOptions::new().with_policy(policy)).await
+        }
+    }
+
+    fn stop(&self, name: &str) -> Result<(), SendError> {
+        let scope = managed::current().ok_or(SendError::Unmanaged)?;
+        scope.board.stop_agent(name)
+    }
+
+    fn replace(&self, name: &str, agent: Box<dyn Agent>) -> Result<(), SendError> {
+        let scope = managed::current().ok_or(SendError::Unmanaged)?;
+        scope.board.replace(name, agent)
+    }
+
+    fn schedule_after(&self, delay: Duration, event: &str) -> Result<TimerId, RequestError> {
+        let scope = managed::current().ok_or(RequestError::Unmanaged)?;
+        Ok(scope.inbox.schedule(delay, None, event))
+    }
+
+    fn schedule_every(&self, period: Duration, event: &str) -> Result<TimerId, RequestError> {
+        let scope = managed::current().ok_or(RequestError::Unmanaged)?;
+        Ok(scope.inbox.repeat(period, event))
+    }
+
+    fn cancel_timer(&self, timer: TimerId) -> Result<bool, RequestError> {
+        let scope = managed::current().ok_or(RequestError::Unmanaged)?;
+        Ok(scope.inbox.cancel(timer))
+    }
+}
+
+/// Send to every receiver except `sender`; returns how many were sent.
+fn send_all(
+    ctx: &AgentContext,
+    receivers: &[String],
+    sender: &str,
+    performative: Performative,
+    content: &str,
+    options: MessageOptions,
+) -> usize {
+    let mut sent = 0;
+    for receiver in receivers.iter().filter(|r| *r != sender) {
+        ctx.send_with(receiver, performative, content, options);
+        sent += 1;
+    }
+    sent
+}
@@ -0,0 +1,214 @@
+//! Request/reply conversations correlated by conversation id.
+use crate::mailbox::{Priority, SendError};
+use crate::payload::{self, PayloadError};
+use crate::Performative;
+use agentropic_core::{AgentError, AgentId};
+use serde::de::DeserializeOwned;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot};
+use tokio::time::Instant;
+
+/// Conversation metadata carried with messages between managed agents.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Envelope {
+    /// The sending agent, when it is known.
+    pub sender_id: Option<AgentId>,
+    pub conversation_id: Option<String>,
+    pub in_reply_to: Option<String>,
+    pub priority: Priority,
+    /// When the message stops being worth delivering.
+    pub deadline: Option<Instant>,
+    pub body: String,
+}
+
+/// The answer to a [`request`](crate::ContextExt::request).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reply {
+    pub sender: String,
+    /// The replying agent, if the switchboard knows its id.
+    pub sender_id: Option<AgentId>,
+    pub performative: Performative,
+    pub content: String,
+}
+
+impl Reply {
+    /// Decode the reply content as a structured payload.
+    pub fn decode<T: DeserializeOwned>(&self) -> Result<T, PayloadError> {
+        payload::decode(&self.content)
+    }
+}
+
+/// Why a request produced no reply.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RequestError {
+    /// No reply arrived before the deadline.
+    Timeout { receiver: String, after: Duration },
+    /// The calling agent was not spawned through a [`Switchboard`](crate::Switchboard).
+    Unmanaged,
+    /// There is no incoming message to reply to.
+    NothingToReplyTo,
+    /// The switchboard dropped the conversation before a reply arrived.
+    Closed,
+    /// The message could not be queued for its receiver.
+    Undeliverable(SendError),
+    /// The answer broke the interaction protocol; see [`protocol`](crate::protocol).
+    Unexpected(Reply),
+}
+
+impl fmt::Display for RequestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RequestError::Timeout { receiver, after } => {
+                write!(f, "no reply from \"{}\" within {:?}", receiver, after)
+            }
+            RequestError::Unmanaged => f.write_str("agent is not managed by a switchboard"),
+            RequestError::NothingToReplyTo => f.write_str("no incoming message to reply to"),
+            RequestError::Closed => f.write_str("conversation closed before a reply arrived"),
+            RequestError::Undeliverable(err) => write!(f, "could not deliver: {}", err),
+            RequestError::Unexpected(reply) => {
+                write!(f, "\"{}\" answered with {}, which the protocol does not allow here", reply.sender, reply.performative)
+            }
+        }
+    }
+}
+
+impl std::error::Error for RequestError {}
+
+impl From<SendError> for RequestError {
+    fn from(err: SendError) -> Self {
+        RequestError::Undeliverable(err)
+    }
+}
+
+impl From<RequestError> for AgentError {
+    fn from(err: RequestError) -> Self {
+        AgentError::ExecutionFailed(err.to_string())
+    }
+}
+
+enum Waiter {
+    /// Closes the conversation with the first reply.
+    Once(oneshot::Sender<Reply>),
+    /// Keeps the conversation open for every reply until it is cancelled.
+    Stream(mpsc::UnboundedSender<Reply>),
+}
+
+struct Pending {
+    asker: String,
+    receiver: String,
+    /// Set when the receiver answers without an envelope.
+    legacy: bool,
+    waiter: Waiter,
+}
+
+/// Open conversations, in the order they were opened.
+///
+/// Managed receivers echo the conversation id back in `in_reply_to`.
+/// Plain agents (like `CognitiveAgent`) answer without an envelope, so their
+/// replies are matched to the oldest open request from the same asker. That
+/// is assumed of unmanaged receivers, and of managed ones spawned with
+/// [`SpawnOptions::replies_without_envelope`](crate::SpawnOptions::replies_without_envelope).
+#[derive(Clone, Default)]
+pub struct Conversations {
+    next_id: Arc<AtomicU64>,
+    pending: Arc<Mutex<Vec<(String, Pending)>>>,
+}
+
+impl Conversations {
+    pub(crate) fn open(
+        &self,
+        asker: &str,
+        receiver: &str,
+        legacy: bool,
+    ) -> (String, oneshot::Receiver<Reply>) {
+        let (tx, rx) = oneshot::channel();
+        let id = self.insert(asker, receiver, legacy, Waiter::Once(tx));
+        (id, rx)
+    }
+
+    /// Open a conversation that receives every reply to it, from any
+    /// managed agent, until it is cancelled.
+    pub(crate) fn open_stream(&self, asker: &str) -> (String, mpsc::UnboundedReceiver<Reply>) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let id = self.insert(asker, "", false, Waiter::Stream(tx));
+        (id, rx)
+    }
+
+    fn insert(&self, asker: &str, receiver: &str, legacy: bool, waiter: Waiter) -> String {
+        let id = format!("{}-{}", asker, self.next_id.fetch_add(1, Ordering::SeqCst) + 1);
+        let pending = Pending { asker: asker.to_string(), receiver: receiver.to_string(), legacy, waiter };
+        self.pending.lock().unwrap().push((id.clone(), pending));
+        id
+    }
+
+    pub(crate) fn is_open(&self, conversation_id: &str) -> bool {
+        self.pending.lock().unwrap().iter().any(|(id, _)| id == conversation_id)
+    }
+
+    pub(crate) fn cancel(&self, conversation_id: &str) {
+        self.pending.lock().unwrap().retain(|(id, _)| id != conversation_id);
+    }
+
+    /// Hand `reply` to the conversation `in_reply_to`, completing it unless
+    /// it is a stream. Returns `false` if it is not open.
+    pub(crate) fn resolve(&self, in_reply_to: &str, reply: Reply) -> bool {
+        let mut pending = self.pending.lock().unwrap();
+        let Some(index) = pending.iter().position(|(id, _)| id == in_reply_to) else {
+            return false;
+        };
+        if let Waiter::Stream(tx) = &pending[index].1.waiter {
+            if tx.send(reply).is_err() {
+                pending.remove(index);
+            }
+            return true;
+        }
+        if let (_, Pending { waiter: Waiter::Once(tx), .. }) = pending.remove(index) {
+            let _ = tx.send(reply);
+        }
+        true
+    }
+
+    /// Match a plain reply from `reply.sender` to the oldest legacy request by `asker`.
+    pub(crate) fn resolve_legacy(&self, asker: &str, reply: Reply) -> bool {
+        let mut pending = self.pending.lock().unwrap();
+        let found = pending
+            .iter()
+            .position(|(_, p)| p.legacy && p.asker == asker && p.receiver == reply.sender);
+        match found {
+            Some(index) => {
+                if let (_, Pending { waiter: Waiter::Once(tx), .. }) = pending.remove(index) {
+                    let _ = tx.send(reply);
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Number of requests still waiting for a reply.
+    pub fn open_count(&self) -> usize {
+        self.pending.lock().unwrap().len()
+    }
+}
+
+/// A request's conversation, cancelled when dropped so that a request given
+/// up on before its reply arrives does not stay open.
+pub(crate) struct Outstanding {
+    conversations: Conversations,
+    id: String,
+}
+
+impl Outstanding {
+    pub fn new(conversations: &Conversations, id: &str) -> Self {
+        Self { conversations: conversations.clone(), id: id.to_string() }
+    }
+}
+
+impl Drop for Outstanding {
+    fn drop(&mut self) {
+        self.conversations.cancel(&self.id);
+    }
+}
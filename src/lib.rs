@@ -1,6 +1,95 @@
 //! # Agentropic Examples
 //!
+//! Shared helpers used by the examples:
+//!
+//! - [`Performative`] — typed FIPA performatives, sent through [`ContextExt`]
+//! - [`payload`] — serde payloads (`Bid`, `Vote`, `TaskAssignment`, `TaskCompletion`)
+//!   decoded by [`TypedHandler`]
+//! - [`Switchboard`] — spawns managed agents, maps their names to `AgentId`s
+//!   and back, keeps a directory of the capabilities they offer, and lets
+//!   them `request` and `reply` with correlated conversations, bounded
+//!   mailboxes ([`MailboxConfig`])
+//!   with [`Priority`] lanes, expiring messages ([`MessageOptions`]) and a
+//!   queue of [`DeadLetter`]s, and reports how each one exited
+//! - [`LifecycleEvent`]s — spawns, failures, restarts and exits, streamed
+//!   to `main` through [`RuntimeExt::events`] and published to agents
+//!   subscribed to [`LIFECYCLE_TOPIC`]
+//! - [`Supervisor`] trees that restart managed agents one-for-one,
+//!   one-for-all or rest-for-one, escalating when restarts pile up
+//! - [`Recovery`] policies that restart failed agents with jittered
+//!   exponential backoff and park them behind a [`Circuit`] breaker when
+//!   they keep failing
+//! - [`Checkpointed`] agents that come back from a restart with their last
+//!   good [`Stateful`] state, or a fresh one
+//! - Agents that [`spawn_child`](ContextExt::spawn_child) agents of their
+//!   own, which are stopped along with them, [`stop`](ContextExt::stop)
+//!   others and [`replace`](Switchboard::replace) a running agent's
+//!   implementation while keeping its mailbox
+//! - Per-agent [`Usage`] accounting and [`Budget`]s that cancel slow
+//!   handlers and throttle agents flooded with messages
+//! - [`Timeouts`] on each of an agent's calls, failing a call that runs
+//!   over with [`TimedOut`], and a [`Cancellation`] long calls can check
+//! - Graceful [`Shutdown`] that drains mailboxes, stops agents in reverse
+//!   dependency order and reports those that had to be killed
+//! - [`RuntimeBuilder`] — spawns the agents, restart policies, mailboxes
+//!   and subscriptions listed in a JSON or TOML [`Manifest`], building each
+//!   agent by kind from an [`AgentRegistry`]
+//! - [`Middleware`] run on every message between managed agents, with
+//!   [`Tracing`] and [`ContentFilter`] built in
+//! - [`protocol`] — FIPA Contract Net, Request and Query-If, with an
+//!   initiator that runs the exchange to an outcome and participant traits
+//! - [`TIMER`] events from `schedule_after`/`schedule_every`, and reactive
+//!   agents that stay parked until a message or timer arrives
+//! - [`RuntimeExt`] — `run_until` and `run_until_idle`, so examples stop
+//!   when their agents are done rather than after a fixed sleep
+//! - `Simulation` — runs a scenario on a seeded virtual clock for tests,
+//!   with the `simulation` feature
+//!
 //! Run any example with:
 //! ```bash
-//! cargo run --example 01_hello_agent
+//! cargo run --example hello_agent
 //! ```
+pub mod budget;
+pub mod checkpoint;
+pub mod context;
+pub mod conversation;
+pub mod lifecycle;
+pub mod mailbox;
+pub mod managed;
+pub mod manifest;
+pub mod middleware;
+pub mod payload;
+pub mod performative;
+pub mod protocol;
+pub mod recovery;
+pub mod run;
+pub mod shutdown;
+pub mod simulation;
+pub mod supervisor;
+pub mod switchboard;
+pub mod timeout;
+
+pub use budget::{Budget, Usage, Violation};
+pub use checkpoint::{Checkpointed, RestartState, Stateful};
+pub use context::ContextExt;
+pub use conversation::{Reply, RequestError};
+pub use lifecycle::{Events, ExitStatus, Lifecycle, LifecycleEvent, LIFECYCLE_TOPIC};
+pub use mailbox::{
+    DeadLetter, MailboxConfig, MailboxStats, MessageOptions, Overflow, Priority, SendError, TimerId, TIMER,
+};
+pub use managed::Managed;
+pub use manifest::{
+    AgentRegistry, AgentSpec, FromManifest, Manifest, ManifestError, RestartSpec, RestartWhen, RuntimeBuilder,
+};
+pub use middleware::{ContentFilter, Message, Middleware, Tracing, Verdict};
+pub use payload::{Bid, PayloadError, TaskAssignment, TaskCompletion, TypedHandler, Vote};
+pub use performative::{Performative, UnknownPerformative};
+pub use recovery::{Circuit, Recovery, CIRCUIT_TOPIC};
+pub use run::{RunTimeout, RuntimeExt};
+pub use shutdown::{Shutdown, ShutdownReport};
+#[cfg(feature = "simulation")]
+pub use simulation::Simulation;
+pub use simulation::Trace;
+pub use supervisor::{Strategy, Supervisor};
+pub use switchboard::{SpawnError, SpawnOptions, Switchboard};
+pub use timeout::{Cancellation, Phase, TimedOut, Timeouts, CANCEL_GRACE};
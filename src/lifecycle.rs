@@ -0,0 +1,146 @@
+//! How a managed agent's run ended, and the events along the way.
+use crate::budget::Violation;
+use crate::timeout::TimedOut;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use tokio::sync::broadcast;
+
+/// How many events a slow [`Events`] reader may fall behind before it
+/// misses the oldest.
+const EVENT_BACKLOG: usize = 256;
+
+/// The topic lifecycle events are published on, and the sender they are
+/// delivered under.
+///
+/// Managed agents subscribed to it with
+/// [`ContextExt::subscribe`](crate::ContextExt::subscribe) receive every
+/// [`LifecycleEvent`] of the other agents as a [`Performative::Inform`](crate::Performative::Inform)
+/// from this sender, encoded as a [`payload`](crate::payload).
+pub const LIFECYCLE_TOPIC: &str = "lifecycle";
+
+/// Why a managed agent stopped running.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ExitStatus {
+    /// The agent called [`stop_self`](crate::ContextExt::stop_self) and has
+    /// shut down. This is not a failure, so supervisors do not restart it.
+    Completed,
+    /// `execute` returned an error and no restart policy will bring the agent back.
+    Failed(String),
+    /// The runtime shut the agent down.
+    Stopped,
+}
+
+impl ExitStatus {
+    pub fn is_success(&self) -> bool {
+        !matches!(self, ExitStatus::Failed(_))
+    }
+}
+
+impl fmt::Display for ExitStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExitStatus::Completed => f.write_str("completed"),
+            ExitStatus::Failed(reason) => write!(f, "failed: {}", reason),
+            ExitStatus::Stopped => f.write_str("stopped"),
+        }
+    }
+}
+
+/// A step in the life of a managed agent.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Lifecycle {
+    Spawned,
+    /// `initialize` succeeded.
+    Initialized,
+    /// `initialize` or `execute` returned this error, or `shutdown` did
+    /// when a supervisor stopped the agent to restart it or it was replaced.
+    Failed(String),
+    /// The agent is being initialized again after failing.
+    Restarting,
+    /// `handle_message` returned `error` for a message from `sender`. The
+    /// agent carries on with its next message.
+    HandlerFailed { sender: String, error: String },
+    /// One of the agent's calls ran past its limit in
+    /// [`Timeouts`](crate::Timeouts) and was cancelled.
+    TimedOut(TimedOut),
+    /// The agent went over its [`Budget`](crate::Budget).
+    OverBudget(Violation),
+    /// A new implementation took over the agent's name and mailbox; see
+    /// [`Switchboard::replace`](crate::Switchboard::replace).
+    Replaced,
+    /// The agent failed and will not be restarted; it exited with
+    /// [`ExitStatus::Failed`] for this reason.
+    GaveUp(String),
+    /// The agent exited without failing, with [`ExitStatus::Completed`] or
+    /// [`ExitStatus::Stopped`].
+    Stopped(ExitStatus),
+}
+
+impl fmt::Display for Lifecycle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Lifecycle::Spawned => f.write_str("spawned"),
+            Lifecycle::Initialized => f.write_str("initialized"),
+            Lifecycle::Failed(error) => write!(f, "failed: {}", error),
+            Lifecycle::Restarting => f.write_str("restarting"),
+            Lifecycle::HandlerFailed { sender, error } => {
+                write!(f, "failed to handle a message from {}: {}", sender, error)
+            }
+            Lifecycle::TimedOut(timed_out) => f.write_str(&timed_out.to_string()),
+            Lifecycle::OverBudget(violation) => write!(f, "over budget: {}", violation),
+            Lifecycle::Replaced => f.write_str("replaced"),
+            Lifecycle::GaveUp(reason) => write!(f, "gave up: {}", reason),
+            Lifecycle::Stopped(status) => f.write_str(&status.to_string()),
+        }
+    }
+}
+
+/// Something that happened to the agent spawned as `agent`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LifecycleEvent {
+    pub agent: String,
+    pub event: Lifecycle,
+}
+
+/// Lifecycle events of a switchboard's agents, from
+/// [`Switchboard::events`](crate::Switchboard::events).
+pub struct Events {
+    receiver: broadcast::Receiver<LifecycleEvent>,
+}
+
+impl Events {
+    /// The next event, once it happens. A reader that falls more than 256
+    /// events behind skips the oldest.
+    pub async fn next(&mut self) -> Option<LifecycleEvent> {
+        loop {
+            match self.receiver.recv().await {
+                Ok(event) => return Some(event),
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    }
+}
+
+/// Hands lifecycle events to every [`Events`] reader.
+#[derive(Clone)]
+pub(crate) struct Announcer {
+    sender: broadcast::Sender<LifecycleEvent>,
+}
+
+impl Announcer {
+    pub fn subscribe(&self) -> Events {
+        Events { receiver: self.sender.subscribe() }
+    }
+
+    pub fn send(&self, event: LifecycleEvent) {
+        // Nobody listening is fine
+        let _ = self.sender.send(event);
+    }
+}
+
+impl Default for Announcer {
+    fn default() -> Self {
+        Self { sender: broadcast::channel(EVENT_BACKLOG).0 }
+    }
+}
@@ -0,0 +1,504 @@
+//! Per-agent inboxes and timers for managed agents.
+use crate::conversation::Envelope;
+use crate::Performative;
+use agentropic_core::{AgentError, AgentId};
+use serde::Deserialize;
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::Notify;
+use tokio::time::Instant;
+
+/// The sender name timer events are delivered under.
+///
+/// Events scheduled with [`schedule_after`](crate::ContextExt::schedule_after)
+/// or [`schedule_every`](crate::ContextExt::schedule_every) arrive in
+/// `handle_message` as an [`Performative::Inform`] from this sender, with the
+/// event as content.
+pub const TIMER: &str = "timer";
+
+/// Shortest period a periodic timer repeats at; shorter ones are raised to
+/// it, so a zero period cannot fire on every pass of the agent's loop.
+const MIN_PERIOD: Duration = Duration::from_millis(1);
+
+/// How many letters in a row may be taken from a higher lane while a lower
+/// one waits, before the longest-waiting letter is delivered instead.
+const STARVATION_LIMIT: u32 = 8;
+
+/// Where a message queues in the receiver's mailbox. Higher lanes are
+/// delivered first; see [`ContextExt::send_with_priority`](crate::ContextExt::send_with_priority).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub enum Priority {
+    Low,
+    #[default]
+    Normal,
+    /// Used for requests sent down a hierarchy; see
+    /// [`Switchboard::set_level`](crate::Switchboard::set_level).
+    High,
+    /// Control messages. Always delivered before anything else, even
+    /// letters that have waited a long time in lower lanes.
+    Urgent,
+}
+
+impl Priority {
+    fn lane(self) -> usize {
+        self as usize
+    }
+}
+
+/// How a single message is delivered; see
+/// [`ContextExt::send_with`](crate::ContextExt::send_with).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct MessageOptions {
+    priority: Priority,
+    ttl: Option<Duration>,
+}
+
+impl MessageOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_priority(mut self, priority: Priority) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Discard the message if it has not been handled within `ttl`.
+    ///
+    /// An expired message becomes a [`DeadLetter`] instead of reaching
+    /// `handle_message`; while it is being handled, the receiver can check
+    /// [`time_left`](crate::ContextExt::time_left).
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    pub fn priority(&self) -> Priority {
+        self.priority
+    }
+
+    pub fn ttl(&self) -> Option<Duration> {
+        self.ttl
+    }
+}
+
+/// Identifies a scheduled timer so it can be cancelled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct TimerId(u64);
+
+/// What happens when a message arrives at a full mailbox.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Overflow {
+    /// [`send_wait`](crate::ContextExt::send_wait) waits for space; other
+    /// sends are rejected.
+    Block,
+    /// Discard the incoming message without telling the sender.
+    DropNewest,
+    /// Discard the oldest queued message of the lowest priority to make
+    /// room. Only messages of the incoming one's priority or lower are
+    /// discarded; if there are none, the incoming message is, as under
+    /// [`DropNewest`](Overflow::DropNewest).
+    DropOldest,
+    /// Refuse the incoming message with [`SendError::MailboxFull`].
+    Reject,
+}
+
+/// Capacity of a managed agent's mailbox, set when it is spawned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MailboxConfig {
+    capacity: Option<usize>,
+    overflow: Overflow,
+}
+
+impl MailboxConfig {
+    pub fn unbounded() -> Self {
+        Self { capacity: None, overflow: Overflow::Block }
+    }
+
+    pub fn bounded(capacity: usize, overflow: Overflow) -> Self {
+        Self { capacity: Some(capacity), overflow }
+    }
+
+    pub fn capacity(&self) -> Option<usize> {
+        self.capacity
+    }
+
+    pub fn overflow(&self) -> Overflow {
+        self.overflow
+    }
+}
+
+impl Default for MailboxConfig {
+    fn default() -> Self {
+        Self::unbounded()
+    }
+}
+
+/// A snapshot of one mailbox, for monitoring.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MailboxStats {
+    /// Messages waiting to be handled.
+    pub depth: usize,
+    pub capacity: Option<usize>,
+    /// Messages discarded or refused because the mailbox was full.
+    pub dropped: u64,
+}
+
+/// Why a message could not be queued.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SendError {
+    MailboxFull { receiver: String, capacity: usize },
+    /// The receiver was neither spawned through the sender's switchboard
+    /// nor registered with [`spawn_external`](crate::Switchboard::spawn_external).
+    UnknownRecipient(String),
+    /// The receiver has exited and no longer handles messages.
+    Stopped(String),
+    /// The receiver's [circuit](crate::Circuit) is open after repeated failures.
+    CircuitOpen(String),
+    /// The receiver's switchboard is shutting down and takes no more
+    /// messages from outside.
+    ShuttingDown(String),
+    /// The message's time to live ran out before the receiver got to it.
+    Expired(String),
+    /// A [`Middleware`](crate::Middleware) dropped the message, for this reason.
+    Filtered(String),
+    /// The sending agent was not spawned through a [`Switchboard`](crate::Switchboard).
+    Unmanaged,
+}
+
+impl fmt::Display for SendError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SendError::MailboxFull { receiver, capacity } => {
+                write!(f, "mailbox of \"{}\" is full ({} messages)", receiver, capacity)
+            }
+            SendError::UnknownRecipient(receiver) => write!(f, "no agent named \"{}\"", receiver),
+            SendError::Stopped(receiver) => write!(f, "\"{}\" has stopped", receiver),
+            SendError::CircuitOpen(receiver) => write!(f, "circuit of \"{}\" is open", receiver),
+            SendError::ShuttingDown(receiver) => write!(f, "\"{}\" is shutting down", receiver),
+            SendError::Expired(receiver) => write!(f, "message to \"{}\" expired before delivery", receiver),
+            SendError::Filtered(reason) => write!(f, "dropped by middleware: {}", reason),
+            SendError::Unmanaged => f.write_str("agent is not managed by a switchboard"),
+        }
+    }
+}
+
+impl std::error::Error for SendError {}
+
+impl From<SendError> for AgentError {
+    fn from(err: SendError) -> Self {
+        AgentError::ExecutionFailed(err.to_string())
+    }
+}
+
+/// A message from one managed agent to another.
+pub(crate) struct Letter {
+    pub sender: String,
+    pub performative: Performative,
+    pub envelope: Envelope,
+}
+
+/// A message that could not be delivered, kept by the switchboard; see
+/// [`Switchboard::dead_letters`](crate::Switchboard::dead_letters).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeadLetter {
+    pub sender: String,
+    pub sender_id: Option<AgentId>,
+    pub receiver: String,
+    pub performative: Performative,
+    pub priority: Priority,
+    pub content: String,
+    pub conversation_id: Option<String>,
+    pub in_reply_to: Option<String>,
+    /// When the message's time to live runs out, if it has one.
+    pub deadline: Option<Instant>,
+    pub reason: SendError,
+}
+
+impl DeadLetter {
+    pub(crate) fn new(receiver: &str, letter: Letter, reason: SendError) -> Self {
+        Self {
+            sender: letter.sender,
+            sender_id: letter.envelope.sender_id,
+            receiver: receiver.to_string(),
+            performative: letter.performative,
+            priority: letter.envelope.priority,
+            content: letter.envelope.body,
+            conversation_id: letter.envelope.conversation_id,
+            in_reply_to: letter.envelope.in_reply_to,
+            deadline: letter.envelope.deadline,
+            reason,
+        }
+    }
+
+    /// Rebuild the original letter, to forward it. A deadline that has
+    /// already passed is left off, or the letter would only expire again.
+    pub(crate) fn letter(&self) -> Letter {
+        let envelope = Envelope {
+            sender_id: self.sender_id,
+            conversation_id: self.conversation_id.clone(),
+            in_reply_to: self.in_reply_to.clone(),
+            priority: self.priority,
+            deadline: self.deadline.filter(|deadline| *deadline > Instant::now()),
+            body: self.content.clone(),
+        };
+        Letter { sender: self.sender.clone(), performative: self.performative, envelope }
+    }
+}
+
+impl Letter {
+    pub fn with_priority(mut self, priority: Priority) -> Self {
+        self.envelope.priority = priority;
+        self
+    }
+
+    pub fn with_options(self, options: MessageOptions) -> Self {
+        let mut letter = self.with_priority(options.priority);
+        letter.envelope.deadline = options.ttl.map(|ttl| Instant::now() + ttl);
+        letter
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.envelope.deadline.is_some_and(|deadline| deadline <= Instant::now())
+    }
+}
+
+/// Queued letters, one lane per [`Priority`].
+#[derive(Default)]
+struct Lanes {
+    queues: [VecDeque<(u64, Letter)>; 4],
+    next_seq: u64,
+    /// Letters taken in a row while a lower lane was waiting.
+    passed_over: u32,
+}
+
+impl Lanes {
+    fn len(&self) -> usize {
+        self.queues.iter().map(VecDeque::len).sum()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.queues.iter().all(VecDeque::is_empty)
+    }
+
+    fn push(&mut self, letter: Letter) {
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        self.queues[letter.envelope.priority.lane()].push_back((seq, letter));
+    }
+
+    /// The oldest letter of the lowest non-empty lane up to `priority`.
+    fn evict(&mut self, priority: Priority) -> Option<Letter> {
+        let queue = self.queues[..=priority.lane()].iter_mut().find(|queue| !queue.is_empty())?;
+        queue.pop_front().map(|(_, letter)| letter)
+    }
+
+    /// The next letter to deliver: from the highest non-empty lane, unless
+    /// lower lanes have been passed over too often.
+    fn pop(&mut self) -> Option<Letter> {
+        let top = self.queues.iter().rposition(|queue| !queue.is_empty())?;
+        let below = &self.queues[..top];
+        let waiting = below.iter().any(|queue| !queue.is_empty());
+        let lane = if top == Priority::Urgent.lane() || !waiting {
+            top
+        } else if self.passed_over >= STARVATION_LIMIT {
+            self.passed_over = 0;
+            let oldest = below.iter().enumerate().filter_map(|(lane, queue)| queue.front().map(|(seq, _)| (*seq, lane)));
+            oldest.min().map_or(top, |(_, lane)| lane)
+        } else {
+            self.passed_over += 1;
+            top
+        };
+        if !waiting {
+            self.passed_over = 0;
+        }
+        self.queues[lane].pop_front().map(|(_, letter)| letter)
+    }
+}
+
+struct Timer {
+    id: TimerId,
+    due: Instant,
+    period: Option<Duration>,
+    event: String,
+}
+
+/// The outcome of offering a letter to an inbox.
+pub(crate) enum Offer {
+    Accepted,
+    /// Accepted after discarding the oldest queued letter, which is handed back.
+    Evicted(Letter),
+    /// Discarded under [`Overflow::DropNewest`]; the letter is handed back.
+    Dropped(Letter),
+    /// Not queued; the letter is handed back.
+    Full(Letter),
+}
+
+/// Messages and timers waiting for one managed agent.
+#[derive(Default)]
+pub(crate) struct Inbox {
+    config: MailboxConfig,
+    letters: Mutex<Lanes>,
+    dropped: AtomicU64,
+    timers: Mutex<Vec<Timer>>,
+    next_timer: AtomicU64,
+    woken: AtomicBool,
+    notify: Notify,
+    space: Notify,
+}
+
+impl Inbox {
+    pub fn new(config: MailboxConfig) -> Self {
+        Self { config, ..Self::default() }
+    }
+
+    pub fn config(&self) -> MailboxConfig {
+        self.config
+    }
+
+    /// Queue `letter`, applying the overflow policy if the inbox is full.
+    /// Discarded letters are counted as dropped and handed back; a refused
+    /// letter only counts once [`record_drop`](Inbox::record_drop) is called.
+    pub fn offer(&self, letter: Letter) -> Offer {
+        let mut letters = self.letters.lock().unwrap();
+        let full = self.config.capacity.is_some_and(|capacity| letters.len() >= capacity);
+        let offer = match (full, self.config.overflow) {
+            (false, _) => Offer::Accepted,
+            (true, Overflow::DropOldest) => match letters.evict(letter.envelope.priority) {
+                Some(oldest) => {
+                    self.record_drop();
+                    Offer::Evicted(oldest)
+                }
+                // Everything queued outranks it, or a zero-capacity mailbox has nothing to evict
+                None => {
+                    self.record_drop();
+                    return Offer::Dropped(letter);
+                }
+            },
+            (true, Overflow::DropNewest) => {
+                self.record_drop();
+                return Offer::Dropped(letter);
+            }
+            (true, _) => return Offer::Full(letter),
+        };
+        letters.push(letter);
+        self.notify.notify_one();
+        offer
+    }
+
+    /// Like [`offer`](Inbox::offer), but waits for space under [`Overflow::Block`].
+    pub async fn offer_wait(&self, mut letter: Letter) -> Offer {
+        loop {
+            let space = self.space.notified();
+            match self.offer(letter) {
+                Offer::Full(refused) if self.config.overflow == Overflow::Block => {
+                    letter = refused;
+                    space.await;
+                }
+                offer => return offer,
+            }
+        }
+    }
+
+    pub fn record_drop(&self) {
+        self.dropped.fetch_add(1, Ordering::SeqCst);
+    }
+
+    pub fn pop_letter(&self) -> Option<Letter> {
+        let letter = self.letters.lock().unwrap().pop();
+        if letter.is_some() {
+            self.space.notify_waiters();
+        }
+        letter
+    }
+
+    /// Bytes of content in the queued letters.
+    pub fn queued_bytes(&self) -> usize {
+        let letters = self.letters.lock().unwrap();
+        letters.queues.iter().flatten().map(|(_, letter)| letter.envelope.body.len()).sum()
+    }
+
+    pub fn stats(&self) -> MailboxStats {
+        MailboxStats {
+            depth: self.letters.lock().unwrap().len(),
+            capacity: self.config.capacity,
+            dropped: self.dropped.load(Ordering::SeqCst),
+        }
+    }
+
+    pub fn schedule(&self, delay: Duration, period: Option<Duration>, event: &str) -> TimerId {
+        let id = TimerId(self.next_timer.fetch_add(1, Ordering::SeqCst));
+        let timer = Timer { id, due: Instant::now() + delay, period, event: event.to_string() };
+        self.timers.lock().unwrap().push(timer);
+        self.notify.notify_one();
+        id
+    }
+
+    /// Schedule `event` every `period`, starting one period from now.
+    pub fn repeat(&self, period: Duration, event: &str) -> TimerId {
+        let period = period.max(MIN_PERIOD);
+        self.schedule(period, Some(period), event)
+    }
+
+    pub fn cancel(&self, id: TimerId) -> bool {
+        let mut timers = self.timers.lock().unwrap();
+        let before = timers.len();
+        timers.retain(|timer| timer.id != id);
+        timers.len() != before
+    }
+
+    /// Events of every timer that is due, in due order. Periodic timers are
+    /// rescheduled for their next period still to come; periods missed while
+    /// the agent was busy are skipped rather than caught up in a burst.
+    pub fn take_due(&self) -> Vec<String> {
+        let now = Instant::now();
+        let mut timers = self.timers.lock().unwrap();
+        timers.sort_by_key(|timer| (timer.due, timer.id));
+        let mut events = Vec::new();
+        for timer in timers.iter_mut().filter(|timer| timer.due <= now) {
+            events.push(timer.event.clone());
+            if let Some(period) = timer.period {
+                let periods = now.duration_since(timer.due).as_nanos() / period.as_nanos() + 1;
+                timer.due += Duration::from_nanos((periods * period.as_nanos()) as u64);
+            }
+        }
+        timers.retain(|timer| timer.due > now || timer.period.is_some());
+        events
+    }
+
+    /// Whether a one-shot timer is still waiting to fire.
+    pub fn has_pending_timer(&self) -> bool {
+        self.timers.lock().unwrap().iter().any(|timer| timer.period.is_none())
+    }
+
+    pub fn clear_timers(&self) {
+        self.timers.lock().unwrap().clear();
+    }
+
+    pub fn wake(&self) {
+        self.woken.store(true, Ordering::SeqCst);
+        self.notify.notify_one();
+    }
+
+    pub fn take_woken(&self) -> bool {
+        self.woken.swap(false, Ordering::SeqCst)
+    }
+
+    /// Wait until something arrives, the next timer is due, or `limit` passes.
+    pub async fn park(&self, limit: Duration) {
+        let next_due = self.timers.lock().unwrap().iter().map(|timer| timer.due).min();
+        let deadline = Instant::now() + limit;
+        let until = next_due.map_or(deadline, |due| due.min(deadline));
+        if !self.letters.lock().unwrap().is_empty() || self.woken.load(Ordering::SeqCst) {
+            return;
+        }
+        tokio::select! {
+            _ = self.notify.notified() => {}
+            _ = tokio::time::sleep_until(until) => {}
+        }
+    }
+}
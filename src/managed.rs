@@ -0,0 +1,684 @@
+//! Wrapper that runs an agent under a [`Switchboard`].
+use crate::budget::{Budget, Meter, Throttle, Violation};
+use crate::checkpoint::RestartState;
+use crate::conversation::{Envelope, Reply, RequestError};
+use crate::lifecycle::{ExitStatus, Lifecycle};
+use crate::context::ContextExt;
+use crate::mailbox::{DeadLetter, Inbox, Letter, Priority, SendError, TIMER};
+use crate::protocol::Proposal;
+use crate::recovery::{Circuit, Decision, Recovering, Recovery, CIRCUIT_TOPIC};
+use crate::simulation;
+use crate::supervisor::Command;
+use crate::timeout::{Phase, TimedOut, Timeouts, CANCEL_GRACE};
+use crate::{Performative, Switchboard};
+use agentropic_core::{Agent, AgentContext, AgentError, AgentId, AgentResult};
+use async_trait::async_trait;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Longest a reactive or finished agent stays parked, so the runtime still
+/// gets a chance to stop it.
+const PARK_LIMIT: Duration = Duration::from_secs(1);
+
+/// The message currently being handled, so it can be replied to.
+#[derive(Debug, Clone)]
+pub(crate) struct Incoming {
+    pub sender: String,
+    pub sender_id: Option<AgentId>,
+    pub conversation_id: Option<String>,
+    pub deadline: Option<Instant>,
+}
+
+/// Per-agent state visible to [`ContextExt`](crate::ContextExt) while a managed agent runs.
+pub(crate) struct Scope {
+    pub name: String,
+    pub id: AgentId,
+    pub board: Switchboard,
+    pub inbox: Arc<Inbox>,
+    pub meter: Arc<Meter>,
+    pub incoming: Mutex<Option<Incoming>>,
+    /// Set by [`stop_self`](crate::ContextExt::stop_self).
+    pub stopping: AtomicBool,
+    /// Proposals this agent made in Contract Net conversations, by conversation.
+    pub proposed: Mutex<BTreeMap<String, Proposal>>,
+    /// Which state a [`Checkpointed`](crate::Checkpointed) agent restarts with.
+    pub restart_state: RestartState,
+}
+
+impl Scope {
+    /// Send to `receiver` after running the send middleware. Messages to
+    /// managed agents go straight to their inbox so the switchboard can
+    /// count them until handled; external agents are reached through the runtime.
+    pub fn send(&self, ctx: &AgentContext, receiver: &str, letter: Letter) -> Result<(), SendError> {
+        let (receiver, letter, delay) = self.board.screen_send(receiver, letter)?;
+        if !self.board.is_managed(&receiver) && self.board.is_external(&receiver) {
+            ctx.send_message(&receiver, letter.performative.as_str(), &letter.envelope.body);
+            return Ok(());
+        }
+        self.board.route(receiver, letter, delay)
+    }
+
+    pub fn letter(
+        &self, performative: Performative, content: &str, conversation_id: Option<String>, in_reply_to: Option<String>,
+    ) -> Letter {
+        let envelope = Envelope {
+            sender_id: Some(self.id),
+            conversation_id,
+            in_reply_to,
+            priority: Priority::Normal,
+            deadline: None,
+            body: content.to_string(),
+        };
+        Letter { sender: self.name.clone(), performative, envelope }
+    }
+
+    /// This scope, for the implementation with `id` that took over the agent.
+    fn handed_to(&self, id: AgentId) -> Scope {
+        Scope {
+            name: self.name.clone(),
+            id,
+            board: self.board.clone(),
+            inbox: self.inbox.clone(),
+            meter: self.meter.clone(),
+            incoming: Mutex::new(None),
+            stopping: AtomicBool::new(self.stopping.load(Ordering::SeqCst)),
+            proposed: Mutex::new(std::mem::take(&mut *self.proposed.lock().unwrap())),
+            restart_state: self.restart_state,
+        }
+    }
+
+    /// Answer the message currently being handled, echoing its conversation id.
+    pub fn reply(&self, ctx: &AgentContext, performative: Performative, content: &str) -> Result<(), RequestError> {
+        let incoming = self.incoming.lock().unwrap().clone();
+        let incoming = incoming.ok_or(RequestError::NothingToReplyTo)?;
+        let id = incoming.conversation_id;
+        self.send(ctx, &incoming.sender, self.letter(performative, content, id.clone(), id))?;
+        Ok(())
+    }
+}
+
+tokio::task_local! {
+    static SCOPE: Arc<Scope>;
+}
+
+/// The scope of the managed agent running on this task, if any.
+pub(crate) fn current() -> Option<Arc<Scope>> {
+    SCOPE.try_with(Arc::clone).ok()
+}
+
+/// Make `call`, one of the inner agent's calls in `phase`, within `scope`
+/// and timed by its meter. If it runs past `limit` it is cancelled, given
+/// [`CANCEL_GRACE`] to return, then dropped and announced as timed out.
+async fn timed<F>(
+    scope: &Arc<Scope>, phase: Phase, limit: Option<Duration>, call: F,
+) -> Result<AgentResult<()>, TimedOut>
+where
+    F: Future<Output = AgentResult<()>> + Unpin,
+{
+    let cancellation = scope.board.arm(&scope.name);
+    let call = SCOPE.scope(scope.clone(), scope.meter.time(call));
+    let Some(limit) = limit else {
+        return Ok(call.await);
+    };
+    tokio::pin!(call);
+    if let Ok(result) = tokio::time::timeout(limit, &mut call).await {
+        return Ok(result);
+    }
+    cancellation.cancel();
+    let _ = tokio::time::timeout(CANCEL_GRACE, &mut call).await;
+    let timed_out = TimedOut { phase, limit };
+    scope.board.announce(&scope.name, Lifecycle::TimedOut(timed_out));
+    Err(timed_out)
+}
+
+/// Make `call` as [`timed`] does, failing it with [`TimedOut`] if it timed out.
+async fn guarded<F>(scope: &Arc<Scope>, phase: Phase, limit: Option<Duration>, call: F) -> AgentResult<()>
+where
+    F: Future<Output = AgentResult<()>> + Unpin,
+{
+    timed(scope, phase, limit, call).await.unwrap_or_else(|timed_out| Err(timed_out.into()))
+}
+
+/// An agent spawned through [`Switchboard::spawn`].
+///
+/// Delivers its inbox and due timers before each `execute`, and hands
+/// replies to the requests waiting on them. A [`Performative::Subscribe`] whose content
+/// names a topic subscribes the sender to that topic and is answered with
+/// [`Performative::Agree`].
+///
+/// Once the inner agent calls [`stop_self`](crate::ContextExt::stop_self)
+/// it is shut down and never called again; incoming messages become
+/// [dead letters](Switchboard::dead_letters).
+pub struct Managed {
+    inner: Box<dyn Agent>,
+    scope: Arc<Scope>,
+    /// Set once `initialize` has run, so running it again is a restart.
+    initialized: bool,
+    started: bool,
+    /// Set when the runtime restarts the agent after a failure.
+    supervised: bool,
+    reactive: bool,
+    finished: bool,
+    /// Set when the inner agent failed and its supervisor is handling it.
+    crashed: bool,
+    recovery: Option<Recovering>,
+    budget: Budget,
+    throttle: Option<Throttle>,
+    timeouts: Timeouts,
+}
+
+impl Managed {
+    pub(crate) fn new(
+        inner: Box<dyn Agent>, name: &str, board: Switchboard, inbox: Arc<Inbox>, meter: Arc<Meter>,
+        supervised: bool, restart_state: RestartState,
+    ) -> Self {
+        let scope = Scope {
+            name: name.to_string(),
+            id: *inner.id(),
+            board,
+            inbox,
+            meter,
+            incoming: Mutex::new(None),
+            stopping: AtomicBool::new(false),
+            proposed: Mutex::new(BTreeMap::new()),
+            restart_state,
+        };
+        Self {
+            inner,
+            scope: Arc::new(scope),
+            initialized: false,
+            started: false,
+            supervised,
+            reactive: false,
+            finished: false,
+            crashed: false,
+            recovery: None,
+            budget: Budget::new(),
+            throttle: None,
+            timeouts: Timeouts::new(),
+        }
+    }
+
+    /// Only run `execute` when woken; see [`Switchboard::spawn_reactive`].
+    pub(crate) fn reactive(mut self) -> Self {
+        self.reactive = true;
+        self
+    }
+
+    /// Restart the agent under `recovery` when it fails.
+    pub(crate) fn recovering(mut self, recovery: Recovery) -> Self {
+        self.recovery = Some(Recovering::new(recovery));
+        self
+    }
+
+    /// Hold the agent to `budget`.
+    pub(crate) fn budgeted(mut self, budget: Budget) -> Self {
+        self.throttle = budget.max_rate().map(Throttle::new);
+        self.budget = budget;
+        self
+    }
+
+    /// Fail the inner agent's calls that run past `timeouts`.
+    pub(crate) fn timed(mut self, timeouts: Timeouts) -> Self {
+        self.timeouts = timeouts;
+        self
+    }
+
+    /// How long a handler may run: the tighter of its timeout and the
+    /// budget, and whether it was the budget.
+    fn handler_limit(&self) -> (Option<Duration>, bool) {
+        let limit = self.timeouts.limit(Phase::HandleMessage);
+        match self.budget.max_handler() {
+            Some(max) if limit.is_none_or(|limit| max <= limit) => (Some(max), true),
+            _ => (limit, false),
+        }
+    }
+
+    /// Count and report going over the budget.
+    fn over_budget(&self, violation: Violation) {
+        self.scope.meter.violated();
+        self.scope.board.announce(&self.scope.name, Lifecycle::OverBudget(violation));
+    }
+
+    /// Wait for the next second if this one's allowance of messages is used up.
+    async fn pace(&mut self) {
+        let Some(throttle) = &mut self.throttle else {
+            return;
+        };
+        if let Some(until) = throttle.admit() {
+            let limit = throttle.limit();
+            self.over_budget(Violation::Throttled { limit });
+            tokio::time::sleep_until(until).await;
+        }
+    }
+
+    /// Hand queued letters and due timer events to the inner agent. Letters
+    /// that expired, or arrive once it has finished, become dead letters instead.
+    async fn drain(&mut self, ctx: &AgentContext) -> AgentResult<()> {
+        while let Some(mut letter) = self.scope.inbox.pop_letter() {
+            let screened = if self.finished {
+                Err(SendError::Stopped(self.scope.name.clone()))
+            } else if self.scope.board.circuit(&self.scope.name) == Some(Circuit::Open) {
+                Err(SendError::CircuitOpen(self.scope.name.clone()))
+            } else if letter.is_expired() {
+                Err(SendError::Expired(self.scope.name.clone()))
+            } else {
+                self.scope.board.screen_receive(&self.scope.name, &mut letter)
+            };
+            match screened {
+                Err(reason) => {
+                    self.scope.board.dead_letter(DeadLetter::new(&self.scope.name, letter, reason));
+                    self.scope.board.handled();
+                    continue;
+                }
+                Ok(delay) if !delay.is_zero() => tokio::time::sleep(delay).await,
+                Ok(_) => {}
+            }
+            self.pace().await;
+            // A handler that failed was announced, and the agent carries on
+            let _ = self.receive(
+                ctx, &letter.sender, letter.performative.as_str(), Some(&letter.envelope), &letter.envelope.body,
+            ).await;
+            self.scope.board.handled();
+        }
+        for event in self.scope.inbox.take_due() {
+            let envelope = Envelope {
+                sender_id: None,
+                conversation_id: None,
+                in_reply_to: None,
+                priority: Priority::Normal,
+                deadline: None,
+                body: event,
+            };
+            self.pace().await;
+            let _ = self.receive(ctx, TIMER, Performative::Inform.as_str(), Some(&envelope), &envelope.body).await;
+        }
+        self.finish_if_stopping(ctx).await
+    }
+
+    async fn receive(
+        &mut self, ctx: &AgentContext, sender: &str, perf: &str, envelope: Option<&Envelope>, body: &str,
+    ) -> AgentResult<()> {
+        simulation::record_delivery(&self.scope.name, sender, perf, body);
+        if self.finished {
+            return Ok(());
+        }
+        self.scope.meter.handled();
+        let result = self.deliver(ctx, sender, perf, envelope, body).await;
+        self.finish_if_stopping(ctx).await?;
+        result
+    }
+
+    /// Shut the inner agent down if it asked to stop.
+    async fn finish_if_stopping(&mut self, ctx: &AgentContext) -> AgentResult<()> {
+        if self.finished || !self.scope.stopping.load(Ordering::SeqCst) {
+            return Ok(());
+        }
+        self.finished = true;
+        self.scope.inbox.clear_timers();
+        let limit = self.timeouts.limit(Phase::Shutdown);
+        let result = guarded(&self.scope, Phase::Shutdown, limit, self.inner.shutdown(ctx)).await;
+        let status = match &result {
+            Ok(()) => ExitStatus::Completed,
+            Err(err) => ExitStatus::Failed(err.to_string()),
+        };
+        self.scope.board.exit(&self.scope.name, status);
+        result
+    }
+
+    /// Shut the inner agent down for good after a call timed out, instead
+    /// of leaving it to be restarted; see [`Timeouts::stop_on_timeout`].
+    async fn stop_timed_out(&mut self, ctx: &AgentContext, timed_out: TimedOut) -> AgentResult<()> {
+        self.finished = true;
+        self.scope.inbox.clear_timers();
+        let limit = self.timeouts.limit(Phase::Shutdown);
+        if let Err(err) = guarded(&self.scope, Phase::Shutdown, limit, self.inner.shutdown(ctx)).await {
+            self.scope.board.announce(&self.scope.name, Lifecycle::Failed(err.to_string()));
+        }
+        self.scope.board.exit(&self.scope.name, ExitStatus::Failed(timed_out.to_string()));
+        Ok(())
+    }
+
+    /// Hand a failure to the supervision tree, if the agent is in one.
+    fn escalate(&mut self) -> bool {
+        if !self.scope.board.child_failed(&self.scope.name) {
+            return false;
+        }
+        self.crashed = true;
+        true
+    }
+
+    /// Schedule a restart under the agent's [`Recovery`] policy, or open its
+    /// circuit. Returns false if it has no such policy.
+    fn recover(&mut self, ctx: &AgentContext, err: &AgentError) -> bool {
+        let Some(recovery) = &mut self.recovery else {
+            return false;
+        };
+        let (name, board) = (&self.scope.name, &self.scope.board);
+        let circuit = board.circuit(name).unwrap_or(Circuit::Closed);
+        // A restart is announced when it happens
+        if let Decision::Open(_) = recovery.failed(circuit) {
+            board.set_circuit(name, Circuit::Open);
+            board.withdraw_all(name);
+            self.scope.inbox.clear_timers();
+            if circuit != Circuit::HalfOpen {
+                self.announce(ctx, Performative::Failure, &err.to_string());
+            }
+        }
+        true
+    }
+
+    /// Restart a failed agent once its backoff or cooldown is over; until
+    /// then, refuse what was queued for it if its circuit is open.
+    async fn retry(&mut self, ctx: &AgentContext) -> AgentResult<()> {
+        let restart_at = self.recovery.as_ref().and_then(|recovery| recovery.restart_at);
+        let circuit = self.scope.board.circuit(&self.scope.name);
+        let due = restart_at.is_some_and(|at| at <= Instant::now());
+        if circuit != Some(Circuit::HalfOpen) && !due {
+            match (circuit, restart_at) {
+                (Some(Circuit::Open), _) => {
+                    self.drain(ctx).await?;
+                    let limit = restart_at.map_or(PARK_LIMIT, |at| at.saturating_duration_since(Instant::now()));
+                    self.scope.inbox.park(limit.min(PARK_LIMIT)).await;
+                }
+                (_, Some(at)) => tokio::time::sleep_until(at).await,
+                _ => {}
+            }
+            return Ok(());
+        }
+        if circuit == Some(Circuit::Open) {
+            self.scope.board.set_circuit(&self.scope.name, Circuit::HalfOpen);
+        }
+        if let Some(recovery) = &mut self.recovery {
+            recovery.pending = false;
+        }
+        self.started = false;
+        Agent::initialize(self, ctx).await
+    }
+
+    /// Close a half-open circuit after the agent ran successfully.
+    fn close_circuit(&mut self, ctx: &AgentContext) {
+        if self.scope.board.circuit(&self.scope.name) != Some(Circuit::HalfOpen) {
+            return;
+        }
+        self.scope.board.set_circuit(&self.scope.name, Circuit::Closed);
+        if let Some(recovery) = &mut self.recovery {
+            recovery.recovered();
+        }
+        self.announce(ctx, Performative::Inform, "recovered");
+    }
+
+    /// Publish a change of circuit to [`CIRCUIT_TOPIC`].
+    fn announce(&self, ctx: &AgentContext, performative: Performative, content: &str) {
+        // Publishing only fails outside a managed agent's scope
+        let publish = || ctx.publish(CIRCUIT_TOPIC, performative, content);
+        let _ = SCOPE.sync_scope(self.scope.clone(), publish);
+    }
+
+    /// Carry out a decision of this agent's supervisor, or of a graceful shutdown.
+    async fn obey(&mut self, ctx: &AgentContext, command: Command) -> AgentResult<()> {
+        if let Command::Stop(timeout) = command {
+            self.finished = true;
+            self.scope.inbox.clear_timers();
+            let limit = self.timeouts.limit(Phase::Shutdown);
+            let shutdown = guarded(&self.scope, Phase::Shutdown, limit, self.inner.shutdown(ctx));
+            let status = match tokio::time::timeout(timeout, shutdown).await {
+                Ok(Ok(())) => ExitStatus::Stopped,
+                Ok(Err(err)) => ExitStatus::Failed(err.to_string()),
+                // Listed in the shutdown report as timed out
+                Err(_) => {
+                    self.scope.board.overran(&self.scope.name);
+                    ExitStatus::Stopped
+                }
+            };
+            self.scope.board.exit(&self.scope.name, status);
+            return Ok(());
+        }
+        if command == Command::Replace {
+            return self.take_over(ctx).await;
+        }
+        if !std::mem::take(&mut self.crashed) {
+            let limit = self.timeouts.limit(Phase::Shutdown);
+            if let Err(err) = guarded(&self.scope, Phase::Shutdown, limit, self.inner.shutdown(ctx)).await {
+                self.scope.board.announce(&self.scope.name, Lifecycle::Failed(err.to_string()));
+            }
+        }
+        self.scope.inbox.clear_timers();
+        match command {
+            Command::Restart => {
+                self.started = false;
+                Agent::initialize(self, ctx).await
+            }
+            Command::Terminate(reason) => {
+                self.finished = true;
+                self.scope.board.exit(&self.scope.name, ExitStatus::Failed(reason));
+                Ok(())
+            }
+            Command::Stop(_) | Command::Replace => Ok(()),
+        }
+    }
+
+    /// Shut the inner agent down and start the implementation waiting to
+    /// replace it, with the same mailbox, as if it had just been spawned.
+    async fn take_over(&mut self, ctx: &AgentContext) -> AgentResult<()> {
+        let Some(agent) = self.scope.board.take_replacement(&self.scope.name) else {
+            return Ok(());
+        };
+        if !std::mem::take(&mut self.crashed) {
+            let limit = self.timeouts.limit(Phase::Shutdown);
+            if let Err(err) = guarded(&self.scope, Phase::Shutdown, limit, self.inner.shutdown(ctx)).await {
+                self.scope.board.announce(&self.scope.name, Lifecycle::Failed(err.to_string()));
+            }
+        }
+        self.scope.inbox.clear_timers();
+        self.inner = agent;
+        self.scope = Arc::new(self.scope.handed_to(*self.inner.id()));
+        let (name, board) = (&self.scope.name, &self.scope.board);
+        board.reassign(name, self.scope.id);
+        if board.circuit(name).is_some() {
+            board.set_circuit(name, Circuit::Closed);
+        }
+        if let Some(recovery) = &mut self.recovery {
+            recovery.recovered();
+            recovery.pending = false;
+            recovery.restart_at = None;
+        }
+        board.announce(name, Lifecycle::Replaced);
+        self.initialized = false;
+        self.started = false;
+        Agent::initialize(self, ctx).await
+    }
+
+    /// Tell whoever is waiting on a request whose handler timed out that
+    /// no answer is coming, and count the timeout against the budget if
+    /// the budget set the limit.
+    fn gave_up(&self, ctx: &AgentContext, sender: &str, timed_out: TimedOut, by_budget: bool) {
+        let requested = self.scope.incoming.lock().unwrap().as_ref().is_some_and(|i| i.conversation_id.is_some());
+        if requested {
+            let _ = self.scope.reply(ctx, Performative::Failure, &timed_out.to_string());
+        }
+        if by_budget {
+            self.over_budget(Violation::SlowHandler { sender: sender.to_string(), limit: timed_out.limit });
+        }
+    }
+
+    async fn deliver(
+        &mut self, ctx: &AgentContext, sender: &str, perf: &str, envelope: Option<&Envelope>, body: &str,
+    ) -> AgentResult<()> {
+        let sender_id = envelope.and_then(|e| e.sender_id).or_else(|| self.scope.board.id_of(sender));
+        if let Ok(performative) = perf.parse() {
+            let conversations = self.scope.board.conversations();
+            let reply = Reply { sender: sender.to_string(), sender_id, performative, content: body.to_string() };
+            let resolved = match envelope {
+                Some(Envelope { in_reply_to: Some(in_reply_to), .. }) => conversations.resolve(in_reply_to, reply),
+                Some(_) => false,
+                None => conversations.resolve_legacy(&self.scope.name, reply),
+            };
+            if resolved {
+                return Ok(());
+            }
+        }
+
+        let incoming = Incoming {
+            sender: sender.to_string(),
+            sender_id,
+            conversation_id: envelope.and_then(|e| e.conversation_id.clone()),
+            deadline: envelope.and_then(|e| e.deadline),
+        };
+        *self.scope.incoming.lock().unwrap() = Some(incoming);
+
+        let result = if perf.parse() == Ok(Performative::Subscribe) && !body.is_empty() {
+            self.scope.board.subscribe(body, sender);
+            self.scope.reply(ctx, Performative::Agree, body).map_err(Into::into)
+        } else {
+            let (limit, by_budget) = self.handler_limit();
+            let handling = self.inner.handle_message(ctx, sender, perf, body);
+            match timed(&self.scope, Phase::HandleMessage, limit, handling).await {
+                Ok(Err(err)) => {
+                    let failed = Lifecycle::HandlerFailed { sender: sender.to_string(), error: err.to_string() };
+                    self.scope.board.announce(&self.scope.name, failed);
+                    Err(err)
+                }
+                Ok(Ok(())) => Ok(()),
+                Err(timed_out) => {
+                    self.gave_up(ctx, sender, timed_out, by_budget);
+                    Err(timed_out.into())
+                }
+            }
+        };
+        *self.scope.incoming.lock().unwrap() = None;
+        result
+    }
+}
+
+#[async_trait]
+impl Agent for Managed {
+    fn id(&self) -> &AgentId { self.inner.id() }
+
+    async fn initialize(&mut self, ctx: &AgentContext) -> AgentResult<()> {
+        if self.finished {
+            return Ok(());
+        }
+        if std::mem::replace(&mut self.initialized, true) {
+            self.scope.board.announce(&self.scope.name, Lifecycle::Restarting);
+        }
+        self.scope.board.restarted(&self.scope.name);
+        let limit = self.timeouts.limit(Phase::Initialize);
+        let result = match timed(&self.scope, Phase::Initialize, limit, self.inner.initialize(ctx)).await {
+            Err(timed_out) if self.timeouts.stops_on_timeout() => return self.stop_timed_out(ctx, timed_out).await,
+            result => result.unwrap_or_else(|timed_out| Err(timed_out.into())),
+        };
+        match &result {
+            Ok(()) => self.scope.board.announce(&self.scope.name, Lifecycle::Initialized),
+            Err(err) => {
+                self.scope.board.announce(&self.scope.name, Lifecycle::Failed(err.to_string()));
+                if self.escalate() || self.recover(ctx, err) {
+                    return Ok(());
+                }
+                self.scope.board.started(&self.scope.name);
+            }
+        }
+        self.finish_if_stopping(ctx).await?;
+        result
+    }
+
+    async fn execute(&mut self, ctx: &AgentContext) -> AgentResult<()> {
+        if self.finished {
+            // Its timers are cleared, so only a letter to dead-letter wakes it.
+            self.drain(ctx).await?;
+            self.scope.inbox.take_woken();
+            self.scope.inbox.park(PARK_LIMIT).await;
+            return Ok(());
+        }
+        if let Some(command) = self.scope.board.take_command(&self.scope.name) {
+            return self.obey(ctx, command).await;
+        }
+        if self.recovery.as_ref().is_some_and(|recovery| recovery.pending) {
+            return self.retry(ctx).await;
+        }
+        self.drain(ctx).await?;
+        if self.reactive && self.started && !self.scope.inbox.take_woken() {
+            self.scope.inbox.park(PARK_LIMIT).await;
+            return Ok(());
+        }
+        if self.finished {
+            return Ok(());
+        }
+        let limit = self.timeouts.limit(Phase::Execute);
+        let result = timed(&self.scope, Phase::Execute, limit, self.inner.execute(ctx)).await;
+        if !self.started {
+            self.started = true;
+            self.scope.board.started(&self.scope.name);
+        }
+        let result = match result {
+            Err(timed_out) if self.timeouts.stops_on_timeout() => return self.stop_timed_out(ctx, timed_out).await,
+            result => result.unwrap_or_else(|timed_out| Err(timed_out.into())),
+        };
+        if let Err(err) = &result {
+            self.scope.board.announce(&self.scope.name, Lifecycle::Failed(err.to_string()));
+        }
+        match &result {
+            Err(err) if self.escalate() || self.recover(ctx, err) => return Ok(()),
+            Err(err) if !self.supervised => {
+                self.scope.board.exit(&self.scope.name, ExitStatus::Failed(err.to_string()));
+            }
+            // The runtime restarts it, and `initialize` registers again
+            Err(_) => self.scope.board.withdraw_all(&self.scope.name),
+            _ => {
+                self.close_circuit(ctx);
+                self.finish_if_stopping(ctx).await?
+            }
+        }
+        result
+    }
+
+    async fn shutdown(&mut self, ctx: &AgentContext) -> AgentResult<()> {
+        if self.finished {
+            return Ok(());
+        }
+        self.finished = true;
+        self.scope.inbox.clear_timers();
+        let limit = self.timeouts.limit(Phase::Shutdown);
+        let result = guarded(&self.scope, Phase::Shutdown, limit, self.inner.shutdown(ctx)).await;
+        self.scope.board.exit(&self.scope.name, ExitStatus::Stopped);
+        result
+    }
+
+    async fn handle_message(
+        &mut self, ctx: &AgentContext, sender: &str, perf: &str, content: &str,
+    ) -> AgentResult<()> {
+        // Only agents outside the switchboard reach a managed agent through
+        // the runtime, and they should be able to get an answer
+        self.scope.board.add_external(sender);
+        let Ok(performative) = perf.parse() else {
+            return self.receive(ctx, sender, perf, None, content).await;
+        };
+        let envelope = Envelope {
+            sender_id: None,
+            conversation_id: None,
+            in_reply_to: None,
+            priority: Priority::Normal,
+            deadline: None,
+            body: content.to_string(),
+        };
+        let mut letter = Letter { sender: sender.to_string(), performative, envelope };
+        let screened = match self.scope.board.is_closing() {
+            true => Err(SendError::ShuttingDown(self.scope.name.clone())),
+            false => self.scope.board.screen_receive(&self.scope.name, &mut letter),
+        };
+        match screened {
+            Err(reason) => {
+                self.scope.board.dead_letter(DeadLetter::new(&self.scope.name, letter, reason));
+                return Ok(());
+            }
+            Ok(delay) if !delay.is_zero() => tokio::time::sleep(delay).await,
+            Ok(_) => {}
+        }
+        let (sender, perf) = (letter.sender.as_str(), letter.performative.as_str());
+        self.receive(ctx, sender, perf, None, &letter.envelope.body).await
+    }
+}
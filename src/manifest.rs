@@ -0,0 +1,305 @@
+//! Spawning a topology described in a JSON or TOML file instead of in code.
+use crate::{MailboxConfig, Overflow, SpawnOptions, Switchboard};
+use agentropic_core::Agent;
+use agentropic_runtime::prelude::*;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// The agents to spawn, as loaded by [`RuntimeBuilder::load`].
+///
+/// In TOML each agent is an `[[agents]]` table:
+///
+/// ```toml
+/// [[agents]]
+/// name = "responder"
+/// kind = "responder"
+/// reactive = true
+/// mailbox = 8
+/// overflow = "reject"
+/// subscribe = ["questions"]
+/// restart = { strategy = "on_failure", max_retries = 3, backoff_seconds = 1 }
+///
+/// [agents.params]
+/// answer = "Hierarchy, Swarm and Team."
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    #[serde(default)]
+    pub agents: Vec<AgentSpec>,
+}
+
+impl Manifest {
+    /// Read a manifest, as JSON or TOML depending on the file extension.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, ManifestError> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path).map_err(|err| ManifestError::Io {
+            path: path.to_path_buf(),
+            message: err.to_string(),
+        })?;
+        let parsed = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("json") => serde_json::from_str(&text).map_err(|err| err.to_string()),
+            Some("toml") => toml::from_str(&text).map_err(|err| err.to_string()),
+            _ => return Err(ManifestError::UnsupportedFormat(path.to_path_buf())),
+        };
+        parsed.map_err(|message| ManifestError::Parse { path: path.to_path_buf(), message })
+    }
+}
+
+/// One agent in a [`Manifest`].
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AgentSpec {
+    /// The name it is spawned under.
+    pub name: String,
+    /// Which factory in the [`AgentRegistry`] builds it.
+    pub kind: String,
+    /// Handed to the factory; missing params are an empty table.
+    #[serde(default)]
+    pub params: Value,
+    #[serde(default)]
+    pub reactive: bool,
+    /// Bound the mailbox to this many messages; unbounded if left out.
+    #[serde(default)]
+    pub mailbox: Option<usize>,
+    /// What a bounded mailbox does when full; [`Overflow::Block`] if left out.
+    #[serde(default)]
+    pub overflow: Option<Overflow>,
+    /// Topics the agent is subscribed to before it is spawned.
+    #[serde(default)]
+    pub subscribe: Vec<String>,
+    /// Agents it needs until it has shut down; see [`SpawnOptions::depends_on`].
+    #[serde(default)]
+    pub depends_on: Vec<String>,
+    #[serde(default)]
+    pub restart: Option<RestartSpec>,
+}
+
+impl AgentSpec {
+    /// How the switchboard runs the agent.
+    pub fn options(&self) -> SpawnOptions {
+        let mut options = SpawnOptions::new();
+        if self.reactive {
+            options = options.reactive();
+        }
+        if let Some(capacity) = self.mailbox {
+            let overflow = self.overflow.unwrap_or(Overflow::Block);
+            options = options.with_mailbox(MailboxConfig::bounded(capacity, overflow));
+        }
+        if let Some(restart) = &self.restart {
+            options = options.with_policy(restart.policy());
+        }
+        for name in &self.depends_on {
+            options = options.depends_on(name);
+        }
+        options
+    }
+}
+
+/// A [`RestartPolicy`] as written in a manifest; the retry count and
+/// backoff default to the runtime's.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RestartSpec {
+    pub strategy: RestartWhen,
+    #[serde(default)]
+    pub max_retries: Option<u32>,
+    #[serde(default)]
+    pub backoff_seconds: Option<u64>,
+}
+
+impl RestartSpec {
+    pub fn policy(&self) -> RestartPolicy {
+        let strategy = match self.strategy {
+            RestartWhen::Never => RestartStrategy::Never,
+            RestartWhen::Always => RestartStrategy::Always,
+            RestartWhen::OnFailure => RestartStrategy::OnFailure,
+            RestartWhen::ExponentialBackoff => RestartStrategy::ExponentialBackoff,
+        };
+        let mut policy = RestartPolicy::new(strategy);
+        if let Some(retries) = self.max_retries {
+            policy = policy.with_max_retries(retries);
+        }
+        if let Some(seconds) = self.backoff_seconds {
+            policy = policy.with_backoff_seconds(seconds);
+        }
+        policy
+    }
+}
+
+/// The runtime's restart strategies, spelled `"never"`, `"always"`,
+/// `"on_failure"` and `"exponential_backoff"` in a manifest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RestartWhen {
+    Never,
+    Always,
+    OnFailure,
+    ExponentialBackoff,
+}
+
+/// An agent type that can be named in a manifest; add it to an
+/// [`AgentRegistry`] with [`register`](AgentRegistry::register).
+pub trait FromManifest: Agent + Sized + 'static {
+    /// The `kind` manifests refer to it by.
+    const KIND: &'static str;
+
+    /// The agent's `params` table.
+    type Params: DeserializeOwned;
+
+    fn from_params(params: Self::Params) -> Self;
+}
+
+type Factory = Box<dyn Fn(Value) -> Result<Box<dyn Agent>, serde_json::Error> + Send + Sync>;
+
+/// Builds agents from the `kind` and `params` of an [`AgentSpec`].
+#[derive(Default)]
+pub struct AgentRegistry {
+    factories: BTreeMap<String, Factory>,
+}
+
+impl AgentRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Build `A` for agents of kind [`A::KIND`](FromManifest::KIND).
+    pub fn register<A: FromManifest>(self) -> Self {
+        self.register_with(A::KIND, A::from_params)
+    }
+
+    /// Build agents of `kind` with `factory`, for agents that need more
+    /// than their params, such as state shared with `main`. Replaces any
+    /// factory already registered for `kind`.
+    pub fn register_with<P, A, F>(mut self, kind: &str, factory: F) -> Self
+    where
+        P: DeserializeOwned,
+        A: Agent + 'static,
+        F: Fn(P) -> A + Send + Sync + 'static,
+    {
+        let factory = move |params: Value| -> Result<Box<dyn Agent>, serde_json::Error> {
+            let params = if params.is_null() { Value::Object(Default::default()) } else { params };
+            Ok(Box::new(factory(serde_json::from_value(params)?)))
+        };
+        self.factories.insert(kind.to_string(), Box::new(factory));
+        self
+    }
+
+    /// The kinds agents can be built as, in order.
+    pub fn kinds(&self) -> Vec<String> {
+        self.factories.keys().cloned().collect()
+    }
+
+    /// Build the agent `spec` describes.
+    pub fn create(&self, spec: &AgentSpec) -> Result<Box<dyn Agent>, ManifestError> {
+        let factory = self.factories.get(&spec.kind).ok_or_else(|| ManifestError::UnknownKind {
+            agent: spec.name.clone(),
+            kind: spec.kind.clone(),
+        })?;
+        factory(spec.params.clone()).map_err(|err| ManifestError::InvalidParams {
+            agent: spec.name.clone(),
+            message: err.to_string(),
+        })
+    }
+}
+
+/// Why a manifest could not be turned into agents.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ManifestError {
+    Io { path: PathBuf, message: String },
+    /// The file is not valid JSON or TOML, or not shaped like a [`Manifest`].
+    Parse { path: PathBuf, message: String },
+    /// The file ends in neither `.json` nor `.toml`.
+    UnsupportedFormat(PathBuf),
+    /// No factory is registered for the agent's kind.
+    UnknownKind { agent: String, kind: String },
+    /// The factory could not make sense of the agent's params.
+    InvalidParams { agent: String, message: String },
+    /// Two agents share a name.
+    DuplicateName(String),
+}
+
+impl fmt::Display for ManifestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ManifestError::Io { path, message } => write!(f, "cannot read {}: {}", path.display(), message),
+            ManifestError::Parse { path, message } => write!(f, "malformed manifest {}: {}", path.display(), message),
+            ManifestError::UnsupportedFormat(path) => {
+                write!(f, "{} is neither a .json nor a .toml manifest", path.display())
+            }
+            ManifestError::UnknownKind { agent, kind } => write!(f, "agent {} has unknown kind {:?}", agent, kind),
+            ManifestError::InvalidParams { agent, message } => write!(f, "bad params for agent {}: {}", agent, message),
+            ManifestError::DuplicateName(name) => write!(f, "more than one agent is named {}", name),
+        }
+    }
+}
+
+impl std::error::Error for ManifestError {}
+
+/// Spawns the agents of one or more [`Manifest`]s on a fresh [`Runtime`].
+///
+/// Every agent is built as its manifest is added, so a typo in a kind or
+/// a param is reported before anything runs:
+///
+/// ```ignore
+/// let registry = AgentRegistry::new().register::<Asker>().register::<Responder>();
+/// let (runtime, board) = RuntimeBuilder::new(registry).load("data/agents.toml")?.build().await?;
+/// ```
+pub struct RuntimeBuilder {
+    registry: AgentRegistry,
+    board: Switchboard,
+    agents: Vec<(AgentSpec, Box<dyn Agent>)>,
+}
+
+impl RuntimeBuilder {
+    pub fn new(registry: AgentRegistry) -> Self {
+        Self { registry, board: Switchboard::new(), agents: Vec::new() }
+    }
+
+    /// Spawn onto `board` rather than a new switchboard, for instance one
+    /// with middleware already added.
+    pub fn with_switchboard(mut self, board: Switchboard) -> Self {
+        self.board = board;
+        self
+    }
+
+    /// Add the agents of the manifest at `path`; see [`Manifest::from_file`].
+    pub fn load(self, path: impl AsRef<Path>) -> Result<Self, ManifestError> {
+        let manifest = Manifest::from_file(path)?;
+        self.with_manifest(manifest)
+    }
+
+    /// Add the agents of `manifest`, after any added before.
+    pub fn with_manifest(mut self, manifest: Manifest) -> Result<Self, ManifestError> {
+        let mut names: BTreeSet<String> = self.agents.iter().map(|(spec, _)| spec.name.clone()).collect();
+        for spec in manifest.agents {
+            if !names.insert(spec.name.clone()) {
+                return Err(ManifestError::DuplicateName(spec.name));
+            }
+            let agent = self.registry.create(&spec)?;
+            self.agents.push((spec, agent));
+        }
+        Ok(self)
+    }
+
+    /// The names of the agents to be spawned, in the order they will be.
+    pub fn names(&self) -> Vec<String> {
+        self.agents.iter().map(|(spec, _)| spec.name.clone()).collect()
+    }
+
+    /// Subscribe and spawn every agent, in manifest order.
+    pub async fn build(self) -> Result<(Runtime, Switchboard), RuntimeError> {
+        let runtime = Runtime::new();
+        for (spec, agent) in self.agents {
+            for topic in &spec.subscribe {
+                self.board.subscribe(topic, &spec.name);
+            }
+            self.board.spawn_with(&runtime, agent, &spec.name, spec.options()).await?;
+        }
+        Ok((runtime, self.board))
+    }
+}
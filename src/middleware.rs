@@ -0,0 +1,157 @@
+//! Cross-cutting behavior around every message between managed agents.
+use crate::mailbox::{Letter, Priority};
+use crate::Performative;
+use std::time::Duration;
+
+/// A message as seen by [`Middleware`]. Changes made to it are delivered.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Message {
+    pub sender: String,
+    pub receiver: String,
+    pub performative: Performative,
+    pub priority: Priority,
+    pub content: String,
+}
+
+impl Message {
+    pub(crate) fn of(receiver: &str, letter: &Letter) -> Self {
+        Self {
+            sender: letter.sender.clone(),
+            receiver: receiver.to_string(),
+            performative: letter.performative,
+            priority: letter.envelope.priority,
+            content: letter.envelope.body.clone(),
+        }
+    }
+
+    /// Carry changes back into `letter`; the receiver is returned separately.
+    pub(crate) fn apply(self, letter: &mut Letter) -> String {
+        if letter.sender != self.sender {
+            // The id belonged to the original sender; receivers look the new one up by name
+            letter.envelope.sender_id = None;
+            letter.sender = self.sender;
+        }
+        letter.performative = self.performative;
+        letter.envelope.priority = self.priority;
+        letter.envelope.body = self.content;
+        self.receiver
+    }
+}
+
+/// What happens to a message after a [`Middleware`] has seen it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verdict {
+    Deliver,
+    /// Deliver after waiting this long.
+    Delay(Duration),
+    /// Discard the message, for this reason.
+    Drop(String),
+}
+
+/// Runs on every message a managed agent sends or receives; add one with
+/// [`Switchboard::add_middleware`](crate::Switchboard::add_middleware).
+///
+/// Middleware runs in the order it was added. The first [`Verdict::Drop`]
+/// stops the message, which is reported to the sender or kept as a
+/// [`DeadLetter`](crate::DeadLetter); delays add up.
+pub trait Middleware: Send + Sync {
+    /// Called when a managed agent sends `message`. Changing the receiver
+    /// redirects it. Delays only hold back messages to managed agents.
+    fn on_send(&self, _message: &mut Message) -> Verdict {
+        Verdict::Deliver
+    }
+
+    /// Called just before a managed agent handles `message`. A delay holds
+    /// up the receiving agent, which is how rate limits are applied.
+    fn on_receive(&self, _message: &mut Message) -> Verdict {
+        Verdict::Deliver
+    }
+}
+
+/// Prints every message as it is sent.
+#[derive(Debug, Clone, Default)]
+pub struct Tracing {
+    receiving: bool,
+}
+
+impl Tracing {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Also print each message as it is handled.
+    pub fn with_receiving(mut self) -> Self {
+        self.receiving = true;
+        self
+    }
+}
+
+impl Middleware for Tracing {
+    fn on_send(&self, message: &mut Message) -> Verdict {
+        println!("  [trace] {} → {} [{}] {}", message.sender, message.receiver, message.performative, message.content);
+        Verdict::Deliver
+    }
+
+    fn on_receive(&self, message: &mut Message) -> Verdict {
+        if self.receiving {
+            println!("  [trace] {} ← {} [{}] {}", message.receiver, message.sender, message.performative, message.content);
+        }
+        Verdict::Deliver
+    }
+}
+
+/// Blocks or redacts messages containing any of a list of words, ignoring case.
+#[derive(Debug, Clone)]
+pub struct ContentFilter {
+    words: Vec<String>,
+    redact: bool,
+}
+
+impl ContentFilter {
+    /// Drop messages that contain any of `words`. Empty words match nothing.
+    pub fn block<S: AsRef<str>>(words: &[S]) -> Self {
+        let words = words.iter().map(|word| word.as_ref().to_lowercase()).filter(|word| !word.is_empty());
+        Self { words: words.collect(), redact: false }
+    }
+
+    /// Deliver messages with each of `words` replaced by asterisks.
+    pub fn redact<S: AsRef<str>>(words: &[S]) -> Self {
+        Self { redact: true, ..Self::block(words) }
+    }
+
+    /// Byte ranges of every blocked word in `content`.
+    fn matches(&self, content: &str) -> Vec<(usize, usize)> {
+        let lower = content.to_lowercase();
+        if lower.len() != content.len() {
+            // Lowercasing changed byte offsets; fall back to whole-message matching
+            return match self.words.iter().any(|word| lower.contains(word.as_str())) {
+                true => vec![(0, content.len())],
+                false => Vec::new(),
+            };
+        }
+        let mut ranges = Vec::new();
+        for word in &self.words {
+            ranges.extend(lower.match_indices(word.as_str()).map(|(start, word)| (start, start + word.len())));
+        }
+        ranges.sort_unstable();
+        ranges
+    }
+}
+
+impl Middleware for ContentFilter {
+    fn on_send(&self, message: &mut Message) -> Verdict {
+        let ranges = self.matches(&message.content);
+        if ranges.is_empty() {
+            return Verdict::Deliver;
+        }
+        if !self.redact {
+            return Verdict::Drop("blocked by content filter".to_string());
+        }
+        let mut redacted = message.content.clone().into_bytes();
+        for (start, end) in ranges {
+            redacted[start..end].fill(b'*');
+        }
+        message.content = String::from_utf8_lossy(&redacted).into_owned();
+        Verdict::Deliver
+    }
+}
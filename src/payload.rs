@@ -0,0 +1,121 @@
+//! Structured message payloads, carried as JSON in the message content.
+use crate::Performative;
+use agentropic_core::{Agent, AgentContext, AgentError, AgentResult};
+use async_trait::async_trait;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// A sealed bid sent in reply to a CFP.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Bid {
+    pub item: String,
+    pub amount: f64,
+}
+
+/// A weighted yes/no vote on a proposal.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Vote {
+    pub approve: bool,
+    pub weight: f64,
+}
+
+/// A task handed to an executor.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TaskAssignment {
+    pub task: String,
+    pub executor: String,
+}
+
+/// Report that an executor finished a task.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TaskCompletion {
+    pub task: String,
+    pub executor: String,
+    pub duration_ms: u64,
+}
+
+/// A payload that could not be encoded or decoded.
+#[derive(Debug)]
+pub struct PayloadError {
+    type_name: &'static str,
+    source: serde_json::Error,
+}
+
+impl fmt::Display for PayloadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "malformed {} payload: {}", self.type_name, self.source)
+    }
+}
+
+impl std::error::Error for PayloadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+impl From<PayloadError> for AgentError {
+    fn from(err: PayloadError) -> Self {
+        AgentError::ExecutionFailed(err.to_string())
+    }
+}
+
+fn short_type_name<T>() -> &'static str {
+    let name = std::any::type_name::<T>();
+    name.rsplit("::").next().unwrap_or(name)
+}
+
+/// Serialize a payload to the JSON string sent as message content.
+pub fn encode<T: Serialize>(payload: &T) -> Result<String, PayloadError> {
+    serde_json::to_string(payload).map_err(|source| PayloadError {
+        type_name: short_type_name::<T>(),
+        source,
+    })
+}
+
+/// Deserialize message content produced by [`encode`].
+pub fn decode<T: DeserializeOwned>(content: &str) -> Result<T, PayloadError> {
+    serde_json::from_str(content).map_err(|source| PayloadError {
+        type_name: short_type_name::<T>(),
+        source,
+    })
+}
+
+/// Receives decoded payloads instead of raw strings.
+///
+/// Implement this next to [`Agent`] and forward from `handle_message`:
+///
+/// ```ignore
+/// async fn handle_message(&mut self, ctx: &AgentContext, s: &str, p: &str, content: &str) -> AgentResult<()> {
+///     self.handle_payload(ctx, s, p, content).await
+/// }
+/// ```
+#[async_trait]
+pub trait TypedHandler: Agent {
+    /// The payload type this agent accepts.
+    type Payload: DeserializeOwned + Send;
+
+    async fn handle_typed_message(
+        &mut self,
+        ctx: &AgentContext,
+        sender: &str,
+        performative: Performative,
+        payload: Self::Payload,
+    ) -> AgentResult<()>;
+
+    /// Parse the performative and decode `content`, then call
+    /// [`handle_typed_message`](TypedHandler::handle_typed_message).
+    ///
+    /// Malformed content is returned as an error rather than ignored.
+    async fn handle_payload(
+        &mut self,
+        ctx: &AgentContext,
+        sender: &str,
+        performative: &str,
+        content: &str,
+    ) -> AgentResult<()> {
+        let performative = performative.parse()?;
+        let payload = decode(content)?;
+        self.handle_typed_message(ctx, sender, performative, payload).await
+    }
+}
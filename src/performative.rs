@@ -0,0 +1,129 @@
+//! FIPA performatives as a typed enum instead of free-form strings.
+use agentropic_core::AgentError;
+use std::fmt;
+use std::str::FromStr;
+
+/// The intent of a message, following the FIPA ACL performatives.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Performative {
+    /// Share a fact.
+    Inform,
+    /// Ask the receiver to perform an action.
+    Request,
+    /// Ask the receiver for information.
+    Query,
+    /// Suggest a course of action or make an offer.
+    Propose,
+    /// Accept a proposal.
+    Accept,
+    /// Reject a proposal.
+    Reject,
+    /// Acknowledge that something is true.
+    Confirm,
+    /// Register interest in future information.
+    Subscribe,
+    /// Call for proposals.
+    Cfp,
+    /// Decline a request.
+    Refuse,
+    /// Agree to perform a requested action.
+    Agree,
+    /// Report that a requested action failed.
+    Failure,
+    /// The received message could not be understood.
+    NotUnderstood,
+}
+
+impl Performative {
+    /// Every performative, in declaration order.
+    pub const ALL: [Performative; 13] = [
+        Performative::Inform,
+        Performative::Request,
+        Performative::Query,
+        Performative::Propose,
+        Performative::Accept,
+        Performative::Reject,
+        Performative::Confirm,
+        Performative::Subscribe,
+        Performative::Cfp,
+        Performative::Refuse,
+        Performative::Agree,
+        Performative::Failure,
+        Performative::NotUnderstood,
+    ];
+
+    /// Wire name passed to the Router.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Performative::Inform => "inform",
+            Performative::Request => "request",
+            Performative::Query => "query",
+            Performative::Propose => "propose",
+            Performative::Accept => "accept",
+            Performative::Reject => "reject",
+            Performative::Confirm => "confirm",
+            Performative::Subscribe => "subscribe",
+            Performative::Cfp => "cfp",
+            Performative::Refuse => "refuse",
+            Performative::Agree => "agree",
+            Performative::Failure => "failure",
+            Performative::NotUnderstood => "not_understood",
+        }
+    }
+}
+
+impl fmt::Display for Performative {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Returned when a performative string matches no known performative.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownPerformative(pub String);
+
+impl fmt::Display for UnknownPerformative {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown performative \"{}\"", self.0)
+    }
+}
+
+impl std::error::Error for UnknownPerformative {}
+
+impl From<UnknownPerformative> for AgentError {
+    fn from(err: UnknownPerformative) -> Self {
+        AgentError::ExecutionFailed(err.to_string())
+    }
+}
+
+/// Parses the strings the Router delivers to `handle_message`.
+///
+/// Matching ignores case, `-` and `_`, so `"cfp"`, `"CFP"`, `"Propose"` and
+/// `"not-understood"` are all accepted.
+impl FromStr for Performative {
+    type Err = UnknownPerformative;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let normalized: String = s
+            .chars()
+            .filter(|c| *c != '-' && *c != '_')
+            .map(|c| c.to_ascii_lowercase())
+            .collect();
+        match normalized.as_str() {
+            "inform" => Ok(Performative::Inform),
+            "request" => Ok(Performative::Request),
+            "query" | "queryif" | "queryref" => Ok(Performative::Query),
+            "propose" => Ok(Performative::Propose),
+            "accept" | "acceptproposal" => Ok(Performative::Accept),
+            "reject" | "rejectproposal" => Ok(Performative::Reject),
+            "confirm" => Ok(Performative::Confirm),
+            "subscribe" => Ok(Performative::Subscribe),
+            "cfp" | "callforproposal" => Ok(Performative::Cfp),
+            "refuse" => Ok(Performative::Refuse),
+            "agree" => Ok(Performative::Agree),
+            "failure" => Ok(Performative::Failure),
+            "notunderstood" => Ok(Performative::NotUnderstood),
+            _ => Err(UnknownPerformative(s.to_string())),
+        }
+    }
+}
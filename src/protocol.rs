@@ -0,0 +1,434 @@
+//! FIPA interaction protocols: Contract Net, Request and Query-If.
+//!
+//! Each protocol has an initiator, which runs the whole exchange as one
+//! future and resolves to its outcome, and a participant trait that answers
+//! with the performatives the protocol allows. Like
+//! [`request`](crate::ContextExt::request), initiators must be started from
+//! a managed agent's handler and their futures spawned, not awaited there.
+use crate::conversation::{Reply, RequestError};
+use crate::managed::{self, Scope};
+use crate::mailbox::{MessageOptions, SendError};
+use crate::Performative;
+use agentropic_core::{Agent, AgentContext, AgentResult};
+use async_trait::async_trait;
+use std::collections::BTreeSet;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+
+/// How long each step of a protocol waits for answers unless told otherwise.
+const DEFAULT_DEADLINE: Duration = Duration::from_secs(5);
+
+/// How a Request or Query-If exchange ended.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outcome<T> {
+    Done(T),
+    /// The participant answered with [`Performative::Refuse`].
+    Refused(Reply),
+    /// The participant agreed but answered with [`Performative::Failure`].
+    Failed(Reply),
+}
+
+/// A conversation started by this agent; every reply to it arrives here
+/// until it is dropped.
+struct Dialogue {
+    scope: Arc<Scope>,
+    id: String,
+    replies: mpsc::UnboundedReceiver<Reply>,
+}
+
+impl Dialogue {
+    fn open() -> Result<Self, RequestError> {
+        let scope = managed::current().ok_or(RequestError::Unmanaged)?;
+        let (id, replies) = scope.board.conversations().open_stream(&scope.name);
+        Ok(Self { scope, id, replies })
+    }
+
+    fn send(&self, receiver: &str, performative: Performative, content: &str, options: MessageOptions) -> Result<(), SendError> {
+        let letter = self.scope.letter(performative, content, Some(self.id.clone()), None).with_options(options);
+        self.scope.board.send_letter(receiver, letter)
+    }
+
+    /// The next reply, or `None` once `deadline` passes.
+    async fn next(&mut self, deadline: Instant) -> Option<Reply> {
+        tokio::time::timeout_at(deadline, self.replies.recv()).await.ok().flatten()
+    }
+}
+
+impl Drop for Dialogue {
+    fn drop(&mut self) {
+        self.scope.board.conversations().cancel(&self.id);
+    }
+}
+
+/// What happened in a [`ContractNet`] round.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ContractNetOutcome {
+    pub proposals: Vec<Reply>,
+    pub refusals: Vec<Reply>,
+    /// Participants whose proposals were accepted.
+    pub accepted: Vec<String>,
+    /// Results reported by accepted participants.
+    pub results: Vec<Reply>,
+    pub failures: Vec<Reply>,
+    /// Participants that did not answer a step in time.
+    pub silent: Vec<String>,
+    /// Participants a message could not be delivered to.
+    pub unreachable: Vec<(String, SendError)>,
+    /// Answers the protocol does not allow at that point, including late ones.
+    pub violations: Vec<Reply>,
+}
+
+/// Initiator of the FIPA Contract Net protocol.
+///
+/// Sends a [`Performative::Cfp`] to every participant and collects
+/// proposals and refusals until all have answered or the deadline passes.
+/// The chosen proposals get [`Performative::Accept`], the rest
+/// [`Performative::Reject`]; accepted participants then report with
+/// [`Performative::Inform`] or [`Performative::Failure`].
+pub struct ContractNet {
+    task: String,
+    participants: Vec<String>,
+    topic: Option<String>,
+    deadline: Duration,
+}
+
+impl ContractNet {
+    pub fn new(task: &str) -> Self {
+        Self { task: task.to_string(), participants: Vec::new(), topic: None, deadline: DEFAULT_DEADLINE }
+    }
+
+    pub fn with_participants<S: AsRef<str>>(mut self, names: &[S]) -> Self {
+        self.participants.extend(names.iter().map(|name| name.as_ref().to_string()));
+        self
+    }
+
+    /// Also call every subscriber of `topic`.
+    pub fn with_topic(mut self, topic: &str) -> Self {
+        self.topic = Some(topic.to_string());
+        self
+    }
+
+    /// How long participants have to propose, and then to report results.
+    /// The call for proposals expires with it.
+    pub fn with_deadline(mut self, deadline: Duration) -> Self {
+        self.deadline = deadline;
+        self
+    }
+
+    /// Send the call for proposals now and run the round. `select` is given
+    /// every proposal and returns the names of the participants to accept.
+    pub fn start<F>(self, select: F) -> impl Future<Output = Result<ContractNetOutcome, RequestError>> + Send + 'static
+    where
+        F: FnOnce(&[Reply]) -> Vec<String> + Send + 'static,
+    {
+        let deadline = self.deadline;
+        let called = Dialogue::open().map(|dialogue| {
+            let mut participants = self.participants.clone();
+            if let Some(topic) = &self.topic {
+                participants.extend(dialogue.scope.board.subscribers(topic));
+            }
+            participants.retain(|name| *name != dialogue.scope.name);
+            participants.sort();
+            participants.dedup();
+
+            let mut outcome = ContractNetOutcome::default();
+            let mut waiting = BTreeSet::new();
+            let call = MessageOptions::new().with_ttl(deadline);
+            for participant in participants {
+                match dialogue.send(&participant, Performative::Cfp, &self.task, call) {
+                    Ok(()) => {
+                        waiting.insert(participant);
+                    }
+                    Err(err) => outcome.unreachable.push((participant, err)),
+                }
+            }
+            (dialogue, waiting, outcome)
+        });
+
+        async move {
+            let (mut dialogue, mut waiting, mut outcome) = called?;
+            let until = Instant::now() + deadline;
+            while !waiting.is_empty() {
+                let Some(reply) = dialogue.next(until).await else { break };
+                match reply.performative {
+                    _ if !waiting.remove(&reply.sender) => outcome.violations.push(reply),
+                    Performative::Propose => outcome.proposals.push(reply),
+                    Performative::Refuse => outcome.refusals.push(reply),
+                    _ => outcome.violations.push(reply),
+                }
+            }
+            outcome.silent.extend(std::mem::take(&mut waiting));
+
+            let winners = select(&outcome.proposals);
+            for proposal in &outcome.proposals {
+                let accepted = winners.contains(&proposal.sender);
+                let answer = if accepted { Performative::Accept } else { Performative::Reject };
+                match dialogue.send(&proposal.sender, answer, &proposal.content, MessageOptions::new()) {
+                    Ok(()) if accepted => {
+                        waiting.insert(proposal.sender.clone());
+                        outcome.accepted.push(proposal.sender.clone());
+                    }
+                    Ok(()) => {}
+                    Err(err) => outcome.unreachable.push((proposal.sender.clone(), err)),
+                }
+            }
+
+            let until = Instant::now() + deadline;
+            while !waiting.is_empty() {
+                let Some(reply) = dialogue.next(until).await else { break };
+                match reply.performative {
+                    _ if !waiting.remove(&reply.sender) => outcome.violations.push(reply),
+                    Performative::Inform => outcome.results.push(reply),
+                    Performative::Failure => outcome.failures.push(reply),
+                    _ => outcome.violations.push(reply),
+                }
+            }
+            outcome.silent.extend(waiting);
+            Ok(outcome)
+        }
+    }
+}
+
+/// Initiator of the FIPA Request protocol: ask `receiver` to carry out an
+/// action. It may [`Agree`](Performative::Agree) first, then reports with
+/// [`Performative::Inform`] or [`Performative::Failure`], or refuses outright.
+pub struct RequestAction {
+    receiver: String,
+    action: String,
+    deadline: Duration,
+}
+
+impl RequestAction {
+    pub fn new(receiver: &str, action: &str) -> Self {
+        Self { receiver: receiver.to_string(), action: action.to_string(), deadline: DEFAULT_DEADLINE }
+    }
+
+    /// How long the whole exchange may take.
+    pub fn with_deadline(mut self, deadline: Duration) -> Self {
+        self.deadline = deadline;
+        self
+    }
+
+    /// Send the request now and wait for the final answer.
+    pub fn start(self) -> impl Future<Output = Result<Outcome<Reply>, RequestError>> + Send + 'static {
+        let sent = Dialogue::open().and_then(|dialogue| {
+            dialogue.send(&self.receiver, Performative::Request, &self.action, MessageOptions::new())?;
+            Ok(dialogue)
+        });
+        async move {
+            let mut dialogue = sent?;
+            let until = Instant::now() + self.deadline;
+            let mut agreed = false;
+            loop {
+                let reply = answer_from(&mut dialogue, &self.receiver, until, self.deadline).await?;
+                match reply.performative {
+                    Performative::Agree if !agreed => agreed = true,
+                    Performative::Refuse if !agreed => return Ok(Outcome::Refused(reply)),
+                    Performative::Inform => return Ok(Outcome::Done(reply)),
+                    Performative::Failure => return Ok(Outcome::Failed(reply)),
+                    _ => return Err(RequestError::Unexpected(reply)),
+                }
+            }
+        }
+    }
+}
+
+/// Initiator of the FIPA Query-If protocol: ask `receiver` whether a
+/// proposition holds. The answer is an [`Performative::Inform`] of `true`
+/// or `false`.
+pub struct QueryIf {
+    receiver: String,
+    proposition: String,
+    deadline: Duration,
+}
+
+impl QueryIf {
+    pub fn new(receiver: &str, proposition: &str) -> Self {
+        Self { receiver: receiver.to_string(), proposition: proposition.to_string(), deadline: DEFAULT_DEADLINE }
+    }
+
+    pub fn with_deadline(mut self, deadline: Duration) -> Self {
+        self.deadline = deadline;
+        self
+    }
+
+    /// Send the query now and wait for the answer.
+    pub fn start(self) -> impl Future<Output = Result<Outcome<bool>, RequestError>> + Send + 'static {
+        let sent = Dialogue::open().and_then(|dialogue| {
+            dialogue.send(&self.receiver, Performative::Query, &self.proposition, MessageOptions::new())?;
+            Ok(dialogue)
+        });
+        async move {
+            let mut dialogue = sent?;
+            let until = Instant::now() + self.deadline;
+            let reply = answer_from(&mut dialogue, &self.receiver, until, self.deadline).await?;
+            match (reply.performative, reply.content.as_str()) {
+                (Performative::Inform, "true") => Ok(Outcome::Done(true)),
+                (Performative::Inform, "false") => Ok(Outcome::Done(false)),
+                (Performative::Refuse, _) => Ok(Outcome::Refused(reply)),
+                (Performative::Failure, _) => Ok(Outcome::Failed(reply)),
+                _ => Err(RequestError::Unexpected(reply)),
+            }
+        }
+    }
+}
+
+/// The next reply from `receiver`, skipping anyone else's.
+async fn answer_from(
+    dialogue: &mut Dialogue, receiver: &str, until: Instant, after: Duration,
+) -> Result<Reply, RequestError> {
+    loop {
+        match dialogue.next(until).await {
+            Some(reply) if reply.sender == receiver => return Ok(reply),
+            Some(_) => {}
+            None => return Err(RequestError::Timeout { receiver: receiver.to_string(), after }),
+        }
+    }
+}
+
+/// A proposal a participant made and has not yet heard back about.
+pub(crate) struct Proposal {
+    content: String,
+    /// Whether the call came in a conversation the switchboard keeps open,
+    /// so the proposal can be forgotten once the initiator closes it.
+    tracked: bool,
+}
+
+/// The conversation the message being handled belongs to.
+fn conversation_key(scope: &Scope, sender: &str) -> String {
+    let incoming = scope.incoming.lock().unwrap();
+    let id = incoming.as_ref().and_then(|incoming| incoming.conversation_id.clone());
+    id.unwrap_or_else(|| sender.to_string())
+}
+
+/// Participant in the Contract Net protocol; see [`ContractNet`].
+///
+/// Forward from `handle_message` to
+/// [`handle_contract_net`](ContractNetParticipant::handle_contract_net),
+/// which answers with the performatives the protocol allows. An accept or
+/// reject for a conversation this agent made no proposal in is answered
+/// with [`Performative::NotUnderstood`].
+#[async_trait]
+pub trait ContractNetParticipant: Agent {
+    /// Answer a call for proposals with a proposal, or `None` to refuse.
+    async fn propose(&mut self, ctx: &AgentContext, initiator: &str, task: &str) -> Option<String>;
+
+    /// Carry out an accepted proposal. `Ok` is reported with
+    /// [`Performative::Inform`], `Err` with [`Performative::Failure`].
+    async fn perform(&mut self, ctx: &AgentContext, initiator: &str, proposal: &str) -> Result<String, String>;
+
+    async fn rejected(&mut self, _ctx: &AgentContext, _initiator: &str, _proposal: &str) {}
+
+    /// Play this agent's part in a Contract Net conversation. Other
+    /// performatives are ignored.
+    async fn handle_contract_net(
+        &mut self,
+        ctx: &AgentContext,
+        sender: &str,
+        performative: &str,
+        content: &str,
+    ) -> AgentResult<()> {
+        use crate::ContextExt;
+        let scope = managed::current().ok_or(RequestError::Unmanaged)?;
+        let key = conversation_key(&scope, sender);
+        let conversations = scope.board.conversations();
+        // Initiators that gave up without answering leave proposals behind
+        scope.proposed.lock().unwrap().retain(|id, proposal| !proposal.tracked || conversations.is_open(id));
+        match performative.parse()? {
+            Performative::Cfp => match self.propose(ctx, sender, content).await {
+                Some(proposal) => {
+                    ctx.reply(Performative::Propose, &proposal)?;
+                    let tracked = conversations.is_open(&key);
+                    scope.proposed.lock().unwrap().insert(key, Proposal { content: proposal, tracked });
+                }
+                None => ctx.reply(Performative::Refuse, content)?,
+            },
+            Performative::Accept => {
+                let proposal = scope.proposed.lock().unwrap().remove(&key).map(|proposal| proposal.content);
+                let Some(proposal) = proposal else {
+                    return Ok(ctx.reply(Performative::NotUnderstood, content)?);
+                };
+                match self.perform(ctx, sender, &proposal).await {
+                    Ok(result) => ctx.reply(Performative::Inform, &result)?,
+                    Err(reason) => ctx.reply(Performative::Failure, &reason)?,
+                }
+            }
+            Performative::Reject => {
+                let proposal = scope.proposed.lock().unwrap().remove(&key).map(|proposal| proposal.content);
+                match proposal {
+                    Some(proposal) => self.rejected(ctx, sender, &proposal).await,
+                    None => ctx.reply(Performative::NotUnderstood, content)?,
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+/// Participant in the Request protocol; see [`RequestAction`].
+#[async_trait]
+pub trait RequestParticipant: Agent {
+    /// Decide whether to carry out `action`; `Err` refuses, with a reason.
+    async fn agree(&mut self, _ctx: &AgentContext, _initiator: &str, _action: &str) -> Result<(), String> {
+        Ok(())
+    }
+
+    /// Carry out an agreed action. `Ok` is reported with
+    /// [`Performative::Inform`], `Err` with [`Performative::Failure`].
+    async fn perform_request(&mut self, ctx: &AgentContext, initiator: &str, action: &str) -> Result<String, String>;
+
+    /// Answer a [`Performative::Request`] with a refusal, or with an
+    /// agreement followed by the result. Other performatives are ignored.
+    async fn handle_request(
+        &mut self,
+        ctx: &AgentContext,
+        sender: &str,
+        performative: &str,
+        content: &str,
+    ) -> AgentResult<()> {
+        use crate::ContextExt;
+        if performative.parse::<Performative>()? != Performative::Request {
+            return Ok(());
+        }
+        if let Err(reason) = self.agree(ctx, sender, content).await {
+            return Ok(ctx.reply(Performative::Refuse, &reason)?);
+        }
+        ctx.reply(Performative::Agree, content)?;
+        match self.perform_request(ctx, sender, content).await {
+            Ok(result) => ctx.reply(Performative::Inform, &result)?,
+            Err(reason) => ctx.reply(Performative::Failure, &reason)?,
+        }
+        Ok(())
+    }
+}
+
+/// Participant in the Query-If protocol; see [`QueryIf`].
+#[async_trait]
+pub trait QueryParticipant: Agent {
+    /// Whether `proposition` holds. `Err` refuses to answer, with a reason.
+    async fn query_if(&mut self, ctx: &AgentContext, initiator: &str, proposition: &str) -> Result<bool, String>;
+
+    /// Answer a [`Performative::Query`] with `true` or `false`. Other
+    /// performatives are ignored.
+    async fn handle_query_if(
+        &mut self,
+        ctx: &AgentContext,
+        sender: &str,
+        performative: &str,
+        content: &str,
+    ) -> AgentResult<()> {
+        use crate::ContextExt;
+        if performative.parse::<Performative>()? != Performative::Query {
+            return Ok(());
+        }
+        match self.query_if(ctx, sender, content).await {
+            Ok(holds) => ctx.reply(Performative::Inform, &holds.to_string())?,
+            Err(reason) => ctx.reply(Performative::Refuse, &reason)?,
+        }
+        Ok(())
+    }
+}
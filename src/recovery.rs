@@ -0,0 +1,173 @@
+//! Restarting failed agents with backoff, and parking them when they keep failing.
+use crate::simulation;
+use std::collections::hash_map::RandomState;
+use std::collections::VecDeque;
+use std::fmt;
+use std::hash::BuildHasher;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// The topic circuit breakers report on.
+///
+/// When an agent's circuit opens it publishes a [`Performative::Failure`](crate::Performative::Failure)
+/// here with the error that tripped it, and a [`Performative::Inform`](crate::Performative::Inform)
+/// once it has recovered; subscribe to hear about either.
+pub const CIRCUIT_TOPIC: &str = "circuit-breaker";
+
+/// Whether a managed agent with a [`Recovery`] policy is restarted when it fails.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Circuit {
+    /// Failures are met with a restart after a backoff.
+    Closed,
+    /// Too many failures: the agent is parked and messages to it are refused.
+    Open,
+    /// The agent has been restarted once after being parked; the next
+    /// failure opens the circuit again, a successful `execute` closes it.
+    HalfOpen,
+}
+
+impl fmt::Display for Circuit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Circuit::Closed => f.write_str("closed"),
+            Circuit::Open => f.write_str("open"),
+            Circuit::HalfOpen => f.write_str("half-open"),
+        }
+    }
+}
+
+/// How the switchboard restarts a managed agent whose `execute` or
+/// `initialize` fails, in place of a runtime
+/// [`RestartPolicy`](agentropic_runtime::prelude::RestartPolicy); set it with
+/// [`SpawnOptions::with_recovery`](crate::SpawnOptions::with_recovery).
+///
+/// Each restart waits twice as long as the one before, up to a maximum,
+/// minus a random share of the delay so agents that failed together do not
+/// all come back at once. Only failures within the failure window count, so
+/// the delay shrinks back once an agent stays up. When the window holds
+/// `max_failures` failures the circuit [opens](Circuit::Open): the agent is
+/// parked rather than restarted, the other agents hear about it on
+/// [`CIRCUIT_TOPIC`], and it is tried again once the cooldown has passed
+/// or [`Switchboard::reset_circuit`](crate::Switchboard::reset_circuit) is called.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Recovery {
+    initial_delay: Duration,
+    max_delay: Duration,
+    jitter: f64,
+    max_failures: u32,
+    window: Duration,
+    cooldown: Option<Duration>,
+}
+
+impl Recovery {
+    /// Back off from 100ms to at most 10s with 20% jitter, and open the
+    /// circuit after 5 failures within a minute, with no cooldown.
+    pub fn new() -> Self {
+        Self {
+            initial_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(10),
+            jitter: 0.2,
+            max_failures: 5,
+            window: Duration::from_secs(60),
+            cooldown: None,
+        }
+    }
+
+    /// Wait `initial` before the first restart, doubling up to `max`.
+    pub fn with_backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.initial_delay = initial;
+        self.max_delay = max.max(initial);
+        self
+    }
+
+    /// Take up to this share of each delay off at random; clamped to 0..=1.
+    pub fn with_jitter(mut self, jitter: f64) -> Self {
+        self.jitter = jitter.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Open the circuit once `max_failures` failures happen within `window`.
+    pub fn with_failure_window(mut self, max_failures: u32, window: Duration) -> Self {
+        self.max_failures = max_failures.max(1);
+        self.window = window;
+        self
+    }
+
+    /// Try a parked agent again `cooldown` after its circuit opened.
+    pub fn with_cooldown(mut self, cooldown: Duration) -> Self {
+        self.cooldown = Some(cooldown);
+        self
+    }
+
+    pub fn cooldown(&self) -> Option<Duration> {
+        self.cooldown
+    }
+
+    /// The delay before restart number `attempt`, counting from 1, before jitter.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let doublings = attempt.saturating_sub(1).min(31);
+        self.initial_delay.saturating_mul(1 << doublings).min(self.max_delay)
+    }
+
+    fn jittered(&self, delay: Duration) -> Duration {
+        let random = simulation::random().unwrap_or_else(|| RandomState::new().hash_one(Instant::now()));
+        let share = self.jitter * (random as f64 / u64::MAX as f64);
+        delay.mul_f64(1.0 - share)
+    }
+}
+
+impl Default for Recovery {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// What happens after a failure under a [`Recovery`] policy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Decision {
+    /// Restart after this delay.
+    Restart(Duration),
+    /// Park the agent, and try it again after the cooldown, if any.
+    Open(Option<Duration>),
+}
+
+/// A [`Recovery`] policy and the failures it has seen.
+pub(crate) struct Recovering {
+    policy: Recovery,
+    failures: VecDeque<Instant>,
+    /// When the agent is due to be initialized again, once it has failed.
+    pub restart_at: Option<Instant>,
+    /// Set while the agent waits to be restarted.
+    pub pending: bool,
+}
+
+impl Recovering {
+    pub fn new(policy: Recovery) -> Self {
+        Self { policy, failures: VecDeque::new(), restart_at: None, pending: false }
+    }
+
+    /// Count a failure while the circuit is `circuit` and decide what follows.
+    pub fn failed(&mut self, circuit: Circuit) -> Decision {
+        let now = Instant::now();
+        let window = self.policy.window;
+        self.failures.retain(|at| now.duration_since(*at) < window);
+        self.failures.push_back(now);
+        self.pending = true;
+        let attempts = self.failures.len() as u32;
+        let decision = if circuit == Circuit::HalfOpen || attempts >= self.policy.max_failures {
+            Decision::Open(self.policy.cooldown)
+        } else {
+            Decision::Restart(self.policy.jittered(self.policy.backoff(attempts)))
+        };
+        self.restart_at = match decision {
+            Decision::Restart(delay) => Some(now + delay),
+            Decision::Open(cooldown) => cooldown.map(|cooldown| now + cooldown),
+        };
+        decision
+    }
+
+    /// Forget past failures once the agent has recovered.
+    pub fn recovered(&mut self) {
+        self.failures.clear();
+    }
+}
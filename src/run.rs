@@ -0,0 +1,102 @@
+//! Waiting on a [`Runtime`] until its agents are done instead of sleeping.
+use crate::lifecycle::Events;
+use crate::{ExitStatus, Switchboard};
+use agentropic_core::AgentError;
+use agentropic_runtime::prelude::*;
+use std::fmt;
+use std::future::Future;
+use std::time::Duration;
+
+/// How often conditions are checked.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// How long the switchboard must stay idle before it counts as quiescent.
+const SETTLE: Duration = Duration::from_millis(100);
+
+/// The condition passed to [`RuntimeExt::run_until`] did not hold in time.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RunTimeout {
+    pub waited: Duration,
+    pub condition: &'static str,
+}
+
+impl fmt::Display for RunTimeout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "agents were not {} after {:?}", self.condition, self.waited)
+    }
+}
+
+impl std::error::Error for RunTimeout {}
+
+impl From<RunTimeout> for AgentError {
+    fn from(err: RunTimeout) -> Self {
+        AgentError::ExecutionFailed(err.to_string())
+    }
+}
+
+/// Lets agents run until something happens rather than for a fixed time.
+pub trait RuntimeExt {
+    /// Resolve once `done` returns true, or fail after `timeout`.
+    fn run_until<F>(&self, done: F, timeout: Duration) -> impl Future<Output = Result<(), RunTimeout>> + Send
+    where
+        F: FnMut() -> bool + Send;
+
+    /// Resolve once `board` has stayed idle for a short settling period;
+    /// see [`Switchboard::is_idle`]. Agents that do work from `execute`
+    /// rather than in response to messages should use
+    /// [`set_busy`](crate::ContextExt::set_busy) until they are finished.
+    fn run_until_idle(&self, board: &Switchboard, timeout: Duration) -> impl Future<Output = Result<(), RunTimeout>> + Send;
+
+    /// Wait for the agent spawned as `name` on `board` to exit; see
+    /// [`Switchboard::join`].
+    fn join(&self, board: &Switchboard, name: &str) -> impl Future<Output = Option<ExitStatus>> + Send;
+
+    /// Lifecycle events of the agents spawned on `board` from now on; see
+    /// [`Switchboard::events`].
+    fn events(&self, board: &Switchboard) -> Events;
+}
+
+impl RuntimeExt for Runtime {
+    async fn run_until<F>(&self, done: F, timeout: Duration) -> Result<(), RunTimeout>
+    where
+        F: FnMut() -> bool + Send,
+    {
+        wait(done, Duration::ZERO, timeout, "done").await
+    }
+
+    async fn run_until_idle(&self, board: &Switchboard, timeout: Duration) -> Result<(), RunTimeout> {
+        wait(|| board.is_idle(), SETTLE, timeout, "idle").await
+    }
+
+    async fn join(&self, board: &Switchboard, name: &str) -> Option<ExitStatus> {
+        board.join(name).await
+    }
+
+    fn events(&self, board: &Switchboard) -> Events {
+        board.events()
+    }
+}
+
+/// Poll `done` until it has held for `settle`, giving up after `timeout`.
+async fn wait<F>(mut done: F, settle: Duration, timeout: Duration, condition: &'static str) -> Result<(), RunTimeout>
+where
+    F: FnMut() -> bool + Send,
+{
+    let started = tokio::time::Instant::now();
+    let mut since = None;
+    loop {
+        let now = tokio::time::Instant::now();
+        if done() {
+            let held_from = *since.get_or_insert(now);
+            if now - held_from >= settle {
+                return Ok(());
+            }
+        } else {
+            since = None;
+        }
+        if now - started >= timeout {
+            return Err(RunTimeout { waited: timeout, condition });
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
@@ -0,0 +1,125 @@
+//! Stopping a switchboard's agents in phases instead of all at once.
+use crate::Switchboard;
+use agentropic_runtime::prelude::*;
+use std::collections::{BTreeMap, BTreeSet};
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// How often the drain phase checks whether the switchboard is drained.
+const DRAIN_POLL: Duration = Duration::from_millis(10);
+
+/// Extra time an agent gets, past its shutdown timeout, to get to its
+/// shutdown at all; one still in `execute` after that is killed.
+const STOP_GRACE: Duration = Duration::from_secs(1);
+
+/// How [`Switchboard::shutdown`] winds its agents down.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Shutdown {
+    drain_deadline: Duration,
+    stop_timeout: Duration,
+}
+
+impl Shutdown {
+    /// Drain for up to 5 seconds and give each agent's `shutdown` 2 seconds.
+    pub fn new() -> Self {
+        Self { drain_deadline: Duration::from_secs(5), stop_timeout: Duration::from_secs(2) }
+    }
+
+    /// Stop waiting for queued messages to be handled after `deadline`.
+    pub fn with_drain_deadline(mut self, deadline: Duration) -> Self {
+        self.drain_deadline = deadline;
+        self
+    }
+
+    /// Give up on an agent's `shutdown` after `timeout`.
+    pub fn with_stop_timeout(mut self, timeout: Duration) -> Self {
+        self.stop_timeout = timeout;
+        self
+    }
+}
+
+impl Default for Shutdown {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// What happened during a [`Switchboard::shutdown`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ShutdownReport {
+    /// Whether every message was handled before the drain deadline.
+    pub drained: bool,
+    /// Agents shut down in turn, in the order they were stopped.
+    pub stopped: Vec<String>,
+    /// Agents whose `shutdown` ran past the stop timeout and was abandoned.
+    pub timed_out: Vec<String>,
+    /// Agents that never got to their `shutdown` and were stopped by the runtime.
+    pub force_killed: Vec<String>,
+}
+
+impl ShutdownReport {
+    /// Whether every agent shut down in time.
+    pub fn is_clean(&self) -> bool {
+        self.drained && self.timed_out.is_empty() && self.force_killed.is_empty()
+    }
+}
+
+/// Group `agents` into the order they stop in: each group only once no
+/// agent still running depends on any of its members. Agents caught in a
+/// dependency cycle stop together at the end.
+pub(crate) fn stop_order(agents: &[String], dependencies: &BTreeMap<String, BTreeSet<String>>) -> Vec<Vec<String>> {
+    let mut running: BTreeSet<&String> = agents.iter().collect();
+    let mut stages = Vec::new();
+    while !running.is_empty() {
+        let needed = |agent: &String| {
+            running.iter().any(|other| dependencies.get(*other).is_some_and(|deps| deps.contains(agent)))
+        };
+        let ready = running.iter().filter(|agent| !needed(agent));
+        let mut stage: Vec<String> = ready.map(|agent| agent.to_string()).collect();
+        if stage.is_empty() {
+            stage = running.iter().map(|agent| agent.to_string()).collect();
+        }
+        for agent in &stage {
+            running.remove(agent);
+        }
+        stages.push(stage);
+    }
+    stages
+}
+
+/// Run the phases of a graceful shutdown; see [`Switchboard::shutdown`].
+pub(crate) async fn run(
+    board: &Switchboard, runtime: &Runtime, shutdown: Shutdown,
+) -> Result<ShutdownReport, RuntimeError> {
+    let mut report = ShutdownReport::default();
+    board.close();
+
+    let deadline = Instant::now() + shutdown.drain_deadline;
+    report.drained = loop {
+        if board.is_drained() {
+            break true;
+        }
+        if Instant::now() >= deadline {
+            break false;
+        }
+        tokio::time::sleep(DRAIN_POLL).await;
+    };
+
+    for stage in stop_order(&board.running(), &board.dependencies()) {
+        for agent in &stage {
+            board.stop(agent, shutdown.stop_timeout);
+        }
+        let deadline = Instant::now() + shutdown.stop_timeout + STOP_GRACE;
+        for agent in stage {
+            let exited = tokio::time::timeout_at(deadline, board.join(&agent)).await;
+            match exited {
+                Ok(_) if board.shutdown_overran(&agent) => report.timed_out.push(agent),
+                Ok(_) => report.stopped.push(agent),
+                Err(_) => report.force_killed.push(agent),
+            }
+        }
+    }
+
+    runtime.shutdown().await?;
+    Ok(report)
+}
@@ -0,0 +1,122 @@
+//! Deterministic simulated time for running scenarios as tests.
+//!
+//! `Simulation` needs tokio's `test-util` and is only built with the
+//! `simulation` feature, which the crate's own tests enable.
+use std::cell::RefCell;
+#[cfg(feature = "simulation")]
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+
+thread_local! {
+    static TRACE: RefCell<Option<Trace>> = const { RefCell::new(None) };
+}
+
+/// Runs a scenario on a virtual clock.
+///
+/// The scenario runs on a single-threaded tokio runtime with time paused:
+/// whenever every task is waiting on a timer, the clock jumps straight to the
+/// next deadline. Agent ticks, `sleep`s and supervisor backoff all run on
+/// that clock, so a scenario that "waits" two minutes finishes in
+/// milliseconds. With one thread and no wall-clock input, tasks are polled in
+/// the same order on every run and the [`Trace`] is byte-identical.
+///
+/// The seed does not change that order. It only seeds [`random`], which
+/// [`Recovery`](crate::Recovery) uses to jitter its backoff, so runs with
+/// the same seed also back off by the same amounts.
+#[cfg(feature = "simulation")]
+pub struct Simulation {
+    seed: u64,
+}
+
+#[cfg(feature = "simulation")]
+impl Simulation {
+    pub fn new(seed: u64) -> Self {
+        Self { seed }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Drive `scenario` to completion and return its output with the trace
+    /// of every message delivered to a managed agent.
+    pub fn run<F: Future>(&self, scenario: F) -> (F::Output, Trace) {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .start_paused(true)
+            .build()
+            .expect("failed to build simulation runtime");
+
+        runtime.block_on(async {
+            let trace = Trace::start(self.seed);
+            TRACE.with(|t| *t.borrow_mut() = Some(trace.clone()));
+            let output = scenario.await;
+            TRACE.with(|t| *t.borrow_mut() = None);
+            (output, trace)
+        })
+    }
+}
+
+/// Ordered record of message deliveries, stamped with virtual time.
+#[derive(Clone)]
+pub struct Trace {
+    started: tokio::time::Instant,
+    rng: Arc<Mutex<SimRng>>,
+    lines: Arc<Mutex<Vec<String>>>,
+}
+
+impl Trace {
+    #[cfg(feature = "simulation")]
+    fn start(seed: u64) -> Self {
+        Self {
+            started: tokio::time::Instant::now(),
+            rng: Arc::new(Mutex::new(SimRng::new(seed))),
+            lines: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    pub fn lines(&self) -> Vec<String> {
+        self.lines.lock().unwrap().clone()
+    }
+
+    fn record(&self, line: String) {
+        let elapsed = self.started.elapsed();
+        let stamped = format!("{:>5}.{:03}s {}", elapsed.as_secs(), elapsed.subsec_millis(), line);
+        self.lines.lock().unwrap().push(stamped);
+    }
+}
+
+/// Record a delivery if a simulation is running on this thread.
+pub(crate) fn record_delivery(receiver: &str, sender: &str, performative: &str, content: &str) {
+    TRACE.with(|t| {
+        if let Some(trace) = t.borrow().as_ref() {
+            trace.record(format!("{} -> {} [{}] {}", sender, receiver, performative, content));
+        }
+    });
+}
+
+/// A random number from the running simulation's seeded generator, or
+/// `None` outside a simulation.
+pub fn random() -> Option<u64> {
+    TRACE.with(|t| t.borrow().as_ref().map(|trace| trace.rng.lock().unwrap().next_u64()))
+}
+
+/// Small seeded generator (SplitMix64); good enough for jitter and tie-breaks.
+#[derive(Debug, Clone)]
+pub struct SimRng {
+    state: u64,
+}
+
+impl SimRng {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+}
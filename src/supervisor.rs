@@ -0,0 +1,209 @@
+//! Supervision trees for managed agents.
+use crate::SpawnOptions;
+use agentropic_core::Agent;
+use std::collections::{BTreeMap, VecDeque};
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Which children a [`Supervisor`] restarts when one of them fails.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Strategy {
+    /// Only the child that failed.
+    OneForOne,
+    /// Every child.
+    OneForAll,
+    /// The child that failed and every child added after it.
+    RestForOne,
+}
+
+/// A node in a supervision tree; spawn one with
+/// [`Switchboard::spawn_supervisor`](crate::Switchboard::spawn_supervisor).
+///
+/// Restarting an agent shuts it down (unless it is the one that failed) and
+/// runs `initialize` again on the same instance, as a runtime
+/// [`RestartPolicy`](agentropic_runtime::prelude::RestartPolicy) does.
+/// Messages already queued for it are kept. A child supervisor is restarted
+/// by restarting everything under it.
+///
+/// If more than `max_restarts` restarts are needed within the intensity
+/// window, the supervisor gives up and fails in turn, which its parent
+/// handles like any other failed child. A top-level supervisor that gives
+/// up stops all of its agents, which exit with
+/// [`ExitStatus::Failed`](crate::ExitStatus::Failed).
+pub struct Supervisor {
+    name: String,
+    strategy: Strategy,
+    max_restarts: u32,
+    within: Duration,
+    children: Vec<Child>,
+}
+
+enum Child {
+    Agent { name: String, agent: Box<dyn Agent>, options: Box<SpawnOptions> },
+    Supervisor(Supervisor),
+}
+
+impl Supervisor {
+    /// A supervisor allowing 3 restarts within 5 seconds.
+    pub fn new(name: &str, strategy: Strategy) -> Self {
+        Self { name: name.to_string(), strategy, max_restarts: 3, within: Duration::from_secs(5), children: Vec::new() }
+    }
+
+    /// Give up once more than `max_restarts` are needed within `within`.
+    pub fn with_intensity(mut self, max_restarts: u32, within: Duration) -> Self {
+        self.max_restarts = max_restarts;
+        self.within = within;
+        self
+    }
+
+    /// Supervise `agent`, spawned as `name`.
+    pub fn child(self, name: &str, agent: Box<dyn Agent>) -> Self {
+        self.child_with(name, agent, SpawnOptions::new())
+    }
+
+    /// Supervise `agent` with explicit [`SpawnOptions`]. Any restart policy
+    /// in `options` is ignored; the supervisor decides when to restart.
+    pub fn child_with(mut self, name: &str, agent: Box<dyn Agent>, options: SpawnOptions) -> Self {
+        self.children.push(Child::Agent { name: name.to_string(), agent, options: Box::new(options) });
+        self
+    }
+
+    /// Nest `supervisor` under this one.
+    pub fn supervisor(mut self, supervisor: Supervisor) -> Self {
+        self.children.push(Child::Supervisor(supervisor));
+        self
+    }
+}
+
+/// What a supervisor asks of one of its agents.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Command {
+    Restart,
+    /// Shut down for good, failing with this reason.
+    Terminate(String),
+    /// Shut down for good as part of a graceful shutdown, giving the
+    /// agent's `shutdown` this long.
+    Stop(Duration),
+    /// Hand over to the implementation waiting in
+    /// [`Switchboard::replace`](crate::Switchboard::replace).
+    Replace,
+}
+
+#[derive(Clone, Copy)]
+enum Member {
+    Agent(usize),
+    Supervisor(usize),
+}
+
+struct Node {
+    name: String,
+    strategy: Strategy,
+    max_restarts: u32,
+    within: Duration,
+    parent: Option<(usize, usize)>,
+    children: Vec<Member>,
+    restarts: VecDeque<Instant>,
+}
+
+/// Every supervision tree spawned on a switchboard.
+#[derive(Default)]
+pub(crate) struct Trees {
+    nodes: Vec<Node>,
+    agents: Vec<String>,
+    /// The node supervising each agent, and the agent's position in it.
+    placement: BTreeMap<String, (usize, usize)>,
+}
+
+impl Trees {
+    /// Record `supervisor` and return the agents to spawn for it.
+    pub fn add(&mut self, supervisor: Supervisor) -> Vec<(String, Box<dyn Agent>, SpawnOptions)> {
+        let mut spawns = Vec::new();
+        self.insert(supervisor, None, &mut spawns);
+        spawns
+    }
+
+    fn insert(
+        &mut self, supervisor: Supervisor, parent: Option<(usize, usize)>,
+        spawns: &mut Vec<(String, Box<dyn Agent>, SpawnOptions)>,
+    ) -> usize {
+        let node = self.nodes.len();
+        self.nodes.push(Node {
+            name: supervisor.name,
+            strategy: supervisor.strategy,
+            max_restarts: supervisor.max_restarts,
+            within: supervisor.within,
+            parent,
+            children: Vec::new(),
+            restarts: VecDeque::new(),
+        });
+        for (position, child) in supervisor.children.into_iter().enumerate() {
+            let member = match child {
+                Child::Agent { name, agent, options } => {
+                    self.placement.insert(name.clone(), (node, position));
+                    self.agents.push(name.clone());
+                    spawns.push((name, agent, *options));
+                    Member::Agent(self.agents.len() - 1)
+                }
+                Child::Supervisor(child) => Member::Supervisor(self.insert(child, Some((node, position)), spawns)),
+            };
+            self.nodes[node].children.push(member);
+        }
+        node
+    }
+
+    pub fn supervises(&self, agent: &str) -> bool {
+        self.placement.contains_key(agent)
+    }
+
+    /// Decide what happens after `agent` failed: which agents to restart,
+    /// or to terminate if the top-level supervisor gave up.
+    pub fn failed(&mut self, agent: &str) -> Vec<(String, Command)> {
+        match self.placement.get(agent) {
+            Some(&(node, position)) => self.handle(node, position, format!("\"{}\" failed", agent)),
+            None => Vec::new(),
+        }
+    }
+
+    fn handle(&mut self, node: usize, position: usize, cause: String) -> Vec<(String, Command)> {
+        let now = Instant::now();
+        let supervisor = &mut self.nodes[node];
+        let within = supervisor.within;
+        supervisor.restarts.retain(|at| now.duration_since(*at) < within);
+        if supervisor.restarts.len() >= supervisor.max_restarts as usize {
+            let restarts = supervisor.restarts.len();
+            let cause = format!("supervisor \"{}\" gave up after {} restarts: {}", supervisor.name, restarts, cause);
+            return match supervisor.parent {
+                Some((parent, position)) => self.handle(parent, position, cause),
+                None => {
+                    let agents = self.agents_under(Member::Supervisor(node));
+                    agents.into_iter().map(|agent| (agent, Command::Terminate(cause.clone()))).collect()
+                }
+            };
+        }
+        supervisor.restarts.push_back(now);
+        let affected = match supervisor.strategy {
+            Strategy::OneForOne => supervisor.children[position..=position].to_vec(),
+            Strategy::OneForAll => supervisor.children.clone(),
+            Strategy::RestForOne => supervisor.children[position..].to_vec(),
+        };
+        let mut restarts = Vec::new();
+        for member in affected {
+            if let Member::Supervisor(child) = member {
+                // A restarted supervisor starts counting afresh
+                self.nodes[child].restarts.clear();
+            }
+            restarts.extend(self.agents_under(member).into_iter().map(|agent| (agent, Command::Restart)));
+        }
+        restarts
+    }
+
+    /// Every agent in the subtree rooted at `member`, in the order added.
+    fn agents_under(&self, member: Member) -> Vec<String> {
+        match member {
+            Member::Agent(agent) => vec![self.agents[agent].clone()],
+            Member::Supervisor(node) => {
+                self.nodes[node].children.iter().flat_map(|child| self.agents_under(*child)).collect()
+            }
+        }
+    }
+}
@@ -0,0 +1,466 @@
+//! Spawns managed agents and tracks the state they share.
+use crate::budget::{Budget, Meter, Usage};
+use crate::checkpoint::RestartState;
+use crate::conversation::Conversations;
+use crate::lifecycle::{Announcer, ExitStatus, Lifecycle};
+use crate::mailbox::{DeadLetter, Inbox, MailboxConfig, MailboxStats};
+use crate::managed::Managed;
+use crate::middleware::Middleware;
+use crate::recovery::{Circuit, Recovery};
+use crate::supervisor::{Command, Supervisor, Trees};
+use crate::timeout::{Cancellation, Timeouts};
+use agentropic_core::{Agent, AgentId};
+use agentropic_runtime::prelude::*;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::Notify;
+
+mod capabilities;
+mod children;
+mod dead_letters;
+mod lifecycle;
+mod routing;
+mod screening;
+mod stopping;
+mod supervision;
+mod topics;
+
+pub use topics::Membership;
+
+/// How long an agent stopped by another agent, or by its parent exiting,
+/// gets for its `shutdown`.
+const STOP_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How [`Switchboard::spawn_with`] runs an agent.
+pub struct SpawnOptions {
+    policy: Option<RestartPolicy>,
+    reactive: bool,
+    unenveloped: bool,
+    mailbox: MailboxConfig,
+    restart_state: RestartState,
+    recovery: Option<Recovery>,
+    budget: Budget,
+    timeouts: Timeouts,
+    dependencies: Vec<String>,
+}
+
+impl SpawnOptions {
+    pub fn new() -> Self {
+        Self {
+            policy: None,
+            reactive: false,
+            unenveloped: false,
+            mailbox: MailboxConfig::unbounded(),
+            restart_state: RestartState::default(),
+            recovery: None,
+            budget: Budget::new(),
+            timeouts: Timeouts::new(),
+            dependencies: Vec::new(),
+        }
+    }
+
+    /// Restart the agent under a supervisor.
+    pub fn with_policy(mut self, policy: RestartPolicy) -> Self {
+        self.policy = Some(policy);
+        self
+    }
+
+    /// Make the agent event-driven.
+    ///
+    /// Its `execute` runs once after `initialize` and then only after
+    /// [`Switchboard::wake`]; in between the agent is parked until a message
+    /// or timer arrives, so passive agents can leave `execute` empty.
+    pub fn reactive(mut self) -> Self {
+        self.reactive = true;
+        self
+    }
+
+    /// Match the agent's replies to requests by who sent them, oldest request
+    /// first, as for agents outside the switchboard. For agents like
+    /// `CognitiveAgent` that answer with `send_message` rather than
+    /// [`reply`](crate::ContextExt::reply), so their replies carry no
+    /// conversation id.
+    pub fn replies_without_envelope(mut self) -> Self {
+        self.unenveloped = true;
+        self
+    }
+
+    /// Restart the agent with backoff and a circuit breaker instead of a
+    /// runtime policy; see [`Recovery`]. Takes the place of any policy set
+    /// with [`with_policy`](SpawnOptions::with_policy), and is ignored under a
+    /// [`Supervisor`].
+    pub fn with_recovery(mut self, recovery: Recovery) -> Self {
+        self.recovery = Some(recovery);
+        self
+    }
+
+    /// Hold the agent to `budget`; see [`Budget`].
+    pub fn with_budget(mut self, budget: Budget) -> Self {
+        self.budget = budget;
+        self
+    }
+
+    /// Fail the agent's calls that run too long; see [`Timeouts`].
+    pub fn with_timeouts(mut self, timeouts: Timeouts) -> Self {
+        self.timeouts = timeouts;
+        self
+    }
+
+    /// Whether a [`Checkpointed`](crate::Checkpointed) agent comes back from
+    /// a restart with its last checkpoint, the default, or as it was spawned.
+    pub fn with_restart_state(mut self, restart_state: RestartState) -> Self {
+        self.restart_state = restart_state;
+        self
+    }
+
+    /// Keep the agent spawned as `name` running until this one has shut
+    /// down, in a graceful [`shutdown`](Switchboard::shutdown).
+    pub fn depends_on(mut self, name: &str) -> Self {
+        self.dependencies.push(name.to_string());
+        self
+    }
+
+    /// Limit how many messages from other managed agents can queue up.
+    pub fn with_mailbox(mut self, mailbox: MailboxConfig) -> Self {
+        self.mailbox = mailbox;
+        self
+    }
+}
+
+impl Default for SpawnOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Why an agent could not be spawned at run time.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SpawnError {
+    /// The spawning agent was not spawned through a switchboard.
+    Unmanaged,
+    /// The parent was never spawned through this switchboard.
+    UnknownParent(String),
+    /// A managed agent is already running under this name.
+    NameTaken(String),
+    /// The switchboard is shutting down and spawns nothing new.
+    ShuttingDown,
+    /// The runtime refused the agent, for this reason.
+    Runtime(String),
+}
+
+impl fmt::Display for SpawnError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SpawnError::Unmanaged => f.write_str("agent is not managed by a switchboard"),
+            SpawnError::UnknownParent(parent) => write!(f, "no agent named \"{}\" to be the parent", parent),
+            SpawnError::NameTaken(name) => write!(f, "an agent named \"{}\" is already running", name),
+            SpawnError::ShuttingDown => f.write_str("the switchboard is shutting down"),
+            SpawnError::Runtime(reason) => write!(f, "the runtime could not spawn the agent: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for SpawnError {}
+
+/// Shared hub for agents spawned through it.
+///
+/// Agents spawned with [`spawn`](Switchboard::spawn) can use the
+/// conversation, broadcast and topic methods of [`ContextExt`](crate::ContextExt).
+/// Cloning is cheap; every clone refers to the same state.
+#[derive(Clone, Default)]
+pub struct Switchboard {
+    roster: Arc<Mutex<BTreeMap<String, AgentId>>>,
+    inboxes: Arc<Mutex<BTreeMap<String, Arc<Inbox>>>>,
+    meters: Arc<Mutex<BTreeMap<String, Arc<Meter>>>>,
+    /// Cancellation of the call each agent is in, or last made.
+    cancellations: Arc<Mutex<BTreeMap<String, Cancellation>>>,
+    /// Managed agents whose replies carry no envelope; see
+    /// [`SpawnOptions::replies_without_envelope`].
+    unenveloped: Arc<Mutex<BTreeSet<String>>>,
+    /// Agents spawned directly on the runtime that managed agents may address,
+    /// with their ids when they were spawned through the switchboard.
+    externals: Arc<Mutex<BTreeMap<String, Option<AgentId>>>>,
+    topics: Arc<Mutex<BTreeMap<String, BTreeSet<String>>>>,
+    groups: Arc<Mutex<BTreeMap<String, Vec<AgentId>>>>,
+    /// Agents offering each capability, for [`find_agents`](Switchboard::find_agents).
+    directory: Arc<Mutex<BTreeMap<String, BTreeSet<String>>>>,
+    /// Hierarchy rank of each agent placed with [`set_level`](Switchboard::set_level).
+    ranks: Arc<Mutex<BTreeMap<String, u32>>>,
+    conversations: Conversations,
+    /// Messages between managed agents that have been sent but not yet handled.
+    in_flight: Arc<AtomicUsize>,
+    /// Agents that have not finished their first `execute` yet.
+    starting: Arc<Mutex<BTreeSet<String>>>,
+    busy: Arc<Mutex<BTreeSet<String>>>,
+    exits: Arc<Mutex<BTreeMap<String, ExitStatus>>>,
+    exited: Arc<Notify>,
+    dead_letters: Arc<Mutex<VecDeque<DeadLetter>>>,
+    dead_letter_agent: Arc<Mutex<Option<String>>>,
+    middleware: Arc<Mutex<Vec<Arc<dyn Middleware>>>>,
+    trees: Arc<Mutex<Trees>>,
+    /// Supervisor decisions waiting for each agent's next `execute`.
+    commands: Arc<Mutex<BTreeMap<String, Command>>>,
+    /// Circuit of each agent spawned with a [`Recovery`] policy.
+    circuits: Arc<Mutex<BTreeMap<String, Circuit>>>,
+    announcer: Announcer,
+    /// Agents each agent needs running until it has shut down.
+    dependencies: Arc<Mutex<BTreeMap<String, BTreeSet<String>>>>,
+    /// Set once a graceful shutdown has begun.
+    closing: Arc<AtomicBool>,
+    /// Agents whose `shutdown` ran past its timeout during a graceful shutdown.
+    overran: Arc<Mutex<BTreeSet<String>>>,
+    /// The runtime agents were spawned on, for agents that spawn others.
+    runtime: Arc<Mutex<Option<Runtime>>>,
+    /// The agent that spawned each child with [`spawn_child`](Switchboard::spawn_child).
+    parents: Arc<Mutex<BTreeMap<String, String>>>,
+    /// Implementations waiting to take over from the running agent of each name.
+    replacements: Arc<Mutex<BTreeMap<String, Box<dyn Agent>>>>,
+}
+
+impl Switchboard {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Spawn `agent` on `runtime` as `name`, managed by this switchboard.
+    /// Returns the agent's id, which [`name_of`](Switchboard::name_of) maps back to `name`.
+    pub async fn spawn(
+        &self, runtime: &Runtime, agent: Box<dyn Agent>, name: &str,
+    ) -> Result<AgentId, RuntimeError> {
+        self.spawn_with(runtime, agent, name, SpawnOptions::new()).await
+    }
+
+    /// Spawn an event-driven agent; see [`SpawnOptions::reactive`].
+    pub async fn spawn_reactive(
+        &self, runtime: &Runtime, agent: Box<dyn Agent>, name: &str,
+    ) -> Result<AgentId, RuntimeError> {
+        self.spawn_with(runtime, agent, name, SpawnOptions::new().reactive()).await
+    }
+
+    /// Like [`spawn`](Switchboard::spawn), with a supervisor restart policy.
+    pub async fn spawn_with_policy(
+        &self, runtime: &Runtime, agent: Box<dyn Agent>, name: &str, policy: RestartPolicy,
+    ) -> Result<AgentId, RuntimeError> {
+        self.spawn_with(runtime, agent, name, SpawnOptions::new().with_policy(policy)).await
+    }
+
+    /// Spawn `agent` as `name` with explicit [`SpawnOptions`].
+    pub async fn spawn_with(
+        &self, runtime: &Runtime, agent: Box<dyn Agent>, name: &str, mut options: SpawnOptions,
+    ) -> Result<AgentId, RuntimeError> {
+        let id = *agent.id();
+        self.runtime.lock().unwrap().get_or_insert_with(|| runtime.clone());
+        if options.recovery.is_some() {
+            options.policy = None;
+        }
+        let mut managed = self.manage(agent, name, &options);
+        if options.reactive {
+            managed = managed.reactive();
+        }
+        if options.unenveloped {
+            self.unenveloped.lock().unwrap().insert(name.to_string());
+        }
+        if let Some(recovery) = options.recovery {
+            self.set_circuit(name, Circuit::Closed);
+            managed = managed.recovering(recovery);
+        }
+        managed = managed.budgeted(options.budget).timed(options.timeouts);
+        if !options.dependencies.is_empty() {
+            let mut dependencies = self.dependencies.lock().unwrap();
+            dependencies.entry(name.to_string()).or_default().extend(options.dependencies);
+        }
+        let spawned = match options.policy {
+            Some(policy) => runtime.spawn_with_policy(Box::new(managed), name, policy).await,
+            None => runtime.spawn(Box::new(managed), name).await,
+        };
+        if let Err(err) = spawned {
+            self.forget(name);
+            return Err(err);
+        }
+        self.announce(name, Lifecycle::Spawned);
+        Ok(id)
+    }
+
+    /// Spawn every agent under `supervisor`, including those of nested
+    /// supervisors, and restart them according to the tree when they fail;
+    /// see [`Supervisor`].
+    pub async fn spawn_supervisor(&self, runtime: &Runtime, supervisor: Supervisor) -> Result<(), RuntimeError> {
+        let spawns = self.trees.lock().unwrap().add(supervisor);
+        for (name, agent, options) in spawns {
+            let options = SpawnOptions { policy: None, recovery: None, ..options };
+            self.spawn_with(runtime, agent, &name, options).await?;
+        }
+        Ok(())
+    }
+
+    /// Spawn `agent` directly on `runtime`, outside the switchboard, and
+    /// let managed agents send to it by `name`.
+    ///
+    /// Messages from managed agents to names that were neither spawned
+    /// through the switchboard nor registered here are undeliverable.
+    pub async fn spawn_external(
+        &self, runtime: &Runtime, agent: Box<dyn Agent>, name: &str,
+    ) -> Result<AgentId, RuntimeError> {
+        let id = *agent.id();
+        runtime.spawn(agent, name).await?;
+        self.externals.lock().unwrap().insert(name.to_string(), Some(id));
+        Ok(id)
+    }
+
+    fn manage(&self, agent: Box<dyn Agent>, name: &str, options: &SpawnOptions) -> Managed {
+        self.roster.lock().unwrap().insert(name.to_string(), *agent.id());
+        self.starting.lock().unwrap().insert(name.to_string());
+        self.exits.lock().unwrap().remove(name);
+        let inbox = Arc::new(Inbox::new(options.mailbox));
+        self.inboxes.lock().unwrap().insert(name.to_string(), inbox.clone());
+        let meter = Arc::new(Meter::default());
+        self.meters.lock().unwrap().insert(name.to_string(), meter.clone());
+        // Only a policy that restarts the agent brings it back after a failure
+        let supervised = options.policy.as_ref().is_some_and(|policy| policy.strategy() != RestartStrategy::Never);
+        Managed::new(agent, name, self.clone(), inbox, meter, supervised, options.restart_state)
+    }
+
+    fn forget(&self, name: &str) {
+        self.roster.lock().unwrap().remove(name);
+        self.inboxes.lock().unwrap().remove(name);
+        self.meters.lock().unwrap().remove(name);
+        self.cancellations.lock().unwrap().remove(name);
+        self.circuits.lock().unwrap().remove(name);
+        self.unenveloped.lock().unwrap().remove(name);
+        self.dependencies.lock().unwrap().remove(name);
+        self.starting.lock().unwrap().remove(name);
+        for subscribers in self.topics.lock().unwrap().values_mut() {
+            subscribers.remove(name);
+        }
+        self.withdraw_all(name);
+    }
+
+    /// Whether `name` was spawned through this switchboard.
+    pub fn is_managed(&self, name: &str) -> bool {
+        self.roster.lock().unwrap().contains_key(name)
+    }
+
+    /// Whether replies from `name` are matched by sender rather than by
+    /// conversation id: it is not managed, or replies without an envelope.
+    pub(crate) fn replies_by_sender(&self, name: &str) -> bool {
+        !self.is_managed(name) || self.unenveloped.lock().unwrap().contains(name)
+    }
+
+    pub(crate) fn add_external(&self, name: &str) {
+        self.externals.lock().unwrap().entry(name.to_string()).or_insert(None);
+    }
+
+    pub(crate) fn is_external(&self, name: &str) -> bool {
+        self.externals.lock().unwrap().contains_key(name)
+    }
+
+    /// Names of all managed agents, sorted.
+    pub fn names(&self) -> Vec<String> {
+        self.roster.lock().unwrap().keys().cloned().collect()
+    }
+
+    /// Managed agents that have not exited, sorted.
+    pub(crate) fn running(&self) -> Vec<String> {
+        let exits = self.exits.lock().unwrap();
+        self.names().into_iter().filter(|name| !exits.contains_key(name)).collect()
+    }
+
+    /// The name an agent was spawned under, through
+    /// [`spawn`](Switchboard::spawn) or [`spawn_external`](Switchboard::spawn_external).
+    pub fn name_of(&self, id: &AgentId) -> Option<String> {
+        let roster = self.roster.lock().unwrap();
+        if let Some((name, _)) = roster.iter().find(|(_, agent)| *agent == id) {
+            return Some(name.clone());
+        }
+        let externals = self.externals.lock().unwrap();
+        externals.iter().find(|(_, agent)| agent.as_ref() == Some(id)).map(|(name, _)| name.clone())
+    }
+
+    /// The id of the agent spawned as `name`; the reverse of [`name_of`](Switchboard::name_of).
+    pub fn id_of(&self, name: &str) -> Option<AgentId> {
+        if let Some(id) = self.roster.lock().unwrap().get(name) {
+            return Some(*id);
+        }
+        self.externals.lock().unwrap().get(name).copied().flatten()
+    }
+
+    /// Requests still waiting for a reply.
+    pub fn conversations(&self) -> &Conversations {
+        &self.conversations
+    }
+
+    /// Whether managed agents have nothing left to do: every agent has run
+    /// at least once, no message between them is waiting to be handled, no
+    /// request is waiting for a reply and no agent has marked itself busy.
+    ///
+    /// Messages to or from agents spawned directly on the runtime are not
+    /// counted.
+    pub fn is_idle(&self) -> bool {
+        self.in_flight.load(Ordering::SeqCst) == 0
+            && self.conversations.open_count() == 0
+            && self.starting.lock().unwrap().is_empty()
+            && self.busy.lock().unwrap().is_empty()
+            && !self.inboxes.lock().unwrap().values().any(|inbox| inbox.has_pending_timer())
+    }
+
+    /// Run the `execute` of an agent spawned with
+    /// [`spawn_reactive`](Switchboard::spawn_reactive) once more. Returns
+    /// false if `name` is not managed.
+    pub fn wake(&self, name: &str) -> bool {
+        match self.inboxes.lock().unwrap().get(name) {
+            Some(inbox) => {
+                inbox.wake();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Depth, capacity and drop count of `name`'s mailbox.
+    pub fn mailbox(&self, name: &str) -> Option<MailboxStats> {
+        self.inboxes.lock().unwrap().get(name).map(|inbox| inbox.stats())
+    }
+
+    /// What `name` has used so far: time spent running, messages handled,
+    /// content still queued, and how often it went over its [`Budget`].
+    pub fn usage(&self, name: &str) -> Option<Usage> {
+        let meter = self.meters.lock().unwrap().get(name).cloned()?;
+        let queued_bytes = self.inboxes.lock().unwrap().get(name).map_or(0, |inbox| inbox.queued_bytes());
+        Some(meter.usage(queued_bytes))
+    }
+
+    /// Mailbox depth of every managed agent, by name.
+    pub fn mailbox_depths(&self) -> BTreeMap<String, usize> {
+        let inboxes = self.inboxes.lock().unwrap();
+        inboxes.iter().map(|(name, inbox)| (name.clone(), inbox.stats().depth)).collect()
+    }
+
+    pub(crate) fn restarted(&self, name: &str) {
+        self.withdraw_all(name);
+        self.exits.lock().unwrap().remove(name);
+    }
+
+    fn dispatched(&self) {
+        self.in_flight.fetch_add(1, Ordering::SeqCst);
+    }
+
+    pub(crate) fn handled(&self) {
+        let _ = self.in_flight.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1));
+    }
+
+    pub(crate) fn started(&self, name: &str) {
+        self.starting.lock().unwrap().remove(name);
+    }
+
+    pub(crate) fn set_busy(&self, name: &str, busy: bool) {
+        let mut names = self.busy.lock().unwrap();
+        if busy {
+            names.insert(name.to_string());
+        } else {
+            names.remove(name);
+        }
+    }
+}
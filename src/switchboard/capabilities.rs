@@ -0,0 +1,38 @@
+//! The directory of what each agent offers.
+use super::Switchboard;
+
+impl Switchboard {
+    /// List `name` as offering `capability`. Entries are removed when the
+    /// agent exits, fails or restarts, so agents register from `initialize`.
+    pub fn register(&self, name: &str, capability: &str) {
+        let mut directory = self.directory.lock().unwrap();
+        directory.entry(capability.to_string()).or_default().insert(name.to_string());
+    }
+
+    pub fn withdraw(&self, name: &str, capability: &str) {
+        if let Some(providers) = self.directory.lock().unwrap().get_mut(capability) {
+            providers.remove(name);
+        }
+    }
+
+    pub(crate) fn withdraw_all(&self, name: &str) {
+        self.directory.lock().unwrap().retain(|_, providers| {
+            providers.remove(name);
+            !providers.is_empty()
+        });
+    }
+
+    /// Names of the running agents offering `capability`, sorted.
+    pub fn find_agents(&self, capability: &str) -> Vec<String> {
+        let providers = self.directory.lock().unwrap().get(capability).cloned().unwrap_or_default();
+        let exits = self.exits.lock().unwrap();
+        providers.into_iter().filter(|name| !exits.contains_key(name)).collect()
+    }
+
+    /// Capabilities `name` has registered, sorted.
+    pub fn capabilities(&self, name: &str) -> Vec<String> {
+        let directory = self.directory.lock().unwrap();
+        let offered = directory.iter().filter(|(_, providers)| providers.contains(name));
+        offered.map(|(capability, _)| capability.clone()).collect()
+    }
+}
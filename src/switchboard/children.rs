@@ -0,0 +1,74 @@
+//! Agents spawned by other agents, and agents swapped while running.
+use super::{SpawnError, SpawnOptions, Switchboard};
+use crate::mailbox::SendError;
+use crate::supervisor::Command;
+use agentropic_core::{Agent, AgentId};
+
+impl Switchboard {
+    /// Spawn `agent` as `name` on the runtime `parent` runs on, as a child
+    /// of `parent`: when `parent` exits, however it exits, its children are
+    /// stopped, and a graceful [`shutdown`](Switchboard::shutdown) keeps
+    /// them running until `parent` has shut down. Agents usually do this through
+    /// [`ContextExt::spawn_child`](crate::ContextExt::spawn_child).
+    pub async fn spawn_child(
+        &self, parent: &str, agent: Box<dyn Agent>, name: &str, options: SpawnOptions,
+    ) -> Result<AgentId, SpawnError> {
+        if self.is_closing() {
+            return Err(SpawnError::ShuttingDown);
+        }
+        if !self.is_managed(parent) {
+            return Err(SpawnError::UnknownParent(parent.to_string()));
+        }
+        if self.is_managed(name) && self.exit_status(name).is_none() {
+            return Err(SpawnError::NameTaken(name.to_string()));
+        }
+        let runtime = self.runtime.lock().unwrap().clone();
+        let runtime = runtime.ok_or_else(|| SpawnError::UnknownParent(parent.to_string()))?;
+        let spawned = self.spawn_with(&runtime, agent, name, options).await;
+        let id = spawned.map_err(|err| SpawnError::Runtime(err.to_string()))?;
+        self.parents.lock().unwrap().insert(name.to_string(), parent.to_string());
+        self.dependencies.lock().unwrap().entry(parent.to_string()).or_default().insert(name.to_string());
+        Ok(id)
+    }
+
+    /// The agent that spawned `name` with [`spawn_child`](Switchboard::spawn_child).
+    pub fn parent(&self, name: &str) -> Option<String> {
+        self.parents.lock().unwrap().get(name).cloned()
+    }
+
+    /// Agents `name` spawned with [`spawn_child`](Switchboard::spawn_child),
+    /// running or not, sorted.
+    pub fn children(&self, name: &str) -> Vec<String> {
+        let parents = self.parents.lock().unwrap();
+        parents.iter().filter(|(_, parent)| *parent == name).map(|(child, _)| child.clone()).collect()
+    }
+
+    /// Swap the implementation of the running agent `name` for `agent` at
+    /// its next `execute`.
+    ///
+    /// The old implementation is shut down and the new one initialized,
+    /// after which it is known by its own id. Its mailbox, subscriptions,
+    /// children and restart policy stay as they were, so messages queued
+    /// for `name` meanwhile are handled by the new implementation.
+    pub fn replace(&self, name: &str, agent: Box<dyn Agent>) -> Result<(), SendError> {
+        if !self.is_managed(name) {
+            return Err(SendError::UnknownRecipient(name.to_string()));
+        }
+        if self.exit_status(name).is_some() {
+            return Err(SendError::Stopped(name.to_string()));
+        }
+        self.replacements.lock().unwrap().insert(name.to_string(), agent);
+        self.commands.lock().unwrap().insert(name.to_string(), Command::Replace);
+        self.wake(name);
+        Ok(())
+    }
+
+    pub(crate) fn take_replacement(&self, name: &str) -> Option<Box<dyn Agent>> {
+        self.replacements.lock().unwrap().remove(name)
+    }
+
+    /// Point `name` at the id of the implementation that replaced its old one.
+    pub(crate) fn reassign(&self, name: &str, id: AgentId) {
+        self.roster.lock().unwrap().insert(name.to_string(), id);
+    }
+}
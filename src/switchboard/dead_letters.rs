@@ -0,0 +1,53 @@
+//! Keeps and forwards messages that could not be delivered.
+use super::routing::mailbox_full;
+use super::Switchboard;
+use crate::mailbox::{DeadLetter, Offer};
+
+/// How many undelivered messages are kept; older ones are discarded first.
+const DEAD_LETTER_LIMIT: usize = 1000;
+
+impl Switchboard {
+    /// Messages that could not be delivered, oldest first.
+    ///
+    /// A message lands here when nobody else learns that it was lost: a
+    /// fire-and-forget [`send`](crate::ContextExt::send) to an unknown or
+    /// stopped agent, a message discarded by a full mailbox's
+    /// [`Overflow`](crate::Overflow) policy, or one still queued when its
+    /// receiver stopped itself. Sends that return a [`SendError`](crate::SendError) leave it
+    /// to the sender. Only the most recent 1000 are kept.
+    pub fn dead_letters(&self) -> Vec<DeadLetter> {
+        self.dead_letters.lock().unwrap().iter().cloned().collect()
+    }
+
+    /// Remove and return every dead letter.
+    pub fn take_dead_letters(&self) -> Vec<DeadLetter> {
+        self.dead_letters.lock().unwrap().drain(..).collect()
+    }
+
+    /// Also hand each dead letter to the managed agent `name`, as if the
+    /// original sender had sent it there. Its reason stays in
+    /// [`dead_letters`](Switchboard::dead_letters). Letters the dead-letter
+    /// agent itself cannot take are not forwarded again.
+    pub fn forward_dead_letters(&self, name: &str) {
+        *self.dead_letter_agent.lock().unwrap() = Some(name.to_string());
+    }
+
+    pub(crate) fn dead_letter(&self, dead: DeadLetter) {
+        let forward_to = self.dead_letter_agent.lock().unwrap().clone().filter(|to| *to != dead.receiver);
+        let forward = forward_to.and_then(|to| Some((self.open_inbox(&to).ok()?, to)));
+        if let Some((inbox, to)) = forward {
+            match inbox.offer(dead.letter()) {
+                Offer::Accepted => self.dispatched(),
+                // The letter it made room for is dead too
+                Offer::Evicted(evicted) => self.dead_letter(DeadLetter::new(&to, evicted, mailbox_full(&to, &inbox))),
+                Offer::Dropped(_) => {}
+                Offer::Full(_) => inbox.record_drop(),
+            }
+        }
+        let mut dead_letters = self.dead_letters.lock().unwrap();
+        if dead_letters.len() == DEAD_LETTER_LIMIT {
+            dead_letters.pop_front();
+        }
+        dead_letters.push_back(dead);
+    }
+}
@@ -0,0 +1,87 @@
+//! Records how agents exit and announces what happens to them.
+use super::{Switchboard, STOP_TIMEOUT};
+use crate::conversation::Envelope;
+use crate::lifecycle::{Events, ExitStatus, Lifecycle, LifecycleEvent, LIFECYCLE_TOPIC};
+use crate::mailbox::{Letter, Priority};
+use crate::payload;
+use crate::Performative;
+
+impl Switchboard {
+    /// How `name` exited, or `None` while it is still running.
+    pub fn exit_status(&self, name: &str) -> Option<ExitStatus> {
+        self.exits.lock().unwrap().get(name).cloned()
+    }
+
+    /// Wait for `name` to exit. Resolves to `None` if `name` was never
+    /// spawned through this switchboard.
+    ///
+    /// An agent with a restart policy that keeps failing is only reported
+    /// once the runtime shuts it down.
+    pub async fn join(&self, name: &str) -> Option<ExitStatus> {
+        loop {
+            let exited = self.exited.notified();
+            if !self.is_managed(name) {
+                return None;
+            }
+            if let Some(status) = self.exit_status(name) {
+                return Some(status);
+            }
+            exited.await;
+        }
+    }
+
+    pub(crate) fn exit(&self, name: &str, status: ExitStatus) {
+        self.withdraw_all(name);
+        self.starting.lock().unwrap().remove(name);
+        self.busy.lock().unwrap().remove(name);
+        let mut exits = self.exits.lock().unwrap();
+        let first = !exits.contains_key(name);
+        if first {
+            exits.insert(name.to_string(), status.clone());
+        }
+        drop(exits);
+        self.exited.notify_waiters();
+        if first {
+            for child in self.children(name) {
+                if self.exit_status(&child).is_none() {
+                    self.stop(&child, STOP_TIMEOUT);
+                }
+            }
+            let event = match status {
+                ExitStatus::Failed(reason) => Lifecycle::GaveUp(reason),
+                status => Lifecycle::Stopped(status),
+            };
+            self.announce(name, event);
+        }
+    }
+
+    /// Lifecycle events of every managed agent from now on: spawns,
+    /// restarts, failures and exits. Agents can hear them too by
+    /// subscribing to [`LIFECYCLE_TOPIC`].
+    pub fn events(&self) -> Events {
+        self.announcer.subscribe()
+    }
+
+    /// Tell [`events`](Switchboard::events) readers and the agents
+    /// subscribed to [`LIFECYCLE_TOPIC`] that `event` happened to `agent`.
+    pub(crate) fn announce(&self, agent: &str, event: Lifecycle) {
+        let event = LifecycleEvent { agent: agent.to_string(), event };
+        if let Ok(content) = payload::encode(&event) {
+            for subscriber in self.subscribers(LIFECYCLE_TOPIC).iter().filter(|name| *name != agent) {
+                let envelope = Envelope {
+                    sender_id: None,
+                    conversation_id: None,
+                    in_reply_to: None,
+                    priority: Priority::Normal,
+                    deadline: None,
+                    body: content.clone(),
+                };
+                let sender = LIFECYCLE_TOPIC.to_string();
+                let letter = Letter { sender, performative: Performative::Inform, envelope };
+                // Subscribers that have exited miss it
+                let _ = self.send_letter(subscriber, letter);
+            }
+        }
+        self.announcer.send(event);
+    }
+}
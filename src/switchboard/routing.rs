@@ -0,0 +1,125 @@
+//! Queues letters in the mailboxes of managed agents.
+use super::Switchboard;
+use crate::mailbox::{DeadLetter, Inbox, Letter, Offer, Priority, SendError};
+use crate::recovery::Circuit;
+use crate::Performative;
+use agentropic_patterns::hierarchy::Level;
+use std::sync::Arc;
+use std::time::Duration;
+
+impl Switchboard {
+    /// Place `name` at `level` of a hierarchy.
+    ///
+    /// A [`Performative::Request`] sent at [`Priority::Normal`] from a
+    /// higher-ranked agent to a lower-ranked one is delivered as
+    /// [`Priority::High`], ahead of routine traffic such as reports.
+    pub fn set_level(&self, name: &str, level: &Level) {
+        self.ranks.lock().unwrap().insert(name.to_string(), level.rank());
+    }
+
+    /// Raise orders sent down the hierarchy; see [`set_level`](Switchboard::set_level).
+    fn ranked(&self, receiver: &str, letter: Letter) -> Letter {
+        if letter.performative != Performative::Request || letter.envelope.priority != Priority::Normal {
+            return letter;
+        }
+        let ranks = self.ranks.lock().unwrap();
+        match (ranks.get(&letter.sender), ranks.get(receiver)) {
+            (Some(sender), Some(receiver)) if sender > receiver => letter.with_priority(Priority::High),
+            _ => letter,
+        }
+    }
+
+    fn inbox(&self, receiver: &str) -> Result<Arc<Inbox>, SendError> {
+        let inbox = self.inboxes.lock().unwrap().get(receiver).cloned();
+        inbox.ok_or_else(|| SendError::UnknownRecipient(receiver.to_string()))
+    }
+
+    /// The inbox of `receiver`, unless it has exited.
+    pub(super) fn open_inbox(&self, receiver: &str) -> Result<Arc<Inbox>, SendError> {
+        let inbox = self.inbox(receiver)?;
+        if self.exit_status(receiver).is_some() {
+            return Err(SendError::Stopped(receiver.to_string()));
+        }
+        if self.circuit(receiver) == Some(Circuit::Open) {
+            return Err(SendError::CircuitOpen(receiver.to_string()));
+        }
+        Ok(inbox)
+    }
+
+    /// Queue `letter` for the managed agent `receiver`.
+    pub(crate) fn post(&self, receiver: &str, letter: Letter) -> Result<(), SendError> {
+        let inbox = self.open_inbox(receiver)?;
+        let offer = inbox.offer(self.ranked(receiver, letter));
+        self.settle(receiver, &inbox, offer)
+    }
+
+    /// Like [`post`](Switchboard::post), waiting for space if the receiver's
+    /// mailbox blocks when full.
+    pub(crate) async fn post_wait(&self, receiver: &str, letter: Letter) -> Result<(), SendError> {
+        let inbox = self.open_inbox(receiver)?;
+        let offer = inbox.offer_wait(self.ranked(receiver, letter)).await;
+        self.settle(receiver, &inbox, offer)
+    }
+
+    /// Send `letter` to the managed agent `receiver` through the send middleware.
+    pub(crate) fn send_letter(&self, receiver: &str, letter: Letter) -> Result<(), SendError> {
+        let (receiver, letter, delay) = self.screen_send(receiver, letter)?;
+        self.route(receiver, letter, delay)
+    }
+
+    /// Queue a screened letter for the managed agent `receiver`, after `delay`.
+    pub(crate) fn route(&self, receiver: String, letter: Letter, delay: Duration) -> Result<(), SendError> {
+        if !self.is_managed(&receiver) {
+            return Err(SendError::UnknownRecipient(receiver));
+        }
+        if delay.is_zero() {
+            return self.post(&receiver, letter);
+        }
+        self.post_later(receiver, letter, delay);
+        Ok(())
+    }
+
+    /// Queue `letter` for `receiver` once `delay` has passed. It counts as
+    /// in flight meanwhile; if it cannot be delivered it becomes a dead letter.
+    pub(crate) fn post_later(&self, receiver: String, letter: Letter, delay: Duration) {
+        let board = self.clone();
+        board.dispatched();
+        tokio::spawn(async move {
+            tokio::time::sleep(delay).await;
+            board.handled();
+            let inbox = match board.open_inbox(&receiver) {
+                Ok(inbox) => inbox,
+                Err(reason) => return board.dead_letter(DeadLetter::new(&receiver, letter, reason)),
+            };
+            match inbox.offer(board.ranked(&receiver, letter)) {
+                Offer::Full(letter) => {
+                    inbox.record_drop();
+                    board.dead_letter(DeadLetter::new(&receiver, letter, mailbox_full(&receiver, &inbox)));
+                }
+                offer => {
+                    let _ = board.settle(&receiver, &inbox, offer);
+                }
+            }
+        });
+    }
+
+    fn settle(&self, receiver: &str, inbox: &Inbox, offer: Offer) -> Result<(), SendError> {
+        match offer {
+            Offer::Accepted => self.dispatched(),
+            // One letter in, one out
+            Offer::Evicted(letter) | Offer::Dropped(letter) => {
+                self.dead_letter(DeadLetter::new(receiver, letter, mailbox_full(receiver, inbox)));
+            }
+            Offer::Full(_) => {
+                inbox.record_drop();
+                return Err(mailbox_full(receiver, inbox));
+            }
+        }
+        Ok(())
+    }
+}
+
+pub(super) fn mailbox_full(receiver: &str, inbox: &Inbox) -> SendError {
+    let capacity = inbox.config().capacity().unwrap_or_default();
+    SendError::MailboxFull { receiver: receiver.to_string(), capacity }
+}
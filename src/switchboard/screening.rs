@@ -0,0 +1,58 @@
+//! Runs middleware over the messages managed agents send and receive.
+use super::Switchboard;
+use crate::mailbox::{Letter, SendError};
+use crate::middleware::{Message, Middleware, Verdict};
+use std::sync::Arc;
+use std::time::Duration;
+
+impl Switchboard {
+    /// Run `middleware` on every message managed agents send and receive,
+    /// after any added before it.
+    pub fn add_middleware(&self, middleware: impl Middleware + 'static) {
+        self.middleware.lock().unwrap().push(Arc::new(middleware));
+    }
+
+    /// Run the send middleware over `letter`, returning where it now goes
+    /// and how long to hold it.
+    pub(crate) fn screen_send(
+        &self, receiver: &str, mut letter: Letter,
+    ) -> Result<(String, Letter, Duration), SendError> {
+        let chain = self.middleware.lock().unwrap().clone();
+        if chain.is_empty() {
+            return Ok((receiver.to_string(), letter, Duration::ZERO));
+        }
+        let mut message = Message::of(receiver, &letter);
+        let delay = screen(&chain, &mut message, |middleware, message| middleware.on_send(message))?;
+        let receiver = message.apply(&mut letter);
+        Ok((receiver, letter, delay))
+    }
+
+    /// Run the receive middleware over `letter`, returning how long to hold it.
+    pub(crate) fn screen_receive(&self, receiver: &str, letter: &mut Letter) -> Result<Duration, SendError> {
+        let chain = self.middleware.lock().unwrap().clone();
+        if chain.is_empty() {
+            return Ok(Duration::ZERO);
+        }
+        let mut message = Message::of(receiver, letter);
+        let delay = screen(&chain, &mut message, |middleware, message| middleware.on_receive(message))?;
+        message.apply(letter);
+        Ok(delay)
+    }
+}
+
+/// Pass `message` through `chain`, adding up delays until one drops it.
+fn screen(
+    chain: &[Arc<dyn Middleware>],
+    message: &mut Message,
+    hook: impl Fn(&dyn Middleware, &mut Message) -> Verdict,
+) -> Result<Duration, SendError> {
+    let mut delay = Duration::ZERO;
+    for middleware in chain {
+        match hook(middleware.as_ref(), message) {
+            Verdict::Deliver => {}
+            Verdict::Delay(by) => delay += by,
+            Verdict::Drop(reason) => return Err(SendError::Filtered(reason)),
+        }
+    }
+    Ok(delay)
+}
@@ -0,0 +1,88 @@
+//! Stops agents, one at a time or all together.
+use super::{Switchboard, STOP_TIMEOUT};
+use crate::mailbox::SendError;
+use crate::shutdown::{self, Shutdown, ShutdownReport};
+use crate::supervisor::Command;
+use crate::timeout::Cancellation;
+use agentropic_runtime::prelude::*;
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+impl Switchboard {
+    /// Stop every managed agent in phases, then shut `runtime` down.
+    ///
+    /// First messages from outside the switchboard are refused. Then the
+    /// agents get until the drain deadline to handle what is already
+    /// queued and answer open requests. Then they are shut down, each only
+    /// after the agents that [depend on it](super::SpawnOptions::depends_on), and
+    /// each `shutdown` is abandoned after the stop timeout. Agents still busy
+    /// in `execute` by then, like agents spawned directly on the runtime,
+    /// are left to `runtime.shutdown()`.
+    pub async fn shutdown(&self, runtime: &Runtime, shutdown: Shutdown) -> Result<ShutdownReport, RuntimeError> {
+        shutdown::run(self, runtime, shutdown).await
+    }
+
+    pub(crate) fn close(&self) {
+        self.closing.store(true, Ordering::SeqCst);
+    }
+
+    /// Whether a graceful shutdown has begun.
+    pub fn is_closing(&self) -> bool {
+        self.closing.load(Ordering::SeqCst)
+    }
+
+    /// Whether every message has been handled and every request answered,
+    /// ignoring timers; what a graceful shutdown waits for.
+    pub(crate) fn is_drained(&self) -> bool {
+        self.in_flight.load(Ordering::SeqCst) == 0
+            && self.conversations.open_count() == 0
+            && self.busy.lock().unwrap().is_empty()
+    }
+
+    pub(crate) fn dependencies(&self) -> BTreeMap<String, BTreeSet<String>> {
+        self.dependencies.lock().unwrap().clone()
+    }
+
+    /// Shut `name` down for good at its next `execute`, stopping its
+    /// children too; see [`ContextExt::stop`](crate::ContextExt::stop).
+    pub(crate) fn stop_agent(&self, name: &str) -> Result<(), SendError> {
+        if !self.is_managed(name) {
+            return Err(SendError::UnknownRecipient(name.to_string()));
+        }
+        if self.exit_status(name).is_some() {
+            return Err(SendError::Stopped(name.to_string()));
+        }
+        self.stop(name, STOP_TIMEOUT);
+        Ok(())
+    }
+
+    /// Ask `name` to shut down for good at its next `execute`, cancelling
+    /// the call it is in so that it gets there sooner.
+    pub(crate) fn stop(&self, name: &str, timeout: Duration) {
+        self.commands.lock().unwrap().insert(name.to_string(), Command::Stop(timeout));
+        if let Some(cancellation) = self.cancellations.lock().unwrap().get(name) {
+            cancellation.cancel();
+        }
+        self.wake(name);
+    }
+
+    /// A fresh cancellation for the call `name` is about to make.
+    pub(crate) fn arm(&self, name: &str) -> Cancellation {
+        let cancellation = Cancellation::default();
+        self.cancellations.lock().unwrap().insert(name.to_string(), cancellation.clone());
+        cancellation
+    }
+
+    pub(crate) fn cancellation(&self, name: &str) -> Option<Cancellation> {
+        self.cancellations.lock().unwrap().get(name).cloned()
+    }
+
+    pub(crate) fn overran(&self, name: &str) {
+        self.overran.lock().unwrap().insert(name.to_string());
+    }
+
+    pub(crate) fn shutdown_overran(&self, name: &str) -> bool {
+        self.overran.lock().unwrap().contains(name)
+    }
+}
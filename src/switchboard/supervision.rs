@@ -0,0 +1,51 @@
+//! Supervisor decisions and circuit breakers.
+use super::Switchboard;
+use crate::recovery::Circuit;
+use crate::supervisor::Command;
+
+impl Switchboard {
+    /// Let the supervision tree react to `name` failing. Returns false if
+    /// no supervisor spawned through [`spawn_supervisor`](Switchboard::spawn_supervisor) watches it.
+    pub(crate) fn child_failed(&self, name: &str) -> bool {
+        let decisions = {
+            let mut trees = self.trees.lock().unwrap();
+            if !trees.supervises(name) {
+                return false;
+            }
+            trees.failed(name)
+        };
+        for (agent, command) in decisions {
+            self.commands.lock().unwrap().insert(agent.clone(), command);
+            self.wake(&agent);
+        }
+        true
+    }
+
+    pub(crate) fn take_command(&self, name: &str) -> Option<Command> {
+        self.commands.lock().unwrap().remove(name)
+    }
+
+    /// The circuit of `name`, if it was spawned with a [`Recovery`](crate::Recovery) policy.
+    pub fn circuit(&self, name: &str) -> Option<Circuit> {
+        self.circuits.lock().unwrap().get(name).copied()
+    }
+
+    /// Try an agent whose circuit is open again now, without waiting for
+    /// its cooldown. Returns false if its circuit is not open.
+    pub fn reset_circuit(&self, name: &str) -> bool {
+        let mut circuits = self.circuits.lock().unwrap();
+        match circuits.get_mut(name) {
+            Some(circuit) if *circuit == Circuit::Open => {
+                *circuit = Circuit::HalfOpen;
+                drop(circuits);
+                self.wake(name);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub(crate) fn set_circuit(&self, name: &str, circuit: Circuit) {
+        self.circuits.lock().unwrap().insert(name.to_string(), circuit);
+    }
+}
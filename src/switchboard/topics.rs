@@ -0,0 +1,68 @@
+//! Topics and the groups of pattern members addressed through them.
+use super::Switchboard;
+use agentropic_core::AgentId;
+use agentropic_patterns::federation::Federation;
+use agentropic_patterns::swarm::Swarm;
+use agentropic_patterns::team::Team;
+
+/// Patterns whose members can be addressed as a topic.
+pub trait Membership {
+    fn member_ids(&self) -> Vec<AgentId>;
+}
+
+impl Membership for Swarm {
+    fn member_ids(&self) -> Vec<AgentId> {
+        self.members().to_vec()
+    }
+}
+
+impl Membership for Team {
+    fn member_ids(&self) -> Vec<AgentId> {
+        self.members().into_iter().copied().collect()
+    }
+}
+
+impl Membership for Federation {
+    fn member_ids(&self) -> Vec<AgentId> {
+        self.members().to_vec()
+    }
+}
+
+impl Switchboard {
+    /// Add `name` to `topic`. Agents outside the switchboard must be spawned
+    /// with [`spawn_external`](Switchboard::spawn_external) to receive.
+    pub fn subscribe(&self, topic: &str, name: &str) {
+        let mut topics = self.topics.lock().unwrap();
+        topics.entry(topic.to_string()).or_default().insert(name.to_string());
+    }
+
+    /// Remove `name` from `topic`, and from the group addressed through it.
+    pub fn unsubscribe(&self, topic: &str, name: &str) {
+        if let Some(subscribers) = self.topics.lock().unwrap().get_mut(topic) {
+            subscribers.remove(name);
+        }
+        let id = self.id_of(name);
+        if let Some(members) = self.groups.lock().unwrap().get_mut(topic) {
+            members.retain(|member| Some(*member) != id);
+        }
+    }
+
+    /// Subscribers of `topic` that have not exited, including group
+    /// members, sorted by name.
+    pub fn subscribers(&self, topic: &str) -> Vec<String> {
+        let mut names = self.topics.lock().unwrap().get(topic).cloned().unwrap_or_default();
+        let members = self.groups.lock().unwrap().get(topic).cloned().unwrap_or_default();
+        names.extend(members.iter().filter_map(|id| self.name_of(id)));
+        let exits = self.exits.lock().unwrap();
+        names.into_iter().filter(|name| !exits.contains_key(name)).collect()
+    }
+
+    /// Address every member of `pattern` through `topic`.
+    ///
+    /// Members are matched by [`AgentId`] when something is published, so
+    /// the group can be declared before its members are spawned. Only
+    /// members spawned through this switchboard receive messages.
+    pub fn group(&self, topic: &str, pattern: &impl Membership) {
+        self.groups.lock().unwrap().insert(topic.to_string(), pattern.member_ids());
+    }
+}
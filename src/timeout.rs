@@ -0,0 +1,197 @@
+//! Time limits on a managed agent's calls, and cancelling the calls that
+//! run past them.
+//!
+//! The runtime only sees an agent's failures as an `AgentError`, which
+//! belongs to `agentropic-core` and has no variant for a timeout, so a
+//! [`TimedOut`] reaches it as a plain failure of its phase. Whether a
+//! timed-out agent is restarted is therefore settled here, before the
+//! runtime, [`Recovery`](crate::Recovery) or a supervisor sees the
+//! failure; see [`Timeouts::stop_on_timeout`].
+use agentropic_core::AgentError;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Notify;
+
+/// How long a call that ran past its limit gets, once its [`Cancellation`]
+/// is cancelled, to return by itself before it is dropped.
+pub const CANCEL_GRACE: Duration = Duration::from_millis(100);
+
+/// One of the calls the runtime makes into an agent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Phase {
+    Initialize,
+    Execute,
+    HandleMessage,
+    Shutdown,
+}
+
+impl Phase {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Phase::Initialize => "initialize",
+            Phase::Execute => "execute",
+            Phase::HandleMessage => "handle_message",
+            Phase::Shutdown => "shutdown",
+        }
+    }
+}
+
+impl fmt::Display for Phase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// How long each of a managed agent's calls may run; set them with
+/// [`SpawnOptions::with_timeouts`](crate::SpawnOptions::with_timeouts).
+///
+/// When a call runs past its limit its [`Cancellation`] is cancelled, and
+/// it gets [`CANCEL_GRACE`] to return before it is dropped. Either way it
+/// is announced as [`Lifecycle::TimedOut`](crate::Lifecycle::TimedOut). An
+/// `initialize`, `execute` or `shutdown` that timed out fails with
+/// [`TimedOut`], which restart policies, [`Recovery`](crate::Recovery) and
+/// supervisors treat like any other failure, unless the agent
+/// [stops on timeout](Timeouts::stop_on_timeout). A `handle_message` that timed
+/// out only fails that message, as a handler error would: a sender waiting
+/// on a reply is answered with [`Performative::Failure`](crate::Performative::Failure),
+/// and the agent carries on.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Timeouts {
+    initialize: Option<Duration>,
+    execute: Option<Duration>,
+    handle_message: Option<Duration>,
+    shutdown: Option<Duration>,
+    stop_on_timeout: bool,
+}
+
+impl Timeouts {
+    /// No limits.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_initialize(mut self, limit: Duration) -> Self {
+        self.initialize = Some(limit);
+        self
+    }
+
+    pub fn with_execute(mut self, limit: Duration) -> Self {
+        self.execute = Some(limit);
+        self
+    }
+
+    /// Limit handling one message. The agent carries on with the next one
+    /// after a message times out.
+    pub fn with_handle_message(mut self, limit: Duration) -> Self {
+        self.handle_message = Some(limit);
+        self
+    }
+
+    pub fn with_shutdown(mut self, limit: Duration) -> Self {
+        self.shutdown = Some(limit);
+        self
+    }
+
+    /// Stop the agent for good when its `initialize` or `execute` times
+    /// out, rather than restart it: a call that hangs once is likely to hang
+    /// again. It is shut down and exits with
+    /// [`ExitStatus::Failed`](crate::ExitStatus::Failed); its restart policy,
+    /// [`Recovery`](crate::Recovery) or supervisor is not consulted. Other
+    /// failures are restarted as usual.
+    pub fn stop_on_timeout(mut self) -> Self {
+        self.stop_on_timeout = true;
+        self
+    }
+
+    pub fn stops_on_timeout(&self) -> bool {
+        self.stop_on_timeout
+    }
+
+    /// The limit on calls in `phase`, if any.
+    pub fn limit(&self, phase: Phase) -> Option<Duration> {
+        match phase {
+            Phase::Initialize => self.initialize,
+            Phase::Execute => self.execute,
+            Phase::HandleMessage => self.handle_message,
+            Phase::Shutdown => self.shutdown,
+        }
+    }
+}
+
+/// A call ran past its limit in [`Timeouts`] and was cancelled.
+///
+/// It is announced as [`Lifecycle::TimedOut`](crate::Lifecycle::TimedOut),
+/// and reaches the runtime as the [`AgentError`] of its phase, which cannot
+/// tell it from other failures.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TimedOut {
+    pub phase: Phase,
+    pub limit: Duration,
+}
+
+impl fmt::Display for TimedOut {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} timed out after {}ms", self.phase, self.limit.as_millis())
+    }
+}
+
+impl std::error::Error for TimedOut {}
+
+impl From<TimedOut> for AgentError {
+    fn from(err: TimedOut) -> Self {
+        match err.phase {
+            Phase::Initialize => AgentError::InitializationFailed(err.to_string()),
+            Phase::Execute | Phase::HandleMessage => AgentError::ExecutionFailed(err.to_string()),
+            Phase::Shutdown => AgentError::ShutdownFailed(err.to_string()),
+        }
+    }
+}
+
+/// Tells a long-running call that it should give up, from
+/// [`ContextExt::cancellation`](crate::ContextExt::cancellation).
+///
+/// It is cancelled when the call runs past its limit in [`Timeouts`], or
+/// when the agent is asked to stop while the call is running. Calls that
+/// loop or wait on something slow can check it, or race it:
+///
+/// ```ignore
+/// tokio::select! {
+///     answer = ask_llm(question) => reply(answer),
+///     _ = ctx.cancellation().cancelled() => return Ok(()),
+/// }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Cancellation {
+    inner: Arc<Flag>,
+}
+
+#[derive(Debug, Default)]
+struct Flag {
+    cancelled: AtomicBool,
+    notify: Notify,
+}
+
+impl Cancellation {
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Resolve once cancelled.
+    pub async fn cancelled(&self) {
+        loop {
+            let notified = self.inner.notify.notified();
+            if self.is_cancelled() {
+                return;
+            }
+            notified.await;
+        }
+    }
+
+    pub(crate) fn cancel(&self) {
+        self.inner.cancelled.store(true, Ordering::SeqCst);
+        self.inner.notify.notify_waiters();
+    }
+}
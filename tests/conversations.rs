@@ -0,0 +1,160 @@
+//! Requests between managed agents, correlated with their replies by
+//! conversation id, run on the simulated clock.
+use agentropic_core::{Agent, AgentContext, AgentId, AgentResult};
+use agentropic_examples::{
+    ContextExt, Performative, Reply, RequestError, RuntimeExt, Simulation, SpawnOptions, Switchboard,
+};
+use agentropic_runtime::prelude::*;
+use async_trait::async_trait;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// How a [`Responder`] answers.
+#[derive(Clone, Copy)]
+enum Answering {
+    Reply,
+    /// With a plain `send_message`, which carries no conversation id.
+    Plain,
+    Silent,
+}
+
+/// Answers every question with "re: <question>".
+struct Responder { id: AgentId, answering: Answering }
+
+#[async_trait]
+impl Agent for Responder {
+    fn id(&self) -> &AgentId { &self.id }
+    async fn initialize(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
+    async fn execute(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
+    async fn shutdown(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
+    async fn handle_message(&mut self, ctx: &AgentContext, sender: &str, _p: &str, content: &str) -> AgentResult<()> {
+        let answer = format!("re: {}", content);
+        match self.answering {
+            Answering::Reply => ctx.reply(Performative::Inform, &answer)?,
+            Answering::Plain => ctx.send_message(sender, "inform", &answer),
+            Answering::Silent => {}
+        }
+        Ok(())
+    }
+}
+
+type Answers = Arc<Mutex<Vec<Result<Reply, RequestError>>>>;
+
+/// Puts all its `questions` to `receiver` at once and records the answers
+/// in the order it asked.
+struct Asker { id: AgentId, receiver: &'static str, questions: Vec<&'static str>, answers: Answers }
+
+#[async_trait]
+impl Agent for Asker {
+    fn id(&self) -> &AgentId { &self.id }
+    async fn initialize(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
+    async fn execute(&mut self, ctx: &AgentContext) -> AgentResult<()> {
+        let pending: Vec<_> = self.questions.drain(..)
+            .map(|question| ctx.request(self.receiver, Performative::Query, question, Duration::from_secs(1)))
+            .collect();
+        if !pending.is_empty() {
+            let answers = self.answers.clone();
+            tokio::spawn(async move {
+                for answer in pending {
+                    let answer = answer.await;
+                    answers.lock().unwrap().push(answer);
+                }
+            });
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        Ok(())
+    }
+    async fn shutdown(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
+}
+
+/// Ask `questions` of a responder spawned with `options` and return the
+/// answers, and how many conversations were still open afterwards.
+fn ask(
+    answering: Answering, options: SpawnOptions, questions: Vec<&'static str>,
+) -> (Vec<Result<Reply, RequestError>>, usize) {
+    let answers = Answers::default();
+    let expected = questions.len();
+    let scenario = async {
+        let runtime = Runtime::new();
+        let board = Switchboard::new();
+        let responder = Responder { id: AgentId::new(), answering };
+        board.spawn_with(&runtime, Box::new(responder), "responder", options.reactive()).await?;
+        let asker = Asker { id: AgentId::new(), receiver: "responder", questions, answers: answers.clone() };
+        board.spawn(&runtime, Box::new(asker), "asker").await?;
+        let _ = runtime.run_until(|| answers.lock().unwrap().len() == expected, Duration::from_secs(5)).await;
+        let open = board.conversations().open_count();
+        runtime.shutdown().await?;
+        Ok::<_, RuntimeError>(open)
+    };
+    let (outcome, _) = Simulation::new(1).run(scenario);
+    let open = outcome.expect("scenario failed");
+    let answers = answers.lock().unwrap().clone();
+    (answers, open)
+}
+
+#[test]
+fn concurrent_requests_each_get_their_own_reply() {
+    let (answers, open) = ask(Answering::Reply, SpawnOptions::new(), vec!["first", "second"]);
+    let contents: Vec<_> = answers.into_iter().map(|answer| answer.unwrap().content).collect();
+    assert_eq!(contents, ["re: first", "re: second"]);
+    assert_eq!(open, 0);
+}
+
+#[test]
+fn an_unanswered_request_times_out_and_closes_its_conversation() {
+    let (answers, open) = ask(Answering::Silent, SpawnOptions::new(), vec!["hello?"]);
+    let timeout = RequestError::Timeout { receiver: "responder".into(), after: Duration::from_secs(1) };
+    assert_eq!(answers, [Err(timeout)]);
+    assert_eq!(open, 0);
+}
+
+#[test]
+fn plain_replies_are_matched_by_sender_only_if_the_receiver_opts_in() {
+    let options = SpawnOptions::new().replies_without_envelope();
+    let (answers, open) = ask(Answering::Plain, options, vec!["first", "second"]);
+    let contents: Vec<_> = answers.into_iter().map(|answer| answer.unwrap().content).collect();
+    assert_eq!(contents, ["re: first", "re: second"]);
+    assert_eq!(open, 0);
+
+    let (answers, open) = ask(Answering::Plain, SpawnOptions::new(), vec!["hello?"]);
+    let timeout = RequestError::Timeout { receiver: "responder".into(), after: Duration::from_secs(1) };
+    assert_eq!(answers, [Err(timeout)]);
+    assert_eq!(open, 0);
+}
+
+/// Asks once, with a long timeout, but stops waiting for the answer after
+/// ten milliseconds and drops the request.
+struct Impatient { id: AgentId, asked: bool }
+
+#[async_trait]
+impl Agent for Impatient {
+    fn id(&self) -> &AgentId { &self.id }
+    async fn initialize(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
+    async fn execute(&mut self, ctx: &AgentContext) -> AgentResult<()> {
+        if !self.asked {
+            self.asked = true;
+            let request = ctx.request("responder", Performative::Query, "hello?", Duration::from_secs(60));
+            let _ = tokio::time::timeout(Duration::from_millis(10), request).await;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        Ok(())
+    }
+    async fn shutdown(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
+}
+
+#[test]
+fn a_dropped_request_closes_its_conversation() {
+    let scenario = async {
+        let runtime = Runtime::new();
+        let board = Switchboard::new();
+        let responder = Responder { id: AgentId::new(), answering: Answering::Silent };
+        board.spawn_reactive(&runtime, Box::new(responder), "responder").await?;
+        board.spawn(&runtime, Box::new(Impatient { id: AgentId::new(), asked: false }), "impatient").await?;
+        let idle = runtime.run_until_idle(&board, Duration::from_secs(5)).await;
+        let open = board.conversations().open_count();
+        runtime.shutdown().await?;
+        Ok::<_, RuntimeError>((idle.is_ok(), open))
+    };
+    let (outcome, _) = Simulation::new(1).run(scenario);
+    assert_eq!(outcome.expect("scenario failed"), (true, 0));
+}
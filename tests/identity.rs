@@ -0,0 +1,121 @@
+//! Sender ids carried with messages, the switchboard's name/id registry
+//! and its directory of capabilities.
+use agentropic_core::{Agent, AgentContext, AgentId, AgentResult};
+use agentropic_examples::{ContextExt, Performative, RuntimeExt, Simulation, Switchboard, TIMER};
+use agentropic_runtime::prelude::*;
+use async_trait::async_trait;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Says hello to "listener" once.
+struct Greeter { id: AgentId, greeted: bool }
+
+#[async_trait]
+impl Agent for Greeter {
+    fn id(&self) -> &AgentId { &self.id }
+    async fn initialize(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
+    async fn execute(&mut self, ctx: &AgentContext) -> AgentResult<()> {
+        if !self.greeted {
+            ctx.send("listener", Performative::Inform, "hello");
+            self.greeted = true;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        Ok(())
+    }
+    async fn shutdown(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
+}
+
+type Heard = Arc<Mutex<Vec<(String, Option<AgentId>)>>>;
+
+/// Records who sent each message, and schedules itself a timer event.
+struct Listener { id: AgentId, heard: Heard }
+
+#[async_trait]
+impl Agent for Listener {
+    fn id(&self) -> &AgentId { &self.id }
+    async fn initialize(&mut self, ctx: &AgentContext) -> AgentResult<()> {
+        ctx.schedule_after(Duration::from_millis(500), "tick")?;
+        Ok(())
+    }
+    async fn execute(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
+    async fn shutdown(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
+    async fn handle_message(&mut self, ctx: &AgentContext, sender: &str, _p: &str, _c: &str) -> AgentResult<()> {
+        self.heard.lock().unwrap().push((sender.to_string(), ctx.sender_id()));
+        Ok(())
+    }
+}
+
+#[test]
+fn messages_carry_the_sender_id() {
+    let heard = Heard::default();
+    let scenario = async {
+        let runtime = Runtime::new();
+        let board = Switchboard::new();
+        let listener = Listener { id: AgentId::new(), heard: heard.clone() };
+        let listener_id = board.spawn_reactive(&runtime, Box::new(listener), "listener").await?;
+        let greeter_id = board.spawn(&runtime, Box::new(Greeter { id: AgentId::new(), greeted: false }), "greeter").await?;
+        let _ = runtime.run_until(|| heard.lock().unwrap().len() == 2, Duration::from_secs(5)).await;
+        runtime.shutdown().await?;
+        Ok::<_, RuntimeError>((board, listener_id, greeter_id))
+    };
+    let (outcome, _) = Simulation::new(1).run(scenario);
+    let (board, listener_id, greeter_id) = outcome.expect("scenario failed");
+
+    let heard = heard.lock().unwrap().clone();
+    assert_eq!(heard, [("greeter".to_string(), Some(greeter_id)), (TIMER.to_string(), None)]);
+    assert_eq!(board.id_of("listener"), Some(listener_id));
+    assert_eq!(board.name_of(&greeter_id).as_deref(), Some("greeter"));
+    assert_eq!(board.id_of("nobody"), None);
+}
+
+/// Offers `capability` until it stops itself, fails or is shut down.
+struct Provider { id: AgentId, capability: &'static str, ending: Option<Result<(), &'static str>> }
+
+#[async_trait]
+impl Agent for Provider {
+    fn id(&self) -> &AgentId { &self.id }
+    async fn initialize(&mut self, ctx: &AgentContext) -> AgentResult<()> {
+        ctx.register_capability(self.capability)?;
+        Ok(())
+    }
+    async fn execute(&mut self, ctx: &AgentContext) -> AgentResult<()> {
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        match self.ending {
+            Some(Ok(())) => ctx.stop_self()?,
+            Some(Err(reason)) => return Err(agentropic_core::AgentError::ExecutionFailed(reason.into())),
+            None => {}
+        }
+        Ok(())
+    }
+    async fn shutdown(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
+}
+
+#[test]
+fn directory_drops_agents_that_exit() {
+    let scenario = async {
+        let runtime = Runtime::new();
+        let board = Switchboard::new();
+        for (name, capability, ending) in [
+            ("steady", "bidding", None),
+            ("retiring", "bidding", Some(Ok(()))),
+            ("crashing", "bidding", Some(Err("boom"))),
+            ("analyst", "risk-analysis", None),
+        ] {
+            board.spawn(&runtime, Box::new(Provider { id: AgentId::new(), capability, ending }), name).await?;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let before = board.find_agents("bidding");
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        let after = board.find_agents("bidding");
+        let analysts = board.find_agents("risk-analysis");
+        runtime.shutdown().await?;
+        Ok::<_, RuntimeError>((before, after, analysts, board))
+    };
+    let (outcome, _) = Simulation::new(1).run(scenario);
+    let (before, after, analysts, board) = outcome.expect("scenario failed");
+
+    assert_eq!(before, ["crashing", "retiring", "steady"]);
+    assert_eq!(after, ["steady"]);
+    assert_eq!(analysts, ["analyst"]);
+    assert_eq!(board.capabilities("steady"), Vec::<String>::new());
+}
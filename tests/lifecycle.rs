@@ -0,0 +1,503 @@
+//! Lifecycle events streamed to `main` and published to subscribed agents,
+//! graceful shutdown, and agents that spawn, stop and replace others.
+use agentropic_core::{Agent, AgentContext, AgentError, AgentId, AgentResult};
+use agentropic_examples::{
+    payload, ContextExt, ExitStatus, Lifecycle, LifecycleEvent, Performative, RuntimeExt, SendError, Shutdown,
+    Simulation, SpawnError, SpawnOptions, Switchboard, LIFECYCLE_TOPIC,
+};
+use agentropic_runtime::prelude::*;
+use async_trait::async_trait;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Fails its first `execute`, then stops itself.
+struct Crasher { id: AgentId, crashed: bool }
+
+#[async_trait]
+impl Agent for Crasher {
+    fn id(&self) -> &AgentId { &self.id }
+    async fn initialize(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
+    async fn execute(&mut self, ctx: &AgentContext) -> AgentResult<()> {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        if !std::mem::replace(&mut self.crashed, true) {
+            return Err(AgentError::ExecutionFailed("boom".into()));
+        }
+        ctx.stop_self()?;
+        Ok(())
+    }
+    async fn shutdown(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
+}
+
+type Heard = Arc<Mutex<Vec<LifecycleEvent>>>;
+
+/// Subscribes to lifecycle events and records them.
+struct Watcher { id: AgentId, heard: Heard }
+
+#[async_trait]
+impl Agent for Watcher {
+    fn id(&self) -> &AgentId { &self.id }
+    async fn initialize(&mut self, ctx: &AgentContext) -> AgentResult<()> {
+        ctx.subscribe(LIFECYCLE_TOPIC)?;
+        Ok(())
+    }
+    async fn execute(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
+    async fn shutdown(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
+    async fn handle_message(&mut self, _ctx: &AgentContext, sender: &str, _p: &str, content: &str) -> AgentResult<()> {
+        if sender == LIFECYCLE_TOPIC {
+            self.heard.lock().unwrap().push(payload::decode(content)?);
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn restarts_are_seen_by_main_and_by_subscribers() {
+    let heard = Heard::default();
+    let streamed = Heard::default();
+    let scenario = async {
+        let runtime = Runtime::new();
+        let board = Switchboard::new();
+        let watcher = Watcher { id: AgentId::new(), heard: heard.clone() };
+        board.spawn_reactive(&runtime, Box::new(watcher), "watcher").await?;
+        tokio::time::sleep(Duration::from_millis(10)).await;
+
+        let mut events = runtime.events(&board);
+        let collected = streamed.clone();
+        tokio::spawn(async move {
+            while let Some(event) = events.next().await {
+                collected.lock().unwrap().push(event);
+            }
+        });
+        let policy = RestartPolicy::new(RestartStrategy::OnFailure).with_backoff_seconds(1);
+        let crasher = Crasher { id: AgentId::new(), crashed: false };
+        board.spawn_with_policy(&runtime, Box::new(crasher), "crasher", policy).await?;
+        let status = tokio::time::timeout(Duration::from_secs(5), runtime.join(&board, "crasher")).await;
+        let _ = runtime.run_until_idle(&board, Duration::from_secs(5)).await;
+        runtime.shutdown().await?;
+        Ok::<_, RuntimeError>(status.ok().flatten())
+    };
+    let (outcome, _) = Simulation::new(1).run(scenario);
+    let status = outcome.expect("scenario failed");
+    assert_eq!(status, Some(ExitStatus::Completed));
+
+    let boom = AgentError::ExecutionFailed("boom".into()).to_string();
+    let expected = [
+        Lifecycle::Spawned,
+        Lifecycle::Initialized,
+        Lifecycle::Failed(boom),
+        Lifecycle::Restarting,
+        Lifecycle::Initialized,
+        Lifecycle::Stopped(ExitStatus::Completed),
+    ];
+    let of_crasher = |events: &Heard| -> Vec<Lifecycle> {
+        let events = events.lock().unwrap();
+        events.iter().filter(|event| event.agent == "crasher").map(|event| event.event.clone()).collect()
+    };
+    assert_eq!(of_crasher(&streamed), expected);
+    assert_eq!(of_crasher(&heard), expected);
+    // Agents do not hear about themselves
+    assert!(heard.lock().unwrap().iter().all(|event| event.agent != "watcher"));
+}
+
+/// Counts its calls, and stops itself from its first `execute`.
+struct Quitter { id: AgentId, calls: Arc<Mutex<Vec<&'static str>>> }
+
+#[async_trait]
+impl Agent for Quitter {
+    fn id(&self) -> &AgentId { &self.id }
+    async fn initialize(&mut self, _ctx: &AgentContext) -> AgentResult<()> {
+        self.calls.lock().unwrap().push("initialize");
+        Ok(())
+    }
+    async fn execute(&mut self, ctx: &AgentContext) -> AgentResult<()> {
+        self.calls.lock().unwrap().push("execute");
+        ctx.stop_self()?;
+        Ok(())
+    }
+    async fn shutdown(&mut self, _ctx: &AgentContext) -> AgentResult<()> {
+        self.calls.lock().unwrap().push("shutdown");
+        Ok(())
+    }
+}
+
+#[test]
+fn an_agent_that_stops_itself_completes_and_is_not_restarted() {
+    let calls = Arc::new(Mutex::new(Vec::new()));
+    let streamed = Heard::default();
+    let scenario = async {
+        let runtime = Runtime::new();
+        let board = Switchboard::new();
+        let mut events = runtime.events(&board);
+        let collected = streamed.clone();
+        tokio::spawn(async move {
+            while let Some(event) = events.next().await {
+                collected.lock().unwrap().push(event);
+            }
+        });
+        let policy = RestartPolicy::new(RestartStrategy::OnFailure).with_backoff_seconds(1);
+        let quitter = Quitter { id: AgentId::new(), calls: calls.clone() };
+        board.spawn_with_policy(&runtime, Box::new(quitter), "quitter", policy).await?;
+        let status = tokio::time::timeout(Duration::from_secs(5), runtime.join(&board, "quitter")).await;
+        // Long enough for any restart to have happened
+        tokio::time::sleep(Duration::from_secs(10)).await;
+        runtime.shutdown().await?;
+        Ok::<_, RuntimeError>(status)
+    };
+    let (outcome, _) = Simulation::new(1).run(scenario);
+    let status = outcome.expect("scenario failed").expect("join did not return");
+    assert_eq!(status, Some(ExitStatus::Completed));
+    assert_eq!(*calls.lock().unwrap(), ["initialize", "execute", "shutdown"]);
+    let events: Vec<Lifecycle> = streamed.lock().unwrap().iter().map(|event| event.event.clone()).collect();
+    assert_eq!(events, [Lifecycle::Spawned, Lifecycle::Initialized, Lifecycle::Stopped(ExitStatus::Completed)]);
+}
+
+/// Fails to handle anything but "ok", and records what it handled.
+struct Picky { id: AgentId, handled: Arc<Mutex<Vec<String>>> }
+
+#[async_trait]
+impl Agent for Picky {
+    fn id(&self) -> &AgentId { &self.id }
+    async fn initialize(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
+    async fn execute(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
+    async fn shutdown(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
+    async fn handle_message(&mut self, _ctx: &AgentContext, _s: &str, _p: &str, content: &str) -> AgentResult<()> {
+        if content != "ok" {
+            return Err(AgentError::ExecutionFailed(format!("cannot handle {}", content)));
+        }
+        self.handled.lock().unwrap().push(content.to_string());
+        Ok(())
+    }
+}
+
+/// Sends each of `messages` to "picky" from its first `execute`.
+struct Pester { id: AgentId, messages: Vec<&'static str> }
+
+#[async_trait]
+impl Agent for Pester {
+    fn id(&self) -> &AgentId { &self.id }
+    async fn initialize(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
+    async fn execute(&mut self, ctx: &AgentContext) -> AgentResult<()> {
+        for message in self.messages.drain(..) {
+            ctx.send("picky", Performative::Inform, message);
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        Ok(())
+    }
+    async fn shutdown(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
+}
+
+#[test]
+fn a_failed_handler_is_announced_and_the_agent_carries_on() {
+    let handled = Arc::new(Mutex::new(Vec::new()));
+    let streamed = Heard::default();
+    let scenario = async {
+        let runtime = Runtime::new();
+        let board = Switchboard::new();
+        let mut events = runtime.events(&board);
+        let collected = streamed.clone();
+        tokio::spawn(async move {
+            while let Some(event) = events.next().await {
+                collected.lock().unwrap().push(event);
+            }
+        });
+        let picky = Picky { id: AgentId::new(), handled: handled.clone() };
+        board.spawn_reactive(&runtime, Box::new(picky), "picky").await?;
+        board.spawn(&runtime, Box::new(Pester { id: AgentId::new(), messages: vec!["bad", "ok"] }), "pester").await?;
+        let _ = runtime.run_until_idle(&board, Duration::from_secs(5)).await;
+        runtime.shutdown().await
+    };
+    let (outcome, _) = Simulation::new(1).run(scenario);
+    outcome.expect("scenario failed");
+
+    assert_eq!(*handled.lock().unwrap(), ["ok"]);
+    let error = AgentError::ExecutionFailed("cannot handle bad".into()).to_string();
+    let failed = Lifecycle::HandlerFailed { sender: "pester".into(), error };
+    let events = streamed.lock().unwrap();
+    let of_picky: Vec<Lifecycle> =
+        events.iter().filter(|event| event.agent == "picky").map(|event| event.event.clone()).collect();
+    assert_eq!(of_picky, [Lifecycle::Spawned, Lifecycle::Initialized, failed, Lifecycle::Stopped(ExitStatus::Stopped)]);
+}
+
+type Log = Arc<Mutex<Vec<String>>>;
+
+/// Passes whatever it receives on to `next` after `work`, or logs it if
+/// there is no next, and sends `next` a job from its first `execute` if it
+/// `emits`. Logs its name when shut down, after lingering in `shutdown`
+/// for `linger`, and spends `busy` in every `execute`.
+struct Stage {
+    id: AgentId,
+    name: &'static str,
+    next: Option<&'static str>,
+    emits: bool,
+    work: Duration,
+    linger: Duration,
+    busy: Duration,
+    log: Log,
+}
+
+impl Stage {
+    fn new(name: &'static str, log: &Log) -> Self {
+        let (id, log, idle) = (AgentId::new(), log.clone(), Duration::ZERO);
+        Self { id, name, next: None, emits: false, work: idle, linger: idle, busy: idle, log }
+    }
+}
+
+#[async_trait]
+impl Agent for Stage {
+    fn id(&self) -> &AgentId { &self.id }
+    async fn initialize(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
+    async fn execute(&mut self, ctx: &AgentContext) -> AgentResult<()> {
+        if let (Some(next), true) = (self.next, std::mem::take(&mut self.emits)) {
+            ctx.send(next, Performative::Request, "job");
+        }
+        tokio::time::sleep(self.busy).await;
+        Ok(())
+    }
+    async fn shutdown(&mut self, _ctx: &AgentContext) -> AgentResult<()> {
+        tokio::time::sleep(self.linger).await;
+        self.log.lock().unwrap().push(format!("{} shut down", self.name));
+        Ok(())
+    }
+    async fn handle_message(&mut self, ctx: &AgentContext, _s: &str, _p: &str, content: &str) -> AgentResult<()> {
+        tokio::time::sleep(self.work).await;
+        match self.next {
+            Some(next) => ctx.send(next, Performative::Inform, content),
+            None => self.log.lock().unwrap().push(format!("{} got {}", self.name, content)),
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn graceful_shutdown_drains_then_stops_dependents_first() {
+    let log = Log::default();
+    let scenario = async {
+        let runtime = Runtime::new();
+        let board = Switchboard::new();
+        let reactive = || SpawnOptions::new().reactive();
+        board.spawn_with(&runtime, Box::new(Stage::new("sink", &log)), "sink", reactive()).await?;
+        let worker = Stage { next: Some("sink"), work: Duration::from_secs(1), ..Stage::new("worker", &log) };
+        board.spawn_with(&runtime, Box::new(worker), "worker", reactive().depends_on("sink")).await?;
+        let producer = Stage { next: Some("worker"), emits: true, ..Stage::new("producer", &log) };
+        board.spawn_with(&runtime, Box::new(producer), "producer", reactive().depends_on("worker")).await?;
+        let stuck = Stage { linger: Duration::from_secs(5), ..Stage::new("stuck", &log) };
+        board.spawn_with(&runtime, Box::new(stuck), "stuck", reactive()).await?;
+        let hog = Stage { busy: Duration::from_secs(10), ..Stage::new("hog", &log) };
+        board.spawn(&runtime, Box::new(hog), "hog").await?;
+        tokio::time::sleep(Duration::from_millis(10)).await;
+
+        let shutdown = Shutdown::new()
+            .with_drain_deadline(Duration::from_secs(3))
+            .with_stop_timeout(Duration::from_secs(1));
+        board.shutdown(&runtime, shutdown).await
+    };
+    let (outcome, _) = Simulation::new(1).run(scenario);
+    let report = outcome.expect("scenario failed");
+
+    assert!(report.drained);
+    assert_eq!(report.stopped, ["producer", "worker", "sink"]);
+    assert_eq!(report.timed_out, ["stuck"]);
+    assert_eq!(report.force_killed, ["hog"]);
+    assert!(!report.is_clean());
+    let log = log.lock().unwrap().clone();
+    let at = |entry: &str| log.iter().position(|line| line == entry);
+    assert!(at("sink got job").is_some());
+    assert!(at("worker shut down") < at("sink shut down"));
+    assert!(at("producer shut down") < at("worker shut down"));
+    assert_eq!(at("stuck shut down"), None);
+}
+
+/// Spawns `children` from `initialize`, stops the first of them from its
+/// first `execute` and itself from the next, recording what went wrong.
+struct Parent {
+    id: AgentId,
+    children: Vec<(&'static str, Stage)>,
+    executed: u32,
+    spawn_errors: Arc<Mutex<Vec<SpawnError>>>,
+    stop_errors: Arc<Mutex<Vec<SendError>>>,
+}
+
+#[async_trait]
+impl Agent for Parent {
+    fn id(&self) -> &AgentId { &self.id }
+    async fn initialize(&mut self, ctx: &AgentContext) -> AgentResult<()> {
+        for (name, child) in self.children.drain(..) {
+            let policy = RestartPolicy::new(RestartStrategy::Never);
+            if let Err(err) = ctx.spawn_child(Box::new(child), name, policy).await {
+                self.spawn_errors.lock().unwrap().push(err);
+            }
+        }
+        Ok(())
+    }
+    async fn execute(&mut self, ctx: &AgentContext) -> AgentResult<()> {
+        self.executed += 1;
+        match self.executed {
+            1 => {
+                for name in ["left", "nobody"] {
+                    if let Err(err) = ctx.stop(name) {
+                        self.stop_errors.lock().unwrap().push(err);
+                    }
+                }
+            }
+            2 => ctx.stop_self()?,
+            _ => {}
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        Ok(())
+    }
+    async fn shutdown(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
+}
+
+#[test]
+fn children_are_stopped_with_their_parent() {
+    let log = Log::default();
+    let spawn_errors = Arc::new(Mutex::new(Vec::new()));
+    let stop_errors = Arc::new(Mutex::new(Vec::new()));
+    let scenario = async {
+        let runtime = Runtime::new();
+        let board = Switchboard::new();
+        let child = |name| Stage { busy: Duration::from_millis(50), ..Stage::new(name, &log) };
+        let children = vec![("left", child("left")), ("right", child("right")), ("left", child("impostor"))];
+        let parent = Parent {
+            id: AgentId::new(),
+            children,
+            executed: 0,
+            spawn_errors: spawn_errors.clone(),
+            stop_errors: stop_errors.clone(),
+        };
+        board.spawn(&runtime, Box::new(parent), "parent").await?;
+        let statuses = async {
+            let parent = board.join("parent").await;
+            (parent, board.join("left").await, board.join("right").await)
+        };
+        let statuses = tokio::time::timeout(Duration::from_secs(5), statuses).await;
+        runtime.shutdown().await?;
+        Ok::<_, RuntimeError>((board, statuses.ok()))
+    };
+    let (outcome, _) = Simulation::new(1).run(scenario);
+    let (board, statuses) = outcome.expect("scenario failed");
+
+    let (parent, left, right) = statuses.expect("the family did not stop");
+    assert_eq!(parent, Some(ExitStatus::Completed));
+    assert_eq!(left, Some(ExitStatus::Stopped));
+    assert_eq!(right, Some(ExitStatus::Stopped));
+    assert_eq!(board.children("parent"), ["left", "right"]);
+    assert_eq!(board.parent("right").as_deref(), Some("parent"));
+    assert_eq!(*spawn_errors.lock().unwrap(), [SpawnError::NameTaken("left".into())]);
+    assert_eq!(*stop_errors.lock().unwrap(), [SendError::UnknownRecipient("nobody".into())]);
+    let log = log.lock().unwrap().clone();
+    assert_eq!(log, ["left shut down", "right shut down"]);
+}
+
+/// Spawns a [`Crasher`] named "child" under `policy` from `initialize`.
+struct Founder { id: AgentId, policy: Option<RestartPolicy> }
+
+#[async_trait]
+impl Agent for Founder {
+    fn id(&self) -> &AgentId { &self.id }
+    async fn initialize(&mut self, ctx: &AgentContext) -> AgentResult<()> {
+        if let Some(policy) = self.policy.take() {
+            let child = Crasher { id: AgentId::new(), crashed: false };
+            let spawned = ctx.spawn_child(Box::new(child), "child", policy).await;
+            spawned.map_err(|err| AgentError::InitializationFailed(err.to_string()))?;
+        }
+        Ok(())
+    }
+    async fn execute(&mut self, _ctx: &AgentContext) -> AgentResult<()> {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        Ok(())
+    }
+    async fn shutdown(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
+}
+
+/// How the crashing child of a [`Founder`] spawned with `strategy` exits.
+fn child_exit(strategy: RestartStrategy) -> Option<ExitStatus> {
+    let scenario = async {
+        let runtime = Runtime::new();
+        let board = Switchboard::new();
+        let policy = RestartPolicy::new(strategy).with_backoff_seconds(0);
+        board.spawn(&runtime, Box::new(Founder { id: AgentId::new(), policy: Some(policy) }), "founder").await?;
+        // Let the founder spawn its child
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        let status = tokio::time::timeout(Duration::from_secs(5), board.join("child")).await;
+        runtime.shutdown().await?;
+        Ok::<_, RuntimeError>(status.ok().flatten())
+    };
+    let (outcome, _) = Simulation::new(1).run(scenario);
+    outcome.expect("scenario failed")
+}
+
+#[test]
+fn a_failed_child_that_is_never_restarted_exits_failed() {
+    let status = child_exit(RestartStrategy::Never);
+    assert!(matches!(status, Some(ExitStatus::Failed(_))), "{:?}", status);
+    // Restarted, it stops itself the second time round
+    assert_eq!(child_exit(RestartStrategy::OnFailure), Some(ExitStatus::Completed));
+}
+
+/// Sends "worker" a job, and once it is handled another, then hands
+/// "worker" over to `replacement` and sends a third.
+struct Driver { id: AgentId, replacement: Option<Stage>, log: Log, sent: u32 }
+
+#[async_trait]
+impl Agent for Driver {
+    fn id(&self) -> &AgentId { &self.id }
+    async fn initialize(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
+    async fn execute(&mut self, ctx: &AgentContext) -> AgentResult<()> {
+        let handled = self.log.lock().unwrap().iter().any(|line| line == "v1 got one");
+        match self.sent {
+            0 => ctx.send("worker", Performative::Request, "one"),
+            1 if handled => {
+                ctx.send("worker", Performative::Request, "two");
+                let replacement = self.replacement.take().expect("replaced once");
+                ctx.replace("worker", Box::new(replacement))?;
+                ctx.send("worker", Performative::Request, "three");
+            }
+            _ => {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                return Ok(());
+            }
+        }
+        self.sent += 1;
+        Ok(())
+    }
+    async fn shutdown(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
+}
+
+#[test]
+fn a_replaced_agent_keeps_its_name_and_mailbox() {
+    let log = Log::default();
+    let streamed = Heard::default();
+    let scenario = async {
+        let runtime = Runtime::new();
+        let board = Switchboard::new();
+        let mut events = board.events();
+        let collected = streamed.clone();
+        tokio::spawn(async move {
+            while let Some(event) = events.next().await {
+                collected.lock().unwrap().push(event);
+            }
+        });
+
+        let replacement = Stage::new("v2", &log);
+        let replacement_id = *replacement.id();
+        board.spawn_reactive(&runtime, Box::new(Stage::new("v1", &log)), "worker").await?;
+        let driver = Driver { id: AgentId::new(), replacement: Some(replacement), log: log.clone(), sent: 0 };
+        board.spawn(&runtime, Box::new(driver), "driver").await?;
+        let done = || log.lock().unwrap().iter().any(|line| line == "v2 got three");
+        let _ = runtime.run_until(done, Duration::from_secs(5)).await;
+        runtime.shutdown().await?;
+        Ok::<_, RuntimeError>((board, replacement_id))
+    };
+    let (outcome, _) = Simulation::new(1).run(scenario);
+    let (board, replacement_id) = outcome.expect("scenario failed");
+
+    let log = log.lock().unwrap().clone();
+    assert_eq!(log[..4], ["v1 got one", "v1 shut down", "v2 got two", "v2 got three"]);
+    assert_eq!(board.id_of("worker"), Some(replacement_id));
+    assert_eq!(board.name_of(&replacement_id).as_deref(), Some("worker"));
+    let events = streamed.lock().unwrap();
+    let of_worker: Vec<_> = events.iter().filter(|event| event.agent == "worker").map(|event| &event.event).collect();
+    let expected = [Lifecycle::Spawned, Lifecycle::Initialized, Lifecycle::Replaced, Lifecycle::Initialized];
+    assert_eq!(of_worker[..4], expected.iter().collect::<Vec<_>>());
+}
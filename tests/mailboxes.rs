@@ -0,0 +1,492 @@
+//! Overflow policies, priority lanes, expiry, dead letters and budgets of
+//! managed mailboxes, run on the simulated clock.
+use agentropic_core::{Agent, AgentContext, AgentId, AgentResult};
+use agentropic_examples::{
+    Budget, ContextExt, DeadLetter, Lifecycle, MailboxConfig, MessageOptions, Overflow, Performative, Priority, RuntimeExt,
+    SendError, Simulation, SpawnOptions, Switchboard, Timeouts, Violation,
+};
+use agentropic_patterns::hierarchy::{Level, LevelType};
+use agentropic_runtime::prelude::*;
+use async_trait::async_trait;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Sends three messages at once and records what `try_send` said.
+struct Burst { id: AgentId, sent: bool, results: Arc<Mutex<Vec<Result<(), SendError>>>> }
+
+#[async_trait]
+impl Agent for Burst {
+    fn id(&self) -> &AgentId { &self.id }
+    async fn initialize(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
+    async fn execute(&mut self, ctx: &AgentContext) -> AgentResult<()> {
+        if !self.sent {
+            for n in 1..=3 {
+                let result = ctx.try_send("sink", Performative::Inform, &n.to_string());
+                self.results.lock().unwrap().push(result);
+            }
+            self.sent = true;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        Ok(())
+    }
+    async fn shutdown(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
+}
+
+/// Records every message it handles.
+struct Sink { id: AgentId, seen: Arc<Mutex<Vec<String>>> }
+
+#[async_trait]
+impl Agent for Sink {
+    fn id(&self) -> &AgentId { &self.id }
+    async fn initialize(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
+    async fn execute(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
+    async fn shutdown(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
+    async fn handle_message(&mut self, _ctx: &AgentContext, _s: &str, _p: &str, content: &str) -> AgentResult<()> {
+        self.seen.lock().unwrap().push(content.to_string());
+        Ok(())
+    }
+}
+
+struct Outcome { results: Vec<Result<(), SendError>>, seen: Vec<String>, dropped: u64, dead: Vec<DeadLetter> }
+
+fn burst_into(mailbox: MailboxConfig) -> Outcome {
+    let results = Arc::new(Mutex::new(Vec::new()));
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let scenario = async {
+        let runtime = Runtime::new();
+        let board = Switchboard::new();
+        let sink = Sink { id: AgentId::new(), seen: seen.clone() };
+        board.spawn_with(&runtime, Box::new(sink), "sink", SpawnOptions::new().reactive().with_mailbox(mailbox)).await?;
+        let burst = Burst { id: AgentId::new(), sent: false, results: results.clone() };
+        board.spawn(&runtime, Box::new(burst), "burst").await?;
+        let _ = runtime.run_until_idle(&board, Duration::from_secs(5)).await;
+        let dropped = board.mailbox("sink").map_or(0, |stats| stats.dropped);
+        runtime.shutdown().await?;
+        Ok::<_, RuntimeError>((dropped, board.dead_letters()))
+    };
+    let (outcome, _) = Simulation::new(1).run(scenario);
+    let (dropped, dead) = outcome.expect("scenario failed");
+    let results = results.lock().unwrap().clone();
+    let seen = seen.lock().unwrap().clone();
+    Outcome { results, seen, dropped, dead }
+}
+
+fn full() -> SendError {
+    SendError::MailboxFull { receiver: "sink".into(), capacity: 2 }
+}
+
+fn contents(dead: &[DeadLetter]) -> Vec<&str> {
+    dead.iter().map(|letter| letter.content.as_str()).collect()
+}
+
+#[test]
+fn unbounded_takes_everything() {
+    let outcome = burst_into(MailboxConfig::unbounded());
+    assert!(outcome.results.iter().all(Result::is_ok));
+    assert_eq!(outcome.seen, ["1", "2", "3"]);
+    assert_eq!(outcome.dropped, 0);
+    assert!(outcome.dead.is_empty());
+}
+
+#[test]
+fn reject_reports_full() {
+    let outcome = burst_into(MailboxConfig::bounded(2, Overflow::Reject));
+    assert_eq!(outcome.results, [Ok(()), Ok(()), Err(full())]);
+    assert_eq!(outcome.seen, ["1", "2"]);
+    assert_eq!(outcome.dropped, 1);
+    // The sender was told, so nothing is dead-lettered
+    assert!(outcome.dead.is_empty());
+}
+
+#[test]
+fn drop_newest_discards_silently() {
+    let outcome = burst_into(MailboxConfig::bounded(2, Overflow::DropNewest));
+    assert!(outcome.results.iter().all(Result::is_ok));
+    assert_eq!(outcome.seen, ["1", "2"]);
+    assert_eq!(outcome.dropped, 1);
+    assert_eq!(contents(&outcome.dead), ["3"]);
+    assert_eq!(outcome.dead[0].reason, full());
+}
+
+#[test]
+fn drop_oldest_makes_room() {
+    let outcome = burst_into(MailboxConfig::bounded(2, Overflow::DropOldest));
+    assert!(outcome.results.iter().all(Result::is_ok));
+    assert_eq!(outcome.seen, ["2", "3"]);
+    assert_eq!(outcome.dropped, 1);
+    assert_eq!(contents(&outcome.dead), ["1"]);
+}
+
+#[test]
+fn unknown_recipient_is_reported() {
+    let results = Arc::new(Mutex::new(Vec::new()));
+    let scenario = async {
+        let runtime = Runtime::new();
+        let board = Switchboard::new();
+        let burst = Burst { id: AgentId::new(), sent: false, results: results.clone() };
+        board.spawn(&runtime, Box::new(burst), "burst").await?;
+        let _ = runtime.run_until_idle(&board, Duration::from_secs(5)).await;
+        runtime.shutdown().await
+    };
+    let (outcome, _) = Simulation::new(1).run(scenario);
+    assert!(outcome.is_ok());
+    let results = results.lock().unwrap();
+    assert_eq!(results[0], Err(SendError::UnknownRecipient("sink".into())));
+}
+
+/// Sends with fire-and-forget `send` to a name nobody was spawned under.
+struct Stray { id: AgentId, sent: bool }
+
+#[async_trait]
+impl Agent for Stray {
+    fn id(&self) -> &AgentId { &self.id }
+    async fn initialize(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
+    async fn execute(&mut self, ctx: &AgentContext) -> AgentResult<()> {
+        if !self.sent {
+            ctx.send("nobody", Performative::Request, "hello?");
+            self.sent = true;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        Ok(())
+    }
+    async fn shutdown(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
+}
+
+#[test]
+fn unknown_recipient_is_dead_lettered_and_forwarded() {
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let scenario = async {
+        let runtime = Runtime::new();
+        let board = Switchboard::new();
+        board.forward_dead_letters("sink");
+        board.spawn_reactive(&runtime, Box::new(Sink { id: AgentId::new(), seen: seen.clone() }), "sink").await?;
+        board.spawn(&runtime, Box::new(Stray { id: AgentId::new(), sent: false }), "stray").await?;
+        let _ = runtime.run_until_idle(&board, Duration::from_secs(5)).await;
+        runtime.shutdown().await?;
+        Ok::<_, RuntimeError>(board.take_dead_letters())
+    };
+    let (outcome, _) = Simulation::new(1).run(scenario);
+    let dead = outcome.expect("scenario failed");
+    assert_eq!(dead.len(), 1);
+    assert_eq!(dead[0].sender, "stray");
+    assert_eq!(dead[0].receiver, "nobody");
+    assert_eq!(dead[0].performative, Performative::Request);
+    assert_eq!(dead[0].reason, SendError::UnknownRecipient("nobody".into()));
+    assert_eq!(*seen.lock().unwrap(), ["hello?"]);
+}
+
+/// Sends three messages in one go to a name nobody was spawned under.
+struct Strays { id: AgentId, options: MessageOptions, sent: bool }
+
+#[async_trait]
+impl Agent for Strays {
+    fn id(&self) -> &AgentId { &self.id }
+    async fn initialize(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
+    async fn execute(&mut self, ctx: &AgentContext) -> AgentResult<()> {
+        if !std::mem::replace(&mut self.sent, true) {
+            for n in 1..=3 {
+                ctx.send_with("nobody", Performative::Inform, &n.to_string(), self.options);
+            }
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        Ok(())
+    }
+    async fn shutdown(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
+}
+
+type Forwarded = Arc<Mutex<Vec<(String, Option<AgentId>, Option<Duration>)>>>;
+
+/// Records what it is forwarded, with who it came from and its time left.
+struct Collector { id: AgentId, seen: Forwarded }
+
+#[async_trait]
+impl Agent for Collector {
+    fn id(&self) -> &AgentId { &self.id }
+    async fn initialize(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
+    async fn execute(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
+    async fn shutdown(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
+    async fn handle_message(&mut self, ctx: &AgentContext, _s: &str, _p: &str, content: &str) -> AgentResult<()> {
+        self.seen.lock().unwrap().push((content.to_string(), ctx.sender_id(), ctx.time_left()));
+        Ok(())
+    }
+}
+
+#[test]
+fn forwarded_dead_letters_keep_their_sender_and_deadline() {
+    let seen = Forwarded::default();
+    let scenario = async {
+        let runtime = Runtime::new();
+        let board = Switchboard::new();
+        board.forward_dead_letters("sink");
+        let options = SpawnOptions::new().reactive().with_mailbox(MailboxConfig::bounded(1, Overflow::DropOldest));
+        let collector = Collector { id: AgentId::new(), seen: seen.clone() };
+        board.spawn_with(&runtime, Box::new(collector), "sink", options).await?;
+        let options = MessageOptions::new().with_ttl(Duration::from_secs(10));
+        let strays = Strays { id: AgentId::new(), options, sent: false };
+        let strays_id = board.spawn(&runtime, Box::new(strays), "strays").await?;
+        let _ = runtime.run_until_idle(&board, Duration::from_secs(5)).await;
+        runtime.shutdown().await?;
+        Ok::<_, RuntimeError>((board.dead_letters(), strays_id))
+    };
+    let (outcome, _) = Simulation::new(1).run(scenario);
+    let (dead, strays_id) = outcome.expect("scenario failed");
+
+    let seen = seen.lock().unwrap().clone();
+    assert_eq!(seen.len(), 1);
+    assert_eq!((seen[0].0.as_str(), seen[0].1), ("3", Some(strays_id)));
+    assert!(seen[0].2.is_some());
+    // The letters the sink's mailbox dropped to make room are dead letters of the sink
+    let receivers: Vec<(&str, &str)> =
+        dead.iter().map(|letter| (letter.receiver.as_str(), letter.content.as_str())).collect();
+    assert_eq!(receivers, [("nobody", "1"), ("sink", "1"), ("nobody", "2"), ("sink", "2"), ("nobody", "3")]);
+    assert!(dead.iter().all(|letter| letter.sender_id == Some(strays_id) && letter.deadline.is_some()));
+    assert_eq!(dead[1].reason, SendError::MailboxFull { receiver: "sink".into(), capacity: 1 });
+}
+
+/// Sends every message in one go, so they all queue before the sink runs.
+struct Script { id: AgentId, sends: Vec<(Performative, MessageOptions, String)> }
+
+#[async_trait]
+impl Agent for Script {
+    fn id(&self) -> &AgentId { &self.id }
+    async fn initialize(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
+    async fn execute(&mut self, ctx: &AgentContext) -> AgentResult<()> {
+        for (performative, options, content) in self.sends.drain(..) {
+            ctx.send_with("sink", performative, &content, options);
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        Ok(())
+    }
+    async fn shutdown(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
+}
+
+/// The order in which the sink handles `sends` from an agent named "boss".
+fn handled_order(sends: Vec<(Performative, MessageOptions, String)>, ranked: bool) -> Vec<String> {
+    handle_all(sends, ranked, MailboxConfig::unbounded()).0
+}
+
+/// What the sink with `mailbox` handles of `sends`, in order, and the dead letters.
+fn handle_all(
+    sends: Vec<(Performative, MessageOptions, String)>,
+    ranked: bool,
+    mailbox: MailboxConfig,
+) -> (Vec<String>, Vec<DeadLetter>) {
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let scenario = async {
+        let runtime = Runtime::new();
+        let board = Switchboard::new();
+        if ranked {
+            board.set_level("boss", &Level::new("Command", LevelType::Strategic, 2));
+            board.set_level("sink", &Level::new("Field", LevelType::Operational, 1));
+        }
+        let sink = Sink { id: AgentId::new(), seen: seen.clone() };
+        board.spawn_with(&runtime, Box::new(sink), "sink", SpawnOptions::new().reactive().with_mailbox(mailbox)).await?;
+        board.spawn(&runtime, Box::new(Script { id: AgentId::new(), sends }), "boss").await?;
+        let _ = runtime.run_until_idle(&board, Duration::from_secs(5)).await;
+        runtime.shutdown().await?;
+        Ok::<_, RuntimeError>(board.dead_letters())
+    };
+    let (outcome, _) = Simulation::new(1).run(scenario);
+    let dead = outcome.expect("scenario failed");
+    let seen = seen.lock().unwrap().clone();
+    (seen, dead)
+}
+
+fn inform(priority: Priority, content: &str) -> (Performative, MessageOptions, String) {
+    (Performative::Inform, MessageOptions::new().with_priority(priority), content.to_string())
+}
+
+#[test]
+fn higher_lanes_are_handled_first() {
+    let sends = vec![
+        inform(Priority::Low, "low"),
+        inform(Priority::Normal, "normal"),
+        inform(Priority::Urgent, "urgent"),
+        inform(Priority::High, "high"),
+        inform(Priority::Normal, "normal again"),
+    ];
+    assert_eq!(handled_order(sends, false), ["urgent", "high", "normal", "normal again", "low"]);
+}
+
+#[test]
+fn waiting_letters_are_not_starved() {
+    let mut sends = vec![inform(Priority::Low, "low")];
+    sends.extend((0..20).map(|n| inform(Priority::High, &n.to_string())));
+    let order = handled_order(sends, false);
+    // Eight high-priority letters pass the low one before it gets its turn
+    assert_eq!(order.iter().position(|content| content == "low"), Some(8));
+}
+
+#[test]
+fn urgent_letters_always_go_first() {
+    let mut sends = vec![inform(Priority::Low, "low")];
+    sends.extend((0..20).map(|n| inform(Priority::Urgent, &n.to_string())));
+    let order = handled_order(sends, false);
+    assert_eq!(order.last().map(String::as_str), Some("low"));
+}
+
+#[test]
+fn drop_oldest_never_discards_a_higher_priority_letter() {
+    let sends = vec![
+        inform(Priority::Normal, "normal"),
+        inform(Priority::High, "high"),
+        inform(Priority::Low, "low"),
+        inform(Priority::Urgent, "urgent"),
+    ];
+    let (seen, dead) = handle_all(sends, false, MailboxConfig::bounded(2, Overflow::DropOldest));
+    assert_eq!(seen, ["urgent", "high"]);
+    // Everything queued outranked "low", so it was dropped instead; "urgent" made room by evicting "normal"
+    assert_eq!(contents(&dead), ["low", "normal"]);
+}
+
+#[test]
+fn requests_down_the_hierarchy_jump_the_queue() {
+    let sends = || vec![
+        inform(Priority::Normal, "report"),
+        (Performative::Request, MessageOptions::new(), "order".to_string()),
+    ];
+    assert_eq!(handled_order(sends(), false), ["report", "order"]);
+    assert_eq!(handled_order(sends(), true), ["order", "report"]);
+}
+
+/// Each message handled, with the time it had left.
+type Handled = Arc<Mutex<Vec<(String, Option<Duration>)>>>;
+
+/// Takes a second over each message and notes how long it had left.
+struct SlowSink { id: AgentId, seen: Handled }
+
+#[async_trait]
+impl Agent for SlowSink {
+    fn id(&self) -> &AgentId { &self.id }
+    async fn initialize(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
+    async fn execute(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
+    async fn shutdown(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
+    async fn handle_message(&mut self, ctx: &AgentContext, _s: &str, _p: &str, content: &str) -> AgentResult<()> {
+        self.seen.lock().unwrap().push((content.to_string(), ctx.time_left()));
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        Ok(())
+    }
+}
+
+#[test]
+fn expired_letters_are_dead_lettered() {
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let scenario = async {
+        let runtime = Runtime::new();
+        let board = Switchboard::new();
+        board.spawn_reactive(&runtime, Box::new(SlowSink { id: AgentId::new(), seen: seen.clone() }), "sink").await?;
+        let expiring = MessageOptions::new().with_ttl(Duration::from_millis(1500));
+        let sends = vec![
+            (Performative::Inform, expiring, "1".to_string()),
+            (Performative::Inform, expiring, "2".to_string()),
+            (Performative::Inform, expiring, "3".to_string()),
+            (Performative::Inform, MessageOptions::new(), "no deadline".to_string()),
+        ];
+        board.spawn(&runtime, Box::new(Script { id: AgentId::new(), sends }), "boss").await?;
+        let _ = runtime.run_until_idle(&board, Duration::from_secs(10)).await;
+        runtime.shutdown().await?;
+        Ok::<_, RuntimeError>(board.dead_letters())
+    };
+    let (outcome, _) = Simulation::new(1).run(scenario);
+    let dead = outcome.expect("scenario failed");
+    let seen = seen.lock().unwrap().clone();
+    let handled: Vec<&str> = seen.iter().map(|(content, _)| content.as_str()).collect();
+    assert_eq!(handled, ["1", "2", "no deadline"]);
+    assert!(seen[0].1.is_some_and(|left| left > seen[1].1.unwrap()));
+    assert_eq!(seen[2].1, None);
+    assert_eq!(contents(&dead), ["3"]);
+    assert_eq!(dead[0].reason, SendError::Expired("sink".into()));
+}
+
+/// Spends a tenth of a second per character of each message, and notes
+/// when it finished with it.
+struct Plodder { id: AgentId, started: tokio::time::Instant, seen: Handled }
+
+#[async_trait]
+impl Agent for Plodder {
+    fn id(&self) -> &AgentId { &self.id }
+    async fn initialize(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
+    async fn execute(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
+    async fn shutdown(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
+    async fn handle_message(&mut self, _ctx: &AgentContext, _s: &str, _p: &str, content: &str) -> AgentResult<()> {
+        tokio::time::sleep(Duration::from_millis(100) * content.len() as u32).await;
+        self.seen.lock().unwrap().push((content.to_string(), Some(self.started.elapsed())));
+        Ok(())
+    }
+}
+
+#[test]
+fn budgets_cancel_slow_handlers_and_hold_back_floods() {
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let scenario = async {
+        let runtime = Runtime::new();
+        let board = Switchboard::new();
+        let mut stream = board.events();
+        let collected = events.clone();
+        tokio::spawn(async move {
+            while let Some(event) = stream.next().await {
+                if let Lifecycle::OverBudget(violation) = event.event {
+                    collected.lock().unwrap().push(violation);
+                }
+            }
+        });
+
+        let budget = Budget::new().with_max_handler(Duration::from_millis(500)).with_max_rate(3);
+        let sink = Plodder { id: AgentId::new(), started: tokio::time::Instant::now(), seen: seen.clone() };
+        board.spawn_with(&runtime, Box::new(sink), "sink", SpawnOptions::new().reactive().with_budget(budget)).await?;
+        let sends = ["a", "a very long story", "b", "c", "d"];
+        let sends = sends.iter().map(|content| inform(Priority::Normal, content)).collect();
+        board.spawn(&runtime, Box::new(Script { id: AgentId::new(), sends }), "boss").await?;
+        let _ = runtime.run_until_idle(&board, Duration::from_secs(10)).await;
+        runtime.shutdown().await?;
+        Ok::<_, RuntimeError>(board.usage("sink"))
+    };
+    let (outcome, _) = Simulation::new(1).run(scenario);
+    let usage = outcome.expect("scenario failed").expect("the sink is managed");
+    let seen = seen.lock().unwrap().clone();
+    let handled: Vec<&str> = seen.iter().map(|(content, _)| content.as_str()).collect();
+    // The long story was cancelled half way through, and the sink moved on
+    assert_eq!(handled, ["a", "b", "c", "d"]);
+    // Only three a second: "c" waited for the next second
+    assert!(seen[2].1.unwrap() >= Duration::from_secs(1), "{:?}", seen);
+    assert!(seen[1].1.unwrap() < Duration::from_secs(1), "{:?}", seen);
+
+    assert_eq!(usage.messages, 5);
+    assert_eq!(usage.queued_bytes, 0);
+    assert_eq!(usage.violations, 2);
+    let events = events.lock().unwrap().clone();
+    assert_eq!(events, [
+        Violation::SlowHandler { sender: "boss".into(), limit: Duration::from_millis(500) },
+        Violation::Throttled { limit: 3 },
+    ]);
+}
+
+/// How many times a plodder with a one second handler budget and the
+/// handler `timeout` went over its budget, after one slow message.
+fn violations_with_timeout(timeout: Duration) -> u64 {
+    let scenario = async {
+        let runtime = Runtime::new();
+        let board = Switchboard::new();
+        let options = SpawnOptions::new()
+            .reactive()
+            .with_budget(Budget::new().with_max_handler(Duration::from_secs(1)))
+            .with_timeouts(Timeouts::new().with_handle_message(timeout));
+        let sink = Plodder { id: AgentId::new(), started: tokio::time::Instant::now(), seen: Handled::default() };
+        board.spawn_with(&runtime, Box::new(sink), "sink", options).await?;
+        let sends = vec![inform(Priority::Normal, "a very long story")];
+        board.spawn(&runtime, Box::new(Script { id: AgentId::new(), sends }), "boss").await?;
+        let _ = runtime.run_until_idle(&board, Duration::from_secs(10)).await;
+        runtime.shutdown().await?;
+        Ok::<_, RuntimeError>(board.usage("sink"))
+    };
+    let (outcome, _) = Simulation::new(1).run(scenario);
+    outcome.expect("scenario failed").expect("the sink is managed").violations
+}
+
+#[test]
+fn only_limits_set_by_the_budget_count_against_it() {
+    assert_eq!(violations_with_timeout(Duration::from_millis(500)), 0);
+    assert_eq!(violations_with_timeout(Duration::from_secs(1)), 1);
+    assert_eq!(violations_with_timeout(Duration::from_secs(2)), 1);
+}
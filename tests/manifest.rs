@@ -0,0 +1,147 @@
+//! Building a topology from a JSON or TOML manifest and an agent registry.
+use agentropic_core::{Agent, AgentContext, AgentId, AgentResult};
+use agentropic_examples::{
+    AgentRegistry, ContextExt, FromManifest, Manifest, ManifestError, Performative, RuntimeBuilder, RuntimeExt,
+    Simulation,
+};
+use agentropic_runtime::prelude::*;
+use async_trait::async_trait;
+use serde::Deserialize;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+const TOML: &str = r#"
+[[agents]]
+name = "herald"
+kind = "herald"
+params = { topic = "news", news = "hello" }
+
+[[agents]]
+name = "reader"
+kind = "reader"
+reactive = true
+mailbox = 4
+overflow = "drop_oldest"
+subscribe = ["news"]
+depends_on = ["herald"]
+restart = { strategy = "on_failure", max_retries = 2 }
+"#;
+
+const JSON: &str = r#"{
+  "agents": [
+    { "name": "herald", "kind": "herald", "params": { "topic": "news", "news": "hello" } },
+    {
+      "name": "reader", "kind": "reader", "reactive": true, "mailbox": 4, "overflow": "drop_oldest",
+      "subscribe": ["news"], "depends_on": ["herald"], "restart": { "strategy": "on_failure", "max_retries": 2 }
+    }
+  ]
+}"#;
+
+#[derive(Deserialize)]
+struct HeraldParams { topic: String, news: String }
+
+/// Publishes one piece of news.
+struct Herald { id: AgentId, params: HeraldParams, published: bool }
+
+impl FromManifest for Herald {
+    const KIND: &'static str = "herald";
+    type Params = HeraldParams;
+    fn from_params(params: HeraldParams) -> Self { Self { id: AgentId::new(), params, published: false } }
+}
+
+#[async_trait]
+impl Agent for Herald {
+    fn id(&self) -> &AgentId { &self.id }
+    async fn initialize(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
+    async fn execute(&mut self, ctx: &AgentContext) -> AgentResult<()> {
+        if !self.published {
+            ctx.publish(&self.params.topic, Performative::Inform, &self.params.news)?;
+            self.published = true;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        Ok(())
+    }
+    async fn shutdown(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
+}
+
+type Heard = Arc<Mutex<Vec<String>>>;
+
+/// Records what it is told, into a log shared with the test.
+struct Reader { id: AgentId, heard: Heard }
+
+#[async_trait]
+impl Agent for Reader {
+    fn id(&self) -> &AgentId { &self.id }
+    async fn initialize(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
+    async fn execute(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
+    async fn shutdown(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
+    async fn handle_message(&mut self, _ctx: &AgentContext, _s: &str, _p: &str, content: &str) -> AgentResult<()> {
+        self.heard.lock().unwrap().push(content.to_string());
+        Ok(())
+    }
+}
+
+fn registry(heard: &Heard) -> AgentRegistry {
+    let heard = heard.clone();
+    AgentRegistry::new()
+        .register::<Herald>()
+        .register_with("reader", move |_: serde_json::Value| Reader { id: AgentId::new(), heard: heard.clone() })
+}
+
+/// Write `contents` to a manifest file unique to this test run.
+fn manifest_file(name: &str, contents: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("agentropic-{}-{}", std::process::id(), name));
+    std::fs::write(&path, contents).unwrap();
+    path
+}
+
+#[test]
+fn json_and_toml_manifests_read_the_same() {
+    let toml = Manifest::from_file(manifest_file("same.toml", TOML)).unwrap();
+    let json = Manifest::from_file(manifest_file("same.json", JSON)).unwrap();
+    assert_eq!(toml, json);
+    assert_eq!(toml.agents.len(), 2);
+
+    let path = manifest_file("agents.yaml", TOML);
+    assert_eq!(Manifest::from_file(&path), Err(ManifestError::UnsupportedFormat(path)));
+}
+
+#[test]
+fn the_builder_spawns_the_manifest_with_its_options() {
+    let heard = Heard::default();
+    let path = manifest_file("spawn.toml", TOML);
+    let scenario = async {
+        let builder = RuntimeBuilder::new(registry(&heard)).load(&path).expect("manifest loads");
+        assert_eq!(builder.names(), ["herald", "reader"]);
+        let (runtime, board) = builder.build().await?;
+        let _ = runtime.run_until(|| !heard.lock().unwrap().is_empty(), Duration::from_secs(5)).await;
+        // Exited agents no longer count as subscribers
+        let subscribers = board.subscribers("news");
+        runtime.shutdown().await?;
+        Ok::<_, RuntimeError>((board, subscribers))
+    };
+    let (outcome, _) = Simulation::new(3).run(scenario);
+    let (board, subscribers) = outcome.unwrap();
+
+    assert_eq!(*heard.lock().unwrap(), ["hello"]);
+    assert_eq!(subscribers, ["reader"]);
+    assert_eq!(board.mailbox("reader").and_then(|stats| stats.capacity), Some(4));
+    assert_eq!(board.mailbox("herald").and_then(|stats| stats.capacity), None);
+}
+
+#[test]
+fn unknown_kinds_and_bad_params_are_caught_before_anything_runs() {
+    let heard = Heard::default();
+    let unknown = manifest_file("unknown.toml", "[[agents]]\nname = \"scribe\"\nkind = \"scribe\"\n");
+    let error = RuntimeBuilder::new(registry(&heard)).load(unknown).err();
+    assert_eq!(error, Some(ManifestError::UnknownKind { agent: "scribe".into(), kind: "scribe".into() }));
+
+    let missing = manifest_file("params.json", r#"{ "agents": [{ "name": "crier", "kind": "herald" }] }"#);
+    let error = RuntimeBuilder::new(registry(&heard)).load(missing).err();
+    assert!(matches!(error, Some(ManifestError::InvalidParams { agent, .. }) if agent == "crier"));
+
+    let error = RuntimeBuilder::new(registry(&heard)).load(manifest_file("twice.toml", TOML)).unwrap();
+    let error = error.load(manifest_file("again.json", JSON)).err();
+    assert_eq!(error, Some(ManifestError::DuplicateName("herald".into())));
+}
@@ -0,0 +1,124 @@
+//! Built-in middleware, and middleware on a switchboard run on the simulated clock.
+use agentropic_core::{Agent, AgentContext, AgentId, AgentResult};
+use agentropic_examples::{
+    ContentFilter, ContextExt, Message, Middleware, Performative, Priority, RuntimeExt, SendError, Simulation,
+    Switchboard, Verdict,
+};
+use agentropic_runtime::prelude::*;
+use async_trait::async_trait;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+fn message(content: &str) -> Message {
+    Message {
+        sender: "a".into(),
+        receiver: "b".into(),
+        performative: Performative::Inform,
+        priority: Priority::Normal,
+        content: content.into(),
+    }
+}
+
+#[test]
+fn content_filter_blocks_matching_words() {
+    let filter = ContentFilter::block(&["password"]);
+    let mut clean = message("all good");
+    assert_eq!(filter.on_send(&mut clean), Verdict::Deliver);
+    let mut leaky = message("my PassWord is hunter2");
+    assert!(matches!(filter.on_send(&mut leaky), Verdict::Drop(_)));
+}
+
+#[test]
+fn content_filter_redacts_matching_words() {
+    let filter = ContentFilter::redact(&["hunter2", "secret"]);
+    let mut leaky = message("the Secret is hunter2");
+    assert_eq!(filter.on_send(&mut leaky), Verdict::Deliver);
+    assert_eq!(leaky.content, "the ****** is *******");
+}
+
+#[test]
+fn empty_words_filter_nothing_even_when_lowercasing_changes_lengths() {
+    let filter = ContentFilter::block(&["", "password"]);
+    // "İ" lowercases to three bytes, so whole messages are matched
+    let mut turkish = message("İstanbul");
+    assert_eq!(filter.on_send(&mut turkish), Verdict::Deliver);
+    let mut leaky = message("İstanbul password");
+    assert!(matches!(filter.on_send(&mut leaky), Verdict::Drop(_)));
+
+    let filter = ContentFilter::redact(&[""]);
+    let mut turkish = message("İstanbul");
+    assert_eq!(filter.on_send(&mut turkish), Verdict::Deliver);
+    assert_eq!(turkish.content, "İstanbul");
+}
+
+/// Sends each content to "sink" in one go.
+struct Sender { id: AgentId, contents: Vec<&'static str> }
+
+#[async_trait]
+impl Agent for Sender {
+    fn id(&self) -> &AgentId { &self.id }
+    async fn initialize(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
+    async fn execute(&mut self, ctx: &AgentContext) -> AgentResult<()> {
+        for content in self.contents.drain(..) {
+            ctx.send("sink", Performative::Inform, content);
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        Ok(())
+    }
+    async fn shutdown(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
+}
+
+/// Records every message it handles, with when it arrived.
+struct Sink { id: AgentId, seen: Arc<Mutex<Vec<(String, Duration)>>>, started: tokio::time::Instant }
+
+#[async_trait]
+impl Agent for Sink {
+    fn id(&self) -> &AgentId { &self.id }
+    async fn initialize(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
+    async fn execute(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
+    async fn shutdown(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
+    async fn handle_message(&mut self, _ctx: &AgentContext, _s: &str, _p: &str, content: &str) -> AgentResult<()> {
+        self.seen.lock().unwrap().push((content.to_string(), self.started.elapsed()));
+        Ok(())
+    }
+}
+
+/// Holds back messages marked "later" by a second and shouts the rest.
+struct Shout;
+
+impl Middleware for Shout {
+    fn on_send(&self, message: &mut Message) -> Verdict {
+        if message.content == "later" {
+            return Verdict::Delay(Duration::from_secs(1));
+        }
+        message.content = message.content.to_uppercase();
+        Verdict::Deliver
+    }
+}
+
+#[test]
+fn middleware_rewrites_delays_and_drops() {
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let scenario = async {
+        let runtime = Runtime::new();
+        let board = Switchboard::new();
+        board.add_middleware(ContentFilter::block(&["spam"]));
+        board.add_middleware(Shout);
+        let sink = Sink { id: AgentId::new(), seen: seen.clone(), started: tokio::time::Instant::now() };
+        board.spawn_reactive(&runtime, Box::new(sink), "sink").await?;
+        let sender = Sender { id: AgentId::new(), contents: vec!["later", "buy spam", "hello"] };
+        board.spawn(&runtime, Box::new(sender), "sender").await?;
+        let _ = runtime.run_until_idle(&board, Duration::from_secs(5)).await;
+        runtime.shutdown().await?;
+        Ok::<_, RuntimeError>(board.dead_letters())
+    };
+    let (outcome, _) = Simulation::new(1).run(scenario);
+    let dead = outcome.expect("scenario failed");
+    let seen = seen.lock().unwrap().clone();
+    let contents: Vec<&str> = seen.iter().map(|(content, _)| content.as_str()).collect();
+    assert_eq!(contents, ["HELLO", "later"]);
+    assert!(seen[1].1 >= Duration::from_secs(1));
+    assert_eq!(dead.len(), 1);
+    assert_eq!(dead[0].content, "buy spam");
+    assert_eq!(dead[0].reason, SendError::Filtered("blocked by content filter".into()));
+}
@@ -0,0 +1,92 @@
+//! Shared payload types and decoding them from message content.
+use agentropic_core::{Agent, AgentContext, AgentId, AgentResult};
+use agentropic_examples::payload::{self, Bid, TaskAssignment};
+use agentropic_examples::{ContextExt, Performative, RuntimeExt, Simulation, Switchboard, TypedHandler};
+use agentropic_runtime::prelude::*;
+use async_trait::async_trait;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+#[test]
+fn payloads_round_trip_and_malformed_content_is_an_error() {
+    let bid = Bid { item: "lamp".into(), amount: 12.5 };
+    assert_eq!(payload::decode::<Bid>(&payload::encode(&bid).unwrap()).unwrap(), bid);
+
+    for content in ["bid:12", "", "{\"item\": \"lamp\"}", "{\"item\": 3, \"amount\": 1}"] {
+        let err = payload::decode::<Bid>(content).unwrap_err();
+        assert!(err.to_string().starts_with("malformed Bid payload"), "{}", err);
+    }
+}
+
+#[test]
+fn fields_added_by_newer_senders_are_ignored() {
+    let content = r#"{"task": "audit", "executor": "executor_1", "priority": "high"}"#;
+    let assignment: TaskAssignment = payload::decode(content).unwrap();
+    assert_eq!(assignment, TaskAssignment { task: "audit".into(), executor: "executor_1".into() });
+}
+
+type Bids = Arc<Mutex<Vec<Bid>>>;
+
+/// Records the bids it receives.
+struct Auctioneer { id: AgentId, bids: Bids }
+
+#[async_trait]
+impl Agent for Auctioneer {
+    fn id(&self) -> &AgentId { &self.id }
+    async fn initialize(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
+    async fn execute(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
+    async fn shutdown(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
+    async fn handle_message(&mut self, ctx: &AgentContext, sender: &str, perf: &str, content: &str) -> AgentResult<()> {
+        self.handle_payload(ctx, sender, perf, content).await
+    }
+}
+
+#[async_trait]
+impl TypedHandler for Auctioneer {
+    type Payload = Bid;
+
+    async fn handle_typed_message(
+        &mut self, _ctx: &AgentContext, _sender: &str, _perf: Performative, bid: Bid,
+    ) -> AgentResult<()> {
+        self.bids.lock().unwrap().push(bid);
+        Ok(())
+    }
+}
+
+/// Sends an old-style string bid, then a typed one.
+struct Bidder { id: AgentId, sent: bool }
+
+#[async_trait]
+impl Agent for Bidder {
+    fn id(&self) -> &AgentId { &self.id }
+    async fn initialize(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
+    async fn execute(&mut self, ctx: &AgentContext) -> AgentResult<()> {
+        if !std::mem::replace(&mut self.sent, true) {
+            ctx.send("auctioneer", Performative::Propose, "bid:12");
+            ctx.send_payload("auctioneer", Performative::Propose, &Bid { item: "lamp".into(), amount: 12.0 })?;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        Ok(())
+    }
+    async fn shutdown(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
+}
+
+#[test]
+fn a_malformed_message_is_refused_and_the_next_one_handled() {
+    let bids = Bids::default();
+    let scenario = async {
+        let runtime = Runtime::new();
+        let board = Switchboard::new();
+        let auctioneer = Auctioneer { id: AgentId::new(), bids: bids.clone() };
+        board.spawn_reactive(&runtime, Box::new(auctioneer), "auctioneer").await?;
+        board.spawn(&runtime, Box::new(Bidder { id: AgentId::new(), sent: false }), "bidder").await?;
+        let _ = runtime.run_until_idle(&board, Duration::from_secs(5)).await;
+        let status = board.exit_status("auctioneer");
+        runtime.shutdown().await?;
+        Ok::<_, RuntimeError>(status)
+    };
+    let (outcome, _) = Simulation::new(1).run(scenario);
+
+    assert_eq!(outcome.expect("scenario failed"), None);
+    assert_eq!(*bids.lock().unwrap(), [Bid { item: "lamp".into(), amount: 12.0 }]);
+}
@@ -0,0 +1,36 @@
+//! Parsing and printing the typed performatives.
+use agentropic_examples::{Performative, UnknownPerformative};
+
+#[test]
+fn every_performative_round_trips_through_its_wire_name() {
+    for performative in Performative::ALL {
+        assert_eq!(performative.as_str().parse(), Ok(performative));
+        assert_eq!(performative.to_string().parse(), Ok(performative));
+    }
+}
+
+#[test]
+fn parsing_ignores_case_separators_and_fipa_aliases() {
+    let cases = [
+        ("CFP", Performative::Cfp),
+        ("Propose", Performative::Propose),
+        ("not-understood", Performative::NotUnderstood),
+        ("NOT_UNDERSTOOD", Performative::NotUnderstood),
+        ("call-for-proposal", Performative::Cfp),
+        ("accept-proposal", Performative::Accept),
+        ("reject_proposal", Performative::Reject),
+        ("query-if", Performative::Query),
+        ("query-ref", Performative::Query),
+    ];
+    for (text, performative) in cases {
+        assert_eq!(text.parse(), Ok(performative), "{}", text);
+    }
+}
+
+#[test]
+fn unknown_performatives_are_rejected() {
+    for text in ["", "gossip", "informal", "in form", "propose!"] {
+        let parsed: Result<Performative, _> = text.parse();
+        assert_eq!(parsed, Err(UnknownPerformative(text.to_string())));
+    }
+}
@@ -0,0 +1,218 @@
+//! FIPA protocol roles played out between managed agents on the simulated clock.
+use agentropic_core::{Agent, AgentContext, AgentId, AgentResult};
+use agentropic_examples::protocol::{
+    ContractNet, ContractNetParticipant, Outcome, QueryIf, QueryParticipant, RequestAction, RequestParticipant,
+};
+use agentropic_examples::{ContextExt, Performative, RequestError, RuntimeExt, SendError, Simulation, Switchboard};
+use agentropic_runtime::prelude::*;
+use async_trait::async_trait;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+type Start = Box<dyn FnOnce() -> Pin<Box<dyn Future<Output = ()> + Send>> + Send + Sync>;
+
+/// Starts a protocol from its first `execute`.
+struct Initiator { id: AgentId, start: Option<Start> }
+
+#[async_trait]
+impl Agent for Initiator {
+    fn id(&self) -> &AgentId { &self.id }
+    async fn initialize(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
+    async fn execute(&mut self, _ctx: &AgentContext) -> AgentResult<()> {
+        if let Some(start) = self.start.take() {
+            tokio::spawn(start());
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        Ok(())
+    }
+    async fn shutdown(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
+}
+
+/// Bids `bid` on any task, and does the work unless it is `broken`. Agrees
+/// to any action but "fly", and knows only that the sky is blue.
+struct Worker { id: AgentId, bid: Option<u32>, broken: bool }
+
+impl Worker {
+    fn new(bid: Option<u32>, broken: bool) -> Self { Self { id: AgentId::new(), bid, broken } }
+
+    fn work(&self, task: &str) -> Result<String, String> {
+        match self.broken {
+            true => Err("broke down".to_string()),
+            false => Ok(format!("did {}", task)),
+        }
+    }
+}
+
+#[async_trait]
+impl Agent for Worker {
+    fn id(&self) -> &AgentId { &self.id }
+    async fn initialize(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
+    async fn execute(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
+    async fn shutdown(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
+    async fn handle_message(&mut self, ctx: &AgentContext, s: &str, p: &str, content: &str) -> AgentResult<()> {
+        self.handle_contract_net(ctx, s, p, content).await?;
+        self.handle_request(ctx, s, p, content).await?;
+        self.handle_query_if(ctx, s, p, content).await
+    }
+}
+
+#[async_trait]
+impl ContractNetParticipant for Worker {
+    async fn propose(&mut self, _ctx: &AgentContext, _initiator: &str, _task: &str) -> Option<String> {
+        self.bid.map(|bid| bid.to_string())
+    }
+
+    async fn perform(&mut self, _ctx: &AgentContext, _initiator: &str, proposal: &str) -> Result<String, String> {
+        self.work(&format!("it for {}", proposal))
+    }
+}
+
+#[async_trait]
+impl RequestParticipant for Worker {
+    async fn agree(&mut self, _ctx: &AgentContext, _initiator: &str, action: &str) -> Result<(), String> {
+        match action {
+            "fly" => Err("cannot fly".to_string()),
+            _ => Ok(()),
+        }
+    }
+
+    async fn perform_request(&mut self, _ctx: &AgentContext, _initiator: &str, action: &str) -> Result<String, String> {
+        self.work(action)
+    }
+}
+
+#[async_trait]
+impl QueryParticipant for Worker {
+    async fn query_if(&mut self, _ctx: &AgentContext, _initiator: &str, proposition: &str) -> Result<bool, String> {
+        match proposition {
+            "the sky is blue" => Ok(true),
+            "the meaning of life" => Err("no idea".to_string()),
+            _ => Ok(false),
+        }
+    }
+}
+
+/// Answers everything with a confirm, which no protocol here allows.
+struct Rude { id: AgentId }
+
+#[async_trait]
+impl Agent for Rude {
+    fn id(&self) -> &AgentId { &self.id }
+    async fn initialize(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
+    async fn execute(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
+    async fn shutdown(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
+    async fn handle_message(&mut self, ctx: &AgentContext, _s: &str, _p: &str, content: &str) -> AgentResult<()> {
+        ctx.reply(Performative::Confirm, content)?;
+        Ok(())
+    }
+}
+
+/// Ignores everything.
+struct Mute { id: AgentId }
+
+#[async_trait]
+impl Agent for Mute {
+    fn id(&self) -> &AgentId { &self.id }
+    async fn initialize(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
+    async fn execute(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
+    async fn shutdown(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
+}
+
+/// Spawn the cast, run `start` from an initiator and return what it produced.
+fn play<T, F, Fut>(start: F) -> T
+where
+    T: Send + 'static,
+    F: FnOnce() -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = T> + Send + 'static,
+{
+    let result = Arc::new(Mutex::new(None));
+    let slot = result.clone();
+    let start: Start = Box::new(move || {
+        let protocol = start();
+        Box::pin(async move { *slot.lock().unwrap() = Some(protocol.await) })
+    });
+    let scenario = async {
+        let runtime = Runtime::new();
+        let board = Switchboard::new();
+        for (name, bid, broken) in [("cheap", Some(3), false), ("dear", Some(9), false), ("lazy", None, false)] {
+            board.spawn_reactive(&runtime, Box::new(Worker::new(bid, broken)), name).await?;
+        }
+        board.spawn_reactive(&runtime, Box::new(Worker::new(Some(5), true)), "broken").await?;
+        board.spawn_reactive(&runtime, Box::new(Rude { id: AgentId::new() }), "rude").await?;
+        board.spawn_reactive(&runtime, Box::new(Mute { id: AgentId::new() }), "mute").await?;
+        board.spawn(&runtime, Box::new(Initiator { id: AgentId::new(), start: Some(start) }), "boss").await?;
+        let _ = runtime.run_until(|| result.lock().unwrap().is_some(), Duration::from_secs(30)).await;
+        runtime.shutdown().await?;
+        Ok::<_, RuntimeError>(())
+    };
+    let (outcome, _) = Simulation::new(1).run(scenario);
+    outcome.expect("scenario failed");
+    let played = result.lock().unwrap().take();
+    played.expect("protocol never finished")
+}
+
+fn senders(replies: &[agentropic_examples::Reply]) -> Vec<&str> {
+    let mut senders: Vec<&str> = replies.iter().map(|reply| reply.sender.as_str()).collect();
+    senders.sort();
+    senders
+}
+
+#[test]
+fn contract_net_runs_to_an_outcome() {
+    let outcome = play(|| {
+        ContractNet::new("paint")
+            .with_participants(&["cheap", "dear", "lazy", "broken", "rude", "mute", "nobody"])
+            .with_deadline(Duration::from_secs(1))
+            .start(|proposals| {
+                let cheap = proposals.iter().filter(|proposal| proposal.content.parse::<u32>().unwrap() < 9);
+                cheap.map(|proposal| proposal.sender.clone()).collect()
+            })
+    })
+    .expect("contract net failed");
+
+    assert_eq!(senders(&outcome.proposals), ["broken", "cheap", "dear"]);
+    assert_eq!(senders(&outcome.refusals), ["lazy"]);
+    let mut accepted = outcome.accepted.clone();
+    accepted.sort();
+    assert_eq!(accepted, ["broken", "cheap"]);
+    assert_eq!(outcome.results.len(), 1);
+    assert_eq!(outcome.results[0].content, "did it for 3");
+    assert_eq!(senders(&outcome.failures), ["broken"]);
+    assert_eq!(outcome.silent, ["mute"]);
+    assert_eq!(outcome.unreachable, [("nobody".to_string(), SendError::UnknownRecipient("nobody".into()))]);
+    assert_eq!(senders(&outcome.violations), ["rude"]);
+}
+
+#[test]
+fn request_action_reports_each_ending() {
+    let (done, failed, refused, rude, mute) = play(|| {
+        let done = RequestAction::new("cheap", "paint").start();
+        let failed = RequestAction::new("broken", "paint").start();
+        let refused = RequestAction::new("cheap", "fly").start();
+        let rude = RequestAction::new("rude", "paint").start();
+        let mute = RequestAction::new("mute", "paint").with_deadline(Duration::from_secs(1)).start();
+        async { tokio::join!(done, failed, refused, rude, mute) }
+    });
+
+    assert!(matches!(done, Ok(Outcome::Done(reply)) if reply.content == "did paint"));
+    assert!(matches!(failed, Ok(Outcome::Failed(reply)) if reply.content == "broke down"));
+    assert!(matches!(refused, Ok(Outcome::Refused(reply)) if reply.content == "cannot fly"));
+    assert!(matches!(rude, Err(RequestError::Unexpected(reply)) if reply.performative == Performative::Confirm));
+    assert!(matches!(mute, Err(RequestError::Timeout { .. })));
+}
+
+#[test]
+fn query_if_answers_true_or_false() {
+    let (yes, no, refused) = play(|| {
+        let yes = QueryIf::new("cheap", "the sky is blue").start();
+        let no = QueryIf::new("cheap", "the grass is blue").start();
+        let refused = QueryIf::new("cheap", "the meaning of life").start();
+        async { tokio::join!(yes, no, refused) }
+    });
+
+    assert_eq!(yes, Ok(Outcome::Done(true)));
+    assert_eq!(no, Ok(Outcome::Done(false)));
+    assert!(matches!(refused, Ok(Outcome::Refused(reply)) if reply.content == "no idea"));
+}
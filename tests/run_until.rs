@@ -0,0 +1,55 @@
+//! Running a scenario until a condition holds, and giving up when it never does.
+use agentropic_core::{Agent, AgentContext, AgentId, AgentResult};
+use agentropic_examples::{ContextExt, RunTimeout, RuntimeExt, Simulation, Switchboard};
+use agentropic_runtime::prelude::*;
+use async_trait::async_trait;
+use std::time::Duration;
+
+/// Says it has work outstanding and never finishes it.
+struct Busybody { id: AgentId }
+
+#[async_trait]
+impl Agent for Busybody {
+    fn id(&self) -> &AgentId { &self.id }
+    async fn initialize(&mut self, ctx: &AgentContext) -> AgentResult<()> {
+        ctx.set_busy(true)?;
+        Ok(())
+    }
+    async fn execute(&mut self, _ctx: &AgentContext) -> AgentResult<()> {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        Ok(())
+    }
+    async fn shutdown(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
+}
+
+#[test]
+fn a_condition_that_never_holds_times_out() {
+    let scenario = async {
+        let runtime = Runtime::new();
+        let started = tokio::time::Instant::now();
+        let outcome = runtime.run_until(|| false, Duration::from_secs(2)).await;
+        (outcome, started.elapsed())
+    };
+    let ((outcome, waited), _) = Simulation::new(1).run(scenario);
+
+    let err = outcome.unwrap_err();
+    assert_eq!(err, RunTimeout { waited: Duration::from_secs(2), condition: "done" });
+    assert_eq!(err.to_string(), "agents were not done after 2s");
+    assert!(waited >= Duration::from_secs(2) && waited < Duration::from_millis(2100), "{:?}", waited);
+}
+
+#[test]
+fn a_busy_agent_keeps_the_switchboard_from_going_idle() {
+    let scenario = async {
+        let runtime = Runtime::new();
+        let board = Switchboard::new();
+        board.spawn(&runtime, Box::new(Busybody { id: AgentId::new() }), "busybody").await?;
+        let outcome = runtime.run_until_idle(&board, Duration::from_secs(3)).await;
+        runtime.shutdown().await?;
+        Ok::<_, RuntimeError>(outcome)
+    };
+    let (outcome, _) = Simulation::new(1).run(scenario);
+
+    let err = outcome.expect("scenario failed").unwrap_err();
+    assert_eq!(err, RunTimeout { waited: Duration::from_secs(3), condition: "idle" });
+}
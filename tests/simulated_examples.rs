@@ -0,0 +1,133 @@
+//! Every example that runs without an LLM, driven on a simulated clock.
+//!
+//! `cognitive_agent` and `full_system` are left out: they call a real LLM
+//! over the network, which a paused clock would time out immediately.
+use agentropic_examples::Simulation;
+use agentropic_runtime::prelude::RuntimeError;
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+#[allow(dead_code)]
+#[path = "../examples/hello_agent.rs"]
+mod hello_agent;
+#[allow(dead_code)]
+#[path = "../examples/messaging.rs"]
+mod messaging;
+#[allow(dead_code)]
+#[path = "../examples/agents_talking.rs"]
+mod agents_talking;
+#[allow(dead_code)]
+#[path = "../examples/supervised_agents.rs"]
+mod supervised_agents;
+#[allow(dead_code)]
+#[path = "../examples/team_pattern.rs"]
+mod team_pattern;
+#[allow(dead_code)]
+#[path = "../examples/hierarchy_pattern.rs"]
+mod hierarchy_pattern;
+#[allow(dead_code)]
+#[path = "../examples/swarm_pattern.rs"]
+mod swarm_pattern;
+#[allow(dead_code)]
+#[path = "../examples/coalition_pattern.rs"]
+mod coalition_pattern;
+#[allow(dead_code)]
+#[path = "../examples/market_pattern.rs"]
+mod market_pattern;
+#[allow(dead_code)]
+#[path = "../examples/federation_pattern.rs"]
+mod federation_pattern;
+#[allow(dead_code)]
+#[path = "../examples/holarchy_pattern.rs"]
+mod holarchy_pattern;
+#[allow(dead_code)]
+#[path = "../examples/blackboard_pattern.rs"]
+mod blackboard_pattern;
+
+const SEED: u64 = 7;
+
+fn simulate<F: Future<Output = Result<(), RuntimeError>>>(scenario: F) -> Vec<String> {
+    let started = Instant::now();
+    let (result, trace) = Simulation::new(SEED).run(scenario);
+    result.expect("scenario failed");
+    assert!(started.elapsed() < Duration::from_secs(5), "simulation ran on the wall clock");
+    trace.lines()
+}
+
+#[test]
+fn hello_agent() {
+    let trace = simulate(hello_agent::run());
+    // A 500ms periodic timer fires at 0.5s through 2.5s before the 3s shutdown
+    assert_eq!(trace.iter().filter(|line| line.contains("timer -> greeter [inform] tick")).count(), 5);
+}
+
+#[test]
+fn messaging() {
+    let trace = simulate(messaging::run());
+    assert!(trace.iter().any(|line| line.contains("ping -> pong [request] ping!")));
+}
+
+#[test]
+fn agents_talking() {
+    let trace = simulate(agents_talking::run());
+    assert!(trace.iter().any(|line| line.contains("asker -> responder [query]")));
+    assert!(trace.iter().any(|line| line.contains("responder -> asker [inform]")));
+}
+
+#[test]
+fn supervised_agents() {
+    simulate(supervised_agents::run());
+}
+
+#[test]
+fn team_pattern() {
+    let trace = simulate(team_pattern::run());
+    // Every completion reaches the leader before the demo shuts down
+    assert_eq!(trace.iter().filter(|line| line.contains("-> leader [inform]")).count(), 6);
+}
+
+#[test]
+fn hierarchy_pattern() {
+    simulate(hierarchy_pattern::run());
+}
+
+#[test]
+fn swarm_pattern() {
+    let trace = simulate(swarm_pattern::run());
+    // Five scouts publish to the other four.
+    assert_eq!(trace.iter().filter(|line| line.contains("vote:")).count(), 20);
+}
+
+#[test]
+fn coalition_pattern() {
+    simulate(coalition_pattern::run());
+}
+
+#[test]
+fn market_pattern() {
+    let trace = simulate(market_pattern::run());
+    assert_eq!(trace.iter().filter(|line| line.contains("-> auctioneer [propose]")).count(), 3);
+}
+
+#[test]
+fn federation_pattern() {
+    simulate(federation_pattern::run());
+}
+
+#[test]
+fn holarchy_pattern() {
+    simulate(holarchy_pattern::run());
+}
+
+#[test]
+fn blackboard_pattern() {
+    simulate(blackboard_pattern::run());
+}
+
+#[test]
+fn same_seed_gives_identical_trace() {
+    let first = simulate(market_pattern::run());
+    let second = simulate(market_pattern::run());
+    assert!(!first.is_empty());
+    assert_eq!(first, second);
+}
@@ -0,0 +1,400 @@
+//! Supervision trees and recovery policies restarting and giving up on
+//! managed agents, the state restarted agents come back with, and calls
+//! that time out.
+use agentropic_core::{Agent, AgentContext, AgentError, AgentId, AgentResult};
+use agentropic_examples::{
+    Checkpointed, Circuit, ContextExt, ExitStatus, Lifecycle, Performative, Phase, Recovery, RestartState,
+    RuntimeExt, Simulation, SpawnOptions, Stateful, Strategy, Supervisor, Switchboard, TimedOut, Timeouts,
+    CIRCUIT_TOPIC,
+};
+use agentropic_runtime::prelude::*;
+use async_trait::async_trait;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+type Starts = Arc<Mutex<BTreeMap<&'static str, u32>>>;
+
+/// Counts how often it is initialized, and fails its first `failures` executions.
+struct Worker { id: AgentId, name: &'static str, starts: Starts, failures: u32 }
+
+impl Worker {
+    fn boxed(name: &'static str, starts: &Starts, failures: u32) -> Box<dyn Agent> {
+        Box::new(Self { id: AgentId::new(), name, starts: starts.clone(), failures })
+    }
+}
+
+#[async_trait]
+impl Agent for Worker {
+    fn id(&self) -> &AgentId { &self.id }
+    async fn initialize(&mut self, _ctx: &AgentContext) -> AgentResult<()> {
+        *self.starts.lock().unwrap().entry(self.name).or_default() += 1;
+        Ok(())
+    }
+    async fn execute(&mut self, _ctx: &AgentContext) -> AgentResult<()> {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        if self.failures > 0 {
+            self.failures -= 1;
+            return Err(AgentError::ExecutionFailed(format!("{} broke", self.name)));
+        }
+        Ok(())
+    }
+    async fn shutdown(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
+}
+
+/// Spawn `tree`, let it run for a few seconds and report how often each agent started.
+fn starts_under(tree: impl FnOnce(&Starts) -> Supervisor) -> Vec<(&'static str, u32)> {
+    let starts = Starts::default();
+    let supervisor = tree(&starts);
+    let scenario = async {
+        let runtime = Runtime::new();
+        let board = Switchboard::new();
+        board.spawn_supervisor(&runtime, supervisor).await?;
+        tokio::time::sleep(Duration::from_secs(3)).await;
+        runtime.shutdown().await?;
+        Ok::<_, RuntimeError>(())
+    };
+    let (outcome, _) = Simulation::new(1).run(scenario);
+    outcome.expect("scenario failed");
+    let starts = starts.lock().unwrap().clone();
+    starts.into_iter().collect()
+}
+
+#[test]
+fn one_for_one_restarts_only_the_failed_child() {
+    let starts = starts_under(|starts| {
+        Supervisor::new("root", Strategy::OneForOne)
+            .child("a", Worker::boxed("a", starts, 0))
+            .child("b", Worker::boxed("b", starts, 2))
+            .child("c", Worker::boxed("c", starts, 0))
+    });
+    assert_eq!(starts, [("a", 1), ("b", 3), ("c", 1)]);
+}
+
+#[test]
+fn one_for_all_restarts_every_child() {
+    let starts = starts_under(|starts| {
+        Supervisor::new("root", Strategy::OneForAll)
+            .child("a", Worker::boxed("a", starts, 0))
+            .child("b", Worker::boxed("b", starts, 1))
+            .child("c", Worker::boxed("c", starts, 0))
+    });
+    assert_eq!(starts, [("a", 2), ("b", 2), ("c", 2)]);
+}
+
+#[test]
+fn rest_for_one_restarts_later_children() {
+    let starts = starts_under(|starts| {
+        Supervisor::new("root", Strategy::RestForOne)
+            .child("a", Worker::boxed("a", starts, 0))
+            .child("b", Worker::boxed("b", starts, 1))
+            .supervisor(Supervisor::new("nested", Strategy::OneForOne).child("c", Worker::boxed("c", starts, 0)))
+    });
+    assert_eq!(starts, [("a", 1), ("b", 2), ("c", 2)]);
+}
+
+#[test]
+fn exceeding_the_restart_intensity_escalates_to_the_parent() {
+    let starts = Starts::default();
+    let inner = Supervisor::new("inner", Strategy::OneForOne)
+        .with_intensity(1, Duration::from_secs(60))
+        .child("doomed", Worker::boxed("doomed", &starts, u32::MAX))
+        .child("buddy", Worker::boxed("buddy", &starts, 0));
+    let root = Supervisor::new("root", Strategy::OneForOne)
+        .with_intensity(1, Duration::from_secs(60))
+        .supervisor(inner)
+        .child("bystander", Worker::boxed("bystander", &starts, 0));
+    let scenario = async {
+        let runtime = Runtime::new();
+        let board = Switchboard::new();
+        board.spawn_supervisor(&runtime, root).await?;
+        let _ = runtime.run_until(|| board.exit_status("bystander").is_some(), Duration::from_secs(10)).await;
+        let statuses: Vec<_> = ["doomed", "buddy", "bystander"].map(|name| board.exit_status(name)).into();
+        runtime.shutdown().await?;
+        Ok::<_, RuntimeError>(statuses)
+    };
+    let (outcome, _) = Simulation::new(1).run(scenario);
+    let statuses = outcome.expect("scenario failed");
+
+    // inner restarts doomed, then gives up; root restarts inner, which
+    // restarts doomed once more and gives up again, and root gives up too
+    let reason = "supervisor \"root\" gave up after 1 restarts: \
+        supervisor \"inner\" gave up after 1 restarts: \"doomed\" failed";
+    assert_eq!(statuses, vec![Some(ExitStatus::Failed(reason.to_string())); 3]);
+    let starts: Vec<_> = starts.lock().unwrap().clone().into_iter().collect();
+    assert_eq!(starts, [("buddy", 2), ("bystander", 1), ("doomed", 4)]);
+}
+
+type Seen = Arc<Mutex<BTreeMap<&'static str, Vec<u32>>>>;
+
+/// Counts its executions, records the count whenever it is initialized and
+/// fails once, after counting its third execution.
+struct Counter { id: AgentId, name: &'static str, count: u32, crashed: bool, seen: Seen }
+
+#[async_trait]
+impl Agent for Counter {
+    fn id(&self) -> &AgentId { &self.id }
+    async fn initialize(&mut self, _ctx: &AgentContext) -> AgentResult<()> {
+        self.seen.lock().unwrap().entry(self.name).or_default().push(self.count);
+        Ok(())
+    }
+    async fn execute(&mut self, _ctx: &AgentContext) -> AgentResult<()> {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        self.count += 1;
+        if self.count == 3 && !self.crashed {
+            self.crashed = true;
+            return Err(AgentError::ExecutionFailed(format!("{} broke", self.name)));
+        }
+        Ok(())
+    }
+    async fn shutdown(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
+}
+
+impl Stateful for Counter {
+    type State = u32;
+    fn snapshot(&self) -> u32 { self.count }
+    fn restore(&mut self, count: u32) { self.count = count; }
+}
+
+#[test]
+fn checkpointed_agents_restart_with_the_chosen_state() {
+    let seen = Seen::default();
+    let counter = |name| {
+        let counter = Counter { id: AgentId::new(), name, count: 0, crashed: false, seen: seen.clone() };
+        Box::new(Checkpointed::new(counter))
+    };
+    let supervisor = Supervisor::new("root", Strategy::OneForOne)
+        .child("kept", counter("kept"))
+        .child_with("fresh", counter("fresh"), SpawnOptions::new().with_restart_state(RestartState::Fresh));
+    let scenario = async {
+        let runtime = Runtime::new();
+        let board = Switchboard::new();
+        board.spawn_supervisor(&runtime, supervisor).await?;
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        runtime.shutdown().await?;
+        Ok::<_, RuntimeError>(())
+    };
+    let (outcome, _) = Simulation::new(1).run(scenario);
+    outcome.expect("scenario failed");
+
+    // The failed third execution is rolled back, not kept
+    let seen: Vec<_> = seen.lock().unwrap().clone().into_iter().collect();
+    assert_eq!(seen, [("fresh", vec![0, 0]), ("kept", vec![0, 2])]);
+}
+
+#[test]
+fn recovery_backs_off_exponentially_up_to_the_maximum() {
+    let recovery = Recovery::new().with_backoff(Duration::from_millis(100), Duration::from_millis(500));
+    let delays: Vec<_> = (1..=5).map(|attempt| recovery.backoff(attempt).as_millis()).collect();
+    assert_eq!(delays, [100, 200, 400, 500, 500]);
+}
+
+type Notices = Arc<Mutex<Vec<(String, String, String)>>>;
+
+/// Records every message it receives.
+struct Watcher { id: AgentId, notices: Notices }
+
+#[async_trait]
+impl Agent for Watcher {
+    fn id(&self) -> &AgentId { &self.id }
+    async fn initialize(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
+    async fn execute(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
+    async fn shutdown(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
+    async fn handle_message(&mut self, _ctx: &AgentContext, s: &str, p: &str, content: &str) -> AgentResult<()> {
+        self.notices.lock().unwrap().push((s.to_string(), p.to_string(), content.to_string()));
+        Ok(())
+    }
+}
+
+#[test]
+fn circuit_opens_after_repeated_failures_and_closes_once_recovered() {
+    let starts = Starts::default();
+    let notices = Notices::default();
+    let recovery = Recovery::new()
+        .with_backoff(Duration::from_millis(100), Duration::from_secs(1))
+        .with_jitter(0.0)
+        .with_failure_window(3, Duration::from_secs(60))
+        .with_cooldown(Duration::from_secs(1));
+    let scenario = async {
+        let runtime = Runtime::new();
+        let board = Switchboard::new();
+        board.subscribe(CIRCUIT_TOPIC, "watcher");
+        let watcher = Watcher { id: AgentId::new(), notices: notices.clone() };
+        board.spawn_reactive(&runtime, Box::new(watcher), "watcher").await?;
+        let options = SpawnOptions::new().with_recovery(recovery);
+        board.spawn_with(&runtime, Worker::boxed("flaky", &starts, 4), "flaky", options).await?;
+        let _ = runtime.run_until(|| board.circuit("flaky") == Some(Circuit::Open), Duration::from_secs(5)).await;
+        let opened = board.circuit("flaky");
+        let _ = runtime.run_until(|| notices.lock().unwrap().len() == 2, Duration::from_secs(10)).await;
+        let closed = board.circuit("flaky");
+        runtime.shutdown().await?;
+        Ok::<_, RuntimeError>((opened, closed))
+    };
+    let (outcome, _) = Simulation::new(1).run(scenario);
+    let (opened, closed) = outcome.expect("scenario failed");
+
+    assert_eq!(opened, Some(Circuit::Open));
+    assert_eq!(closed, Some(Circuit::Closed));
+    // Two restarts with backoff, then one try after each of two cooldowns
+    assert_eq!(starts.lock().unwrap().get("flaky"), Some(&5));
+    let notices = notices.lock().unwrap().clone();
+    let kinds: Vec<_> = notices.iter().map(|(sender, perf, _)| (sender.as_str(), perf.as_str())).collect();
+    assert_eq!(kinds, [("flaky", "failure"), ("flaky", "inform")]);
+    assert_eq!(notices[1].2, "recovered");
+}
+
+/// Hangs in its first `execute` without ever looking at its cancellation,
+/// and stops itself in the next.
+struct Hanger { id: AgentId, starts: Starts, executed: u32 }
+
+#[async_trait]
+impl Agent for Hanger {
+    fn id(&self) -> &AgentId { &self.id }
+    async fn initialize(&mut self, _ctx: &AgentContext) -> AgentResult<()> {
+        *self.starts.lock().unwrap().entry("hanger").or_default() += 1;
+        Ok(())
+    }
+    async fn execute(&mut self, ctx: &AgentContext) -> AgentResult<()> {
+        self.executed += 1;
+        if self.executed == 1 {
+            std::future::pending::<()>().await;
+        }
+        ctx.stop_self()?;
+        Ok(())
+    }
+    async fn shutdown(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
+}
+
+#[test]
+fn a_hung_call_times_out_and_is_recovered_from() {
+    let starts = Starts::default();
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let scenario = async {
+        let runtime = Runtime::new();
+        let board = Switchboard::new();
+        let mut stream = board.events();
+        let collected = events.clone();
+        tokio::spawn(async move {
+            while let Some(event) = stream.next().await {
+                collected.lock().unwrap().push(event.event);
+            }
+        });
+        let options = SpawnOptions::new()
+            .with_timeouts(Timeouts::new().with_execute(Duration::from_secs(1)))
+            .with_recovery(Recovery::new().with_backoff(Duration::from_millis(100), Duration::from_secs(1)));
+        let hanger = Hanger { id: AgentId::new(), starts: starts.clone(), executed: 0 };
+        board.spawn_with(&runtime, Box::new(hanger), "hanger", options).await?;
+        let status = tokio::time::timeout(Duration::from_secs(5), board.join("hanger")).await;
+        runtime.shutdown().await?;
+        Ok::<_, RuntimeError>(status.ok().flatten())
+    };
+    let (outcome, _) = Simulation::new(1).run(scenario);
+
+    assert_eq!(outcome.expect("scenario failed"), Some(ExitStatus::Completed));
+    assert_eq!(starts.lock().unwrap().get("hanger"), Some(&2));
+    let timed_out = TimedOut { phase: Phase::Execute, limit: Duration::from_secs(1) };
+    let events = events.lock().unwrap().clone();
+    let at = |event: &Lifecycle| events.iter().position(|seen| seen == event);
+    assert!(at(&Lifecycle::TimedOut(timed_out)) < at(&Lifecycle::Restarting), "{:?}", events);
+    assert!(at(&Lifecycle::Restarting).is_some(), "{:?}", events);
+}
+
+#[test]
+fn an_agent_that_stops_on_timeout_is_not_restarted_after_timing_out() {
+    let options = || {
+        let timeouts = Timeouts::new().with_execute(Duration::from_secs(1)).stop_on_timeout();
+        SpawnOptions::new().with_timeouts(timeouts)
+    };
+    let starts = starts_under(|starts| {
+        let hanger = Hanger { id: AgentId::new(), starts: starts.clone(), executed: 0 };
+        Supervisor::new("root", Strategy::OneForOne)
+            .child_with("hanger", Box::new(hanger), options())
+            .child_with("worker", Worker::boxed("worker", starts, 1), options())
+    });
+    // Both failed once, but only the one that did not time out was restarted
+    assert_eq!(starts, [("hanger", 1), ("worker", 2)]);
+}
+
+type Answers = Arc<Mutex<Vec<(Performative, String, Duration)>>>;
+
+/// Ponders every question until told to give up.
+struct Ponderer { id: AgentId, gave_up: Arc<Mutex<u32>> }
+
+#[async_trait]
+impl Agent for Ponderer {
+    fn id(&self) -> &AgentId { &self.id }
+    async fn initialize(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
+    async fn execute(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
+    async fn shutdown(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
+    async fn handle_message(&mut self, ctx: &AgentContext, _s: &str, _p: &str, _content: &str) -> AgentResult<()> {
+        ctx.cancellation().cancelled().await;
+        *self.gave_up.lock().unwrap() += 1;
+        Ok(())
+    }
+}
+
+/// Asks the ponderer one question and records the answer and how long it took.
+struct Asker { id: AgentId, asked: bool, answers: Answers }
+
+#[async_trait]
+impl Agent for Asker {
+    fn id(&self) -> &AgentId { &self.id }
+    async fn initialize(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
+    async fn execute(&mut self, ctx: &AgentContext) -> AgentResult<()> {
+        if !std::mem::replace(&mut self.asked, true) {
+            let started = tokio::time::Instant::now();
+            let answer = ctx.request("ponderer", Performative::Query, "why?", Duration::from_secs(10));
+            let answers = self.answers.clone();
+            tokio::spawn(async move {
+                if let Ok(reply) = answer.await {
+                    answers.lock().unwrap().push((reply.performative, reply.content, started.elapsed()));
+                }
+            });
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        Ok(())
+    }
+    async fn shutdown(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
+}
+
+#[test]
+fn a_timed_out_handler_is_cancelled_and_the_requester_told() {
+    let answers = Answers::default();
+    let gave_up = Arc::new(Mutex::new(0));
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let scenario = async {
+        let runtime = Runtime::new();
+        let board = Switchboard::new();
+        let mut stream = board.events();
+        let collected = events.clone();
+        tokio::spawn(async move {
+            while let Some(event) = stream.next().await {
+                collected.lock().unwrap().push(event.event);
+            }
+        });
+        let ponderer = Ponderer { id: AgentId::new(), gave_up: gave_up.clone() };
+        let timeouts = Timeouts::new().with_handle_message(Duration::from_millis(500));
+        let options = SpawnOptions::new().reactive().with_timeouts(timeouts);
+        board.spawn_with(&runtime, Box::new(ponderer), "ponderer", options).await?;
+        let asker = Asker { id: AgentId::new(), asked: false, answers: answers.clone() };
+        board.spawn(&runtime, Box::new(asker), "asker").await?;
+        let _ = runtime.run_until(|| !answers.lock().unwrap().is_empty(), Duration::from_secs(5)).await;
+        runtime.shutdown().await
+    };
+    let (outcome, _) = Simulation::new(1).run(scenario);
+    outcome.expect("scenario failed");
+
+    // It noticed the cancellation within the grace period and returned
+    assert_eq!(*gave_up.lock().unwrap(), 1);
+    let answers = answers.lock().unwrap().clone();
+    assert_eq!(answers.len(), 1);
+    let (performative, content, waited) = &answers[0];
+    assert_eq!(*performative, Performative::Failure);
+    assert_eq!(content, "handle_message timed out after 500ms");
+    assert!(*waited < Duration::from_secs(1), "{:?}", waited);
+    // Only the message failed; the agent carried on
+    let timed_out = TimedOut { phase: Phase::HandleMessage, limit: Duration::from_millis(500) };
+    let events = events.lock().unwrap().clone();
+    assert!(events.contains(&Lifecycle::TimedOut(timed_out)), "{:?}", events);
+    assert!(!events.iter().any(|event| matches!(event, Lifecycle::Failed(_) | Lifecycle::Restarting)), "{:?}", events);
+}
@@ -0,0 +1,107 @@
+//! Reactive agents stay parked until something arrives, and timers wake them.
+use agentropic_core::{Agent, AgentContext, AgentId, AgentResult};
+use agentropic_examples::{ContextExt, Simulation, Switchboard, TIMER};
+use agentropic_runtime::prelude::*;
+use async_trait::async_trait;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Counts its `execute` calls and the timer events it hears, after
+/// scheduling `every` in `initialize` if set. Takes `stall` over the first tick.
+struct Counter {
+    id: AgentId,
+    every: Option<Duration>,
+    stall: Duration,
+    executes: Arc<AtomicU32>,
+    ticks: Arc<AtomicU32>,
+}
+
+impl Counter {
+    fn new(every: Option<Duration>) -> Self {
+        Self { id: AgentId::new(), every, stall: Duration::ZERO, executes: Arc::default(), ticks: Arc::default() }
+    }
+
+    fn stalling(self, stall: Duration) -> Self {
+        Self { stall, ..self }
+    }
+}
+
+#[async_trait]
+impl Agent for Counter {
+    fn id(&self) -> &AgentId { &self.id }
+    async fn initialize(&mut self, ctx: &AgentContext) -> AgentResult<()> {
+        if let Some(every) = self.every {
+            ctx.schedule_every(every, "tick")?;
+        }
+        Ok(())
+    }
+    async fn execute(&mut self, _ctx: &AgentContext) -> AgentResult<()> {
+        self.executes.fetch_add(1, Ordering::SeqCst);
+        Ok(())
+    }
+    async fn shutdown(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
+    async fn handle_message(&mut self, _ctx: &AgentContext, sender: &str, _p: &str, _c: &str) -> AgentResult<()> {
+        if sender == TIMER && self.ticks.fetch_add(1, Ordering::SeqCst) == 0 {
+            tokio::time::sleep(self.stall).await;
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn an_idle_reactive_agent_is_not_polled() {
+    let counter = Counter::new(None);
+    let executes = counter.executes.clone();
+    let scenario = async {
+        let runtime = Runtime::new();
+        let board = Switchboard::new();
+        board.spawn_reactive(&runtime, Box::new(counter), "idler").await?;
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        let started = executes.load(Ordering::SeqCst);
+        tokio::time::sleep(Duration::from_secs(60)).await;
+        let idle = executes.load(Ordering::SeqCst) - started;
+        runtime.shutdown().await?;
+        Ok::<_, RuntimeError>(idle)
+    };
+    let (outcome, _) = Simulation::new(1).run(scenario);
+    assert_eq!(outcome.expect("scenario failed"), 0);
+}
+
+#[test]
+fn a_zero_period_repeats_every_millisecond() {
+    let heard = ticks_within(Counter::new(Some(Duration::ZERO)), Duration::from_millis(100));
+    assert!((90..=100).contains(&heard), "heard {} ticks in 100ms", heard);
+}
+
+/// Timer events `counter` hears in its first `span`.
+fn ticks_within(counter: Counter, span: Duration) -> u32 {
+    let ticks = counter.ticks.clone();
+    let scenario = async {
+        let runtime = Runtime::new();
+        let board = Switchboard::new();
+        board.spawn_reactive(&runtime, Box::new(counter), "ticker").await?;
+        // Let the agent handle whatever is already due before the clock moves
+        for _ in 0..50 {
+            tokio::task::yield_now().await;
+        }
+        tokio::time::sleep(span).await;
+        let heard = ticks.load(Ordering::SeqCst);
+        runtime.shutdown().await?;
+        Ok::<_, RuntimeError>(heard)
+    };
+    let (outcome, _) = Simulation::new(1).run(scenario);
+    outcome.expect("scenario failed")
+}
+
+#[test]
+fn a_zero_period_waits_a_millisecond_before_the_first_tick() {
+    assert_eq!(ticks_within(Counter::new(Some(Duration::ZERO)), Duration::ZERO), 0);
+}
+
+#[test]
+fn ticks_missed_while_stalled_are_skipped() {
+    let counter = Counter::new(Some(Duration::from_secs(1))).stalling(Duration::from_millis(10_500));
+    // One tick at 1s, one for all the periods missed when the stall ends, then one each second from 12s
+    assert_eq!(ticks_within(counter, Duration::from_millis(30_500)), 21);
+}
@@ -0,0 +1,104 @@
+//! Broadcasts, topics and pattern groups between managed agents, run on
+//! the simulated clock.
+use agentropic_core::{Agent, AgentContext, AgentId, AgentResult};
+use agentropic_examples::{ContextExt, Performative, RuntimeExt, Simulation, Switchboard};
+use agentropic_patterns::swarm::Swarm;
+use agentropic_runtime::prelude::*;
+use async_trait::async_trait;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+type Heard = Arc<Mutex<Vec<(&'static str, String)>>>;
+
+/// What a listener does as it starts.
+#[derive(Clone, Copy)]
+enum Start { Stay, Leave, Die }
+
+/// Records what it hears, after leaving "news" and "swarm" or stopping if told to.
+struct Listener { id: AgentId, name: &'static str, start: Start, heard: Heard }
+
+#[async_trait]
+impl Agent for Listener {
+    fn id(&self) -> &AgentId { &self.id }
+    async fn initialize(&mut self, ctx: &AgentContext) -> AgentResult<()> {
+        match self.start {
+            Start::Stay => {}
+            Start::Leave => {
+                ctx.unsubscribe("news")?;
+                ctx.unsubscribe("swarm")?;
+            }
+            Start::Die => ctx.stop_self()?,
+        }
+        Ok(())
+    }
+    async fn execute(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
+    async fn shutdown(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
+    async fn handle_message(&mut self, _ctx: &AgentContext, _s: &str, _p: &str, content: &str) -> AgentResult<()> {
+        self.heard.lock().unwrap().push((self.name, content.to_string()));
+        Ok(())
+    }
+}
+
+/// Once everyone has started, broadcasts, then publishes to "news" and
+/// "swarm", recording how many receivers each reached.
+struct Caller { id: AgentId, ticks: u32, reached: Arc<Mutex<Vec<usize>>> }
+
+#[async_trait]
+impl Agent for Caller {
+    fn id(&self) -> &AgentId { &self.id }
+    async fn initialize(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
+    async fn execute(&mut self, ctx: &AgentContext) -> AgentResult<()> {
+        self.ticks += 1;
+        if self.ticks == 3 {
+            let reached = vec![
+                ctx.broadcast(Performative::Inform, "everyone")?,
+                ctx.publish("news", Performative::Inform, "news")?,
+                ctx.publish("swarm", Performative::Inform, "swarm")?,
+            ];
+            *self.reached.lock().unwrap() = reached;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        Ok(())
+    }
+    async fn shutdown(&mut self, _ctx: &AgentContext) -> AgentResult<()> { Ok(()) }
+}
+
+#[test]
+fn senders_leavers_and_the_dead_are_left_out() {
+    let heard = Heard::default();
+    let reached = Arc::new(Mutex::new(Vec::new()));
+    let scenario = async {
+        let runtime = Runtime::new();
+        let board = Switchboard::new();
+        let mut swarm = Swarm::new("Listeners");
+        let listeners = [("stayer", Start::Stay), ("leaver", Start::Leave), ("dier", Start::Die)];
+        for (name, start) in listeners.into_iter().chain([("outsider", Start::Stay)]) {
+            let listener = Listener { id: AgentId::new(), name, start, heard: heard.clone() };
+            if name != "outsider" {
+                board.subscribe("news", name);
+                swarm.add_member(listener.id);
+            }
+            board.spawn_reactive(&runtime, Box::new(listener), name).await?;
+        }
+        let caller = Caller { id: AgentId::new(), ticks: 0, reached: reached.clone() };
+        swarm.add_member(caller.id);
+        board.group("swarm", &swarm);
+        board.spawn(&runtime, Box::new(caller), "caller").await?;
+        let _ = runtime.run_until_idle(&board, Duration::from_secs(5)).await;
+        runtime.shutdown().await
+    };
+    let (outcome, _) = Simulation::new(1).run(scenario);
+    outcome.expect("scenario failed");
+
+    assert_eq!(*reached.lock().unwrap(), [3, 1, 1]);
+    let mut heard = heard.lock().unwrap().clone();
+    heard.sort();
+    let heard: Vec<_> = heard.iter().map(|(name, content)| (*name, content.as_str())).collect();
+    assert_eq!(heard, [
+        ("leaver", "everyone"),
+        ("outsider", "everyone"),
+        ("stayer", "everyone"),
+        ("stayer", "news"),
+        ("stayer", "swarm"),
+    ]);
+}